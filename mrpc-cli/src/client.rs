@@ -0,0 +1,153 @@
+use anyhow::{anyhow, bail, Context};
+use mrpc::{
+    descriptor::{MethodDescriptor, Route, ServerDescriptor, ServiceEntry},
+    net::{self, ClientConfig},
+    reflection,
+    sync::{mpsc, oneshot},
+    trace::Span,
+    Connection, Message,
+};
+use serde_json::Value;
+
+/// Where the server listens, parsed from `tcp://host:port`, `host:port`,
+/// `ws://..`, `wss://..` or `unix:/path`.
+#[derive(Clone, Debug)]
+pub enum Address {
+    Tcp(String),
+    WebSocket(String),
+    Unix(String),
+}
+
+impl std::str::FromStr for Address {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            Ok(Address::Tcp(addr.into()))
+        } else if s.starts_with("ws://") || s.starts_with("wss://") {
+            Ok(Address::WebSocket(s.into()))
+        } else if let Some(path) = s.strip_prefix("unix:") {
+            Ok(Address::Unix(path.trim_start_matches("//").into()))
+        } else if s.contains("://") {
+            bail!("unknown scheme in {}", s)
+        } else {
+            Ok(Address::Tcp(s.into()))
+        }
+    }
+}
+
+/// A client calling the methods of any server by their descriptor, with
+/// JSON values in place of the generated types.
+pub struct Client {
+    sender: mpsc::Sender<Message<Value, Value>>,
+    pub descriptor: ServerDescriptor,
+}
+
+impl Client {
+    pub async fn connect(address: &Address, config: ClientConfig) -> anyhow::Result<Self> {
+        let sender = match address {
+            Address::Tcp(addr) => net::tcp::writer_with_config(addr.as_str(), config).await,
+            Address::WebSocket(url) => net::websocket::writer_with_config(url, config).await,
+            #[cfg(unix)]
+            Address::Unix(path) => net::unix::writer_with_config(path, config).await,
+            #[cfg(not(unix))]
+            Address::Unix(_) => bail!("unix sockets are not supported on this platform"),
+        }
+        .with_context(|| format!("failed to connect {:?}", address))?;
+
+        Ok(Self {
+            sender,
+            descriptor: ServerDescriptor {
+                name: String::new(),
+                tagging: Default::default(),
+                services: Vec::new(),
+            },
+        })
+    }
+
+    /// Asks the server for its descriptor, see `#[mrpc::server(reflection)]`.
+    pub async fn reflect(&mut self) -> anyhow::Result<()> {
+        let route = reflection::describe_route();
+        let request = route
+            .request::<Value>(Value::Null)
+            .map_err(|e| anyhow!(e))?;
+        let response = self
+            .post(request)
+            .await
+            .context("failed to call the reflection service, pass --descriptor instead")?;
+        let value = route.output(response).map_err(|e| anyhow!(e))?;
+        self.descriptor = serde_json::from_value(value)?;
+        Ok(())
+    }
+
+    /// `Service.method` of every method.
+    pub fn method_paths(&self) -> Vec<String> {
+        self.descriptor
+            .services
+            .iter()
+            .flat_map(|entry| {
+                entry
+                    .service
+                    .methods
+                    .iter()
+                    .map(move |method| format!("{}.{}", entry.name, method.name))
+            })
+            .collect()
+    }
+
+    pub fn find(&self, path: &str) -> anyhow::Result<(&ServiceEntry, &MethodDescriptor)> {
+        let (service, method) = path
+            .split_once('.')
+            .ok_or_else(|| anyhow!("expected `service.method`, got `{}`", path))?;
+        let entry = self
+            .descriptor
+            .services
+            .iter()
+            .find(|entry| entry.name == service)
+            .ok_or_else(|| anyhow!("no service `{}`", service))?;
+        let method = entry
+            .service
+            .methods
+            .iter()
+            .find(|m| m.name == method || m.wire_name == method)
+            .ok_or_else(|| anyhow!("no method `{}` in `{}`", method, service))?;
+        Ok((entry, method))
+    }
+
+    /// Calls `Service.method` with `args`, an object of the arguments by
+    /// name or an array of them in order, and returns what it returned.
+    pub async fn call(&self, path: &str, args: Value) -> anyhow::Result<Value> {
+        let (entry, method) = self.find(path)?;
+        let route = Route::new(&self.descriptor, entry, method).map_err(|e| anyhow!(e))?;
+
+        let request = route.request::<Value>(args).map_err(|e| anyhow!(e))?;
+        let response = self.post(request).await?;
+        route.output(response).map_err(|e| anyhow!(e))
+    }
+
+    async fn post(&self, req: Value) -> anyhow::Result<Value> {
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .send(Message {
+                req,
+                resp: tx,
+                conn: Connection::new(None),
+                span: Span::none(),
+                metadata: Default::default(),
+            })
+            .await
+            .map_err(|_| anyhow!("connection closed"))?;
+        Ok(rx.await??)
+    }
+}
+
+/// `name(arg: Ty, ..) -> Output` of a method.
+pub fn signature(method: &MethodDescriptor) -> String {
+    let args = method
+        .args
+        .iter()
+        .map(|arg| format!("{}: {}", arg.name, arg.ty))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({}) -> {}", method.name, args, method.output)
+}
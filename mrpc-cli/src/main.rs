@@ -0,0 +1,183 @@
+//! `mrpc`, a client calling the methods of any mrpc server with JSON.
+
+mod client;
+mod repl;
+mod typescript;
+
+use std::{path::PathBuf, process::ExitCode};
+
+use anyhow::anyhow;
+use clap::{Parser, Subcommand};
+use mrpc::{
+    descriptor::ServerDescriptor,
+    net::{ClientConfig, Hello},
+};
+use serde_json::Value;
+use tokio::runtime::Runtime;
+
+use client::{Address, Client};
+use typescript::Language;
+
+#[derive(Parser)]
+#[command(name = "mrpc", about = "Calls the methods of an mrpc server")]
+struct Args {
+    /// `host:port`, `tcp://host:port`, `ws://..`, `wss://..` or `unix:/path`.
+    /// Only `typescript` with `--descriptor` doesn't need one.
+    address: Option<Address>,
+    /// Read the server descriptor from this file instead of asking the
+    /// reflection service of the server.
+    #[arg(long)]
+    descriptor: Option<PathBuf>,
+    /// Send a hello when connecting, for servers expecting a handshake.
+    #[arg(long)]
+    handshake: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the methods of the server.
+    List,
+    /// Call `service.method` and print what it returns.
+    Call {
+        method: String,
+        /// The arguments, as a JSON object by name or a JSON array.
+        #[arg(default_value = "null")]
+        args: String,
+    },
+    /// Read calls from the terminal, the default.
+    Repl,
+    /// Generate a TypeScript client for the server, which calls it over a
+    /// WebSocket.
+    Typescript {
+        /// Write the client to this file instead of the standard output.
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Generate an ES module without the types.
+        #[arg(long)]
+        javascript: bool,
+        /// Declare the Rust types from their JSON Schemas in this OpenRPC
+        /// document of the server, see `mrpc::openrpc`. They are `unknown`
+        /// otherwise.
+        #[arg(long)]
+        openrpc: Option<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let rt = match Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&rt, args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(rt: &Runtime, args: Args) -> anyhow::Result<()> {
+    let config = ClientConfig {
+        handshake: args.handshake.then(Hello::default),
+    };
+
+    let descriptor = match &args.descriptor {
+        Some(path) => Some(serde_json::from_slice(&std::fs::read(path)?)?),
+        None => None,
+    };
+    let command = args.command.unwrap_or(Command::Repl);
+
+    if let (
+        Command::Typescript {
+            out,
+            javascript,
+            openrpc,
+        },
+        Some(descriptor),
+    ) = (&command, &descriptor)
+    {
+        return typescript(descriptor, out, *javascript, openrpc);
+    }
+
+    let address = args
+        .address
+        .ok_or_else(|| anyhow!("the address of the server is required"))?;
+    let mut client = rt.block_on(Client::connect(&address, config))?;
+    match descriptor {
+        Some(descriptor) => client.descriptor = descriptor,
+        None => rt.block_on(client.reflect())?,
+    }
+
+    match command {
+        Command::List => list(&client),
+        Command::Call { method, args } => {
+            let args: Value = serde_json::from_str(&args)?;
+            let output = rt.block_on(client.call(&method, args))?;
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        Command::Repl => repl::run(rt, &client)?,
+        Command::Typescript {
+            out,
+            javascript,
+            openrpc,
+        } => typescript(&client.descriptor, &out, javascript, &openrpc)?,
+    }
+
+    Ok(())
+}
+
+fn typescript(
+    descriptor: &ServerDescriptor,
+    out: &Option<PathBuf>,
+    javascript: bool,
+    openrpc: &Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let language = if javascript {
+        Language::JavaScript
+    } else {
+        Language::TypeScript
+    };
+    let schemas = match openrpc {
+        Some(path) => {
+            let document: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+            match document.pointer("/components/schemas") {
+                Some(Value::Object(schemas)) => schemas.clone(),
+                _ => return Err(anyhow!("no components.schemas in {}", path.display())),
+            }
+        }
+        None => Default::default(),
+    };
+    let (code, unknown) = typescript::generate(descriptor, schemas, language)?;
+    if language == Language::TypeScript {
+        for name in unknown {
+            eprintln!(
+                "warning: `{}` has no JSON Schema, it is declared `unknown`",
+                name
+            );
+        }
+    }
+    match out {
+        Some(path) => std::fs::write(path, code)?,
+        None => print!("{}", code),
+    }
+    Ok(())
+}
+
+fn list(client: &Client) {
+    for entry in &client.descriptor.services {
+        for method in &entry.service.methods {
+            println!("{}.{}", entry.name, client::signature(method));
+            for line in method.doc.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use rustyline::{
+    completion::Completer, error::ReadlineError, Context, Editor, Helper, Highlighter, Hinter,
+    Validator,
+};
+use serde_json::Value;
+use tokio::runtime::Runtime;
+
+use crate::client::Client;
+
+const COMMANDS: &[&str] = &["help", "list", "quit"];
+
+/// Completes the first word of a line with a command or `service.method`.
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct Words(Vec<String>);
+
+impl Completer for Words {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let prefix = &line[..pos];
+        if prefix.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+
+        let words = self
+            .0
+            .iter()
+            .filter(|word| word.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok((0, words))
+    }
+}
+
+pub fn run(rt: &Runtime, client: &Client) -> anyhow::Result<()> {
+    let mut words = client.method_paths();
+    words.extend(COMMANDS.iter().map(ToString::to_string));
+
+    let mut editor = Editor::new()?;
+    editor.set_helper(Some(Words(words)));
+
+    loop {
+        let line = match editor.readline("mrpc> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        let (word, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match word {
+            "quit" | "exit" => return Ok(()),
+            "help" => {
+                println!("list                      list the methods");
+                println!("service.method [args]     call a method, args as a JSON object or array");
+                println!("quit                      leave");
+            }
+            "list" => crate::list(client),
+            path => {
+                let args = match args.trim() {
+                    "" => Ok(Value::Null),
+                    args => serde_json::from_str(args),
+                };
+                let result = match args {
+                    Ok(args) => rt.block_on(client.call(path, args)),
+                    Err(e) => Err(e.into()),
+                };
+                match result {
+                    Ok(output) => println!("{}", serde_json::to_string_pretty(&output)?),
+                    Err(e) => eprintln!("error: {:#}", e),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1008 @@
+//! A TypeScript client generated from a server descriptor, speaking the JSON
+//! wire format over a WebSocket.
+//!
+//! The descriptor only names the Rust types of the arguments and outputs.
+//! Those of the standard library are converted, the others are declared from
+//! their JSON Schemas when an OpenRPC document of the server is given, see
+//! `mrpc::openrpc`, and are `unknown` otherwise. Generic types are always
+//! `unknown`, since schemars names their instances apart.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::anyhow;
+use mrpc::descriptor::{MethodDescriptor, Route, ServerDescriptor, ServiceEntry, Tagging};
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    TypeScript,
+    /// The same client without the types, an ES module for runtimes which
+    /// can't run TypeScript.
+    JavaScript,
+}
+
+/// Names of the globals the generated code uses, which the Rust types
+/// mustn't shadow.
+const GLOBALS: &[&str] = &[
+    "Array",
+    "Error",
+    "Map",
+    "Object",
+    "Promise",
+    "Record",
+    "TextDecoder",
+    "WebSocket",
+];
+
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "class",
+    "default",
+    "delete",
+    "enum",
+    "export",
+    "function",
+    "import",
+    "new",
+    "null",
+    "this",
+    "typeof",
+    "var",
+    "void",
+    "with",
+    "yield",
+];
+
+/// The client and the Rust types it declares `unknown`, those without a
+/// schema in `schemas`, the `components.schemas` of an OpenRPC document.
+/// Fails when the methods can't be called by name, see [`Route::new`].
+pub fn generate(
+    descriptor: &ServerDescriptor,
+    schemas: Map<String, Value>,
+    language: Language,
+) -> anyhow::Result<(String, Vec<String>)> {
+    for entry in &descriptor.services {
+        for method in &entry.service.methods {
+            Route::new(descriptor, entry, method).map_err(|e| anyhow!(e))?;
+        }
+    }
+
+    let mut generator = Generator {
+        ts: language == Language::TypeScript,
+        tagging: descriptor.tagging.clone(),
+        aliases: BTreeSet::new(),
+        schemas,
+        unknown: Vec::new(),
+        out: String::new(),
+    };
+    let services = descriptor
+        .services
+        .iter()
+        .map(|entry| generator.service(entry))
+        .collect::<Vec<_>>();
+    generator.emit(&descriptor.name, &services);
+    Ok((generator.out, generator.unknown))
+}
+
+/// A service with its types converted.
+struct Service<'a> {
+    entry: &'a ServiceEntry,
+    methods: Vec<Method<'a>>,
+}
+
+struct Method<'a> {
+    method: &'a MethodDescriptor,
+    /// Name and type of the arguments.
+    args: Vec<(String, String)>,
+    /// Number of trailing arguments which may be left out.
+    optional: usize,
+    output: String,
+}
+
+struct Generator {
+    ts: bool,
+    /// How the variants of the services are tagged.
+    tagging: Tagging,
+    /// The Rust types the descriptor doesn't describe.
+    aliases: BTreeSet<String>,
+    /// The JSON Schemas of the types by name.
+    schemas: Map<String, Value>,
+    /// The types without a schema, declared `unknown`.
+    unknown: Vec<String>,
+    out: String,
+}
+
+impl Generator {
+    fn service<'a>(&mut self, entry: &'a ServiceEntry) -> Service<'a> {
+        let service = &entry.service;
+        let params = service
+            .type_params
+            .iter()
+            .map(String::as_str)
+            .zip(entry.type_args.iter().map(|arg| Ty::parse(arg)))
+            .collect::<HashMap<_, _>>();
+
+        let methods = service
+            .methods
+            .iter()
+            .map(|method| {
+                let args = method
+                    .args
+                    .iter()
+                    .map(|arg| (arg.name.clone(), self.ts(&Ty::parse(&arg.ty), &params)))
+                    .collect();
+                // Only the arguments of services encoded by name can be left
+                // out, and only at the end of the parameters.
+                let optional = if service.compact {
+                    0
+                } else {
+                    method
+                        .args
+                        .iter()
+                        .rev()
+                        .take_while(|arg| arg.default)
+                        .count()
+                };
+                Method {
+                    method,
+                    args,
+                    optional,
+                    output: self.ts(&Ty::parse(&method.output), &params),
+                }
+            })
+            .collect();
+
+        Service { entry, methods }
+    }
+
+    fn ts(&mut self, ty: &Ty, params: &HashMap<&str, Ty>) -> String {
+        let (name, args) = match ty {
+            Ty::Tuple(elems) if elems.is_empty() => return "null".into(),
+            Ty::Tuple(elems) => {
+                let elems = elems
+                    .iter()
+                    .map(|elem| self.ts(elem, params))
+                    .collect::<Vec<_>>();
+                return format!("[{}]", elems.join(", "));
+            }
+            Ty::Slice(elem) => return array(self.ts(elem, params)),
+            Ty::Path(name, args) => (name.as_str(), args),
+        };
+
+        if let (true, Some(arg)) = (args.is_empty(), params.get(name)) {
+            // The type arguments are written in the scope of the server.
+            return self.ts(arg, &HashMap::new());
+        }
+
+        let mut arg = |i: usize| match args.get(i) {
+            Some(arg) => self.ts(arg, params),
+            None => "unknown".into(),
+        };
+        match name {
+            "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+            | "i128" | "isize" | "f32" | "f64" => "number".into(),
+            "bool" => "boolean".into(),
+            "String" | "str" | "char" | "PathBuf" | "Path" => "string".into(),
+            "Option" => format!("{} | null", arg(0)),
+            "Vec" | "VecDeque" | "LinkedList" | "HashSet" | "BTreeSet" => array(arg(0)),
+            "HashMap" | "BTreeMap" => format!("Record<string, {}>", arg(1)),
+            "Box" | "Arc" | "Rc" | "Cow" => arg(args.len().saturating_sub(1)),
+            "Result" => format!("{{ Ok: {} }} | {{ Err: {} }}", arg(0), arg(1)),
+            "Value" | "unknown" => "unknown".into(),
+            _ => {
+                let name = alias(name);
+                self.aliases.insert(name.clone());
+                name
+            }
+        }
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        self.out.push_str(line.as_ref());
+        self.out.push('\n');
+    }
+
+    fn doc(&mut self, indent: &str, doc: &str) {
+        let doc = doc.replace("*/", "*\\/");
+        match doc.lines().collect::<Vec<_>>().as_slice() {
+            [] => {}
+            [line] => self.line(format!("{}/** {} */", indent, line)),
+            lines => {
+                self.line(format!("{}/**", indent));
+                for line in lines {
+                    self.line(format!("{} * {}", indent, line).trim_end());
+                }
+                self.line(format!("{} */", indent));
+            }
+        }
+    }
+
+    fn union(&mut self, name: &str, variants: &[String]) {
+        self.line("");
+        self.variants(name, variants);
+    }
+
+    fn variants(&mut self, name: &str, variants: &[String]) {
+        if variants.is_empty() {
+            self.line(format!("export type {} = never;", name));
+            return;
+        }
+        self.line(format!("export type {} =", name));
+        for (i, variant) in variants.iter().enumerate() {
+            let end = if i + 1 == variants.len() { ";" } else { "" };
+            self.line(format!("  | {}{}", variant, end));
+        }
+    }
+
+    fn emit(&mut self, server: &str, services: &[Service]) {
+        self.line(format!(
+            "// Generated by `mrpc typescript` from the descriptor of `{}`. Do not edit.",
+            server
+        ));
+        if self.ts {
+            self.emit_types(server, services);
+        }
+        self.emit_client(server, services);
+    }
+
+    /// Declares the types in `aliases` and those their schemas refer to.
+    fn emit_aliases(&mut self) {
+        let mut pending = std::mem::take(&mut self.aliases);
+        let mut declared = BTreeSet::new();
+        while let Some(alias) = pending.pop_first() {
+            if !declared.insert(alias.clone()) {
+                continue;
+            }
+            let schema = self
+                .schemas
+                .iter()
+                .find(|(name, _)| self::alias(name) == alias)
+                .map(|(_, schema)| schema.clone());
+            self.line("");
+            match schema {
+                Some(schema) => self.definition(&alias, &schema, &mut pending),
+                None => {
+                    self.line(format!(
+                        "/** Rust type `{}`, which the descriptor doesn't describe. */",
+                        alias
+                    ));
+                    self.line(format!("export type {} = unknown;", alias));
+                    self.unknown.push(alias);
+                }
+            }
+        }
+    }
+
+    /// `name` as an interface when `schema` is a plain object, as a type
+    /// otherwise.
+    fn definition(&mut self, name: &str, schema: &Value, refs: &mut BTreeSet<String>) {
+        if let Some(doc) = schema.get("description").and_then(Value::as_str) {
+            self.doc("", doc);
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let plain = ["$ref", "enum", "const", "oneOf", "anyOf", "allOf"]
+            .iter()
+            .all(|key| schema.get(key).is_none())
+            && schema.get("type").is_none_or(|ty| ty == "object")
+            && schema
+                .get("additionalProperties")
+                .is_none_or(|additional| additional == false);
+        match properties {
+            Some(properties) if plain => {
+                self.line(format!("export interface {} {{", name));
+                for (key, property) in properties {
+                    if let Some(doc) = property.get("description").and_then(Value::as_str) {
+                        self.doc("  ", doc);
+                    }
+                    self.line(format!(
+                        "  {}{}: {};",
+                        quote_key(key),
+                        optional(schema, key),
+                        schema_ts(property, refs)
+                    ));
+                }
+                self.line("}");
+            }
+            _ => match schema.get("oneOf").or_else(|| schema.get("anyOf")) {
+                Some(Value::Array(variants)) if variants.len() > 1 => {
+                    let variants = variants
+                        .iter()
+                        .map(|variant| schema_ts(variant, refs))
+                        .collect::<Vec<_>>();
+                    self.variants(name, &variants);
+                }
+                _ => self.line(format!(
+                    "export type {} = {};",
+                    name,
+                    schema_ts(schema, refs)
+                )),
+            },
+        }
+    }
+
+    fn emit_types(&mut self, server: &str, services: &[Service]) {
+        self.emit_aliases();
+
+        self.line(
+            r#"
+/** An error of the framework, see `mrpc::Error`. */
+export type MrpcError =
+  | { Protocol: string }
+  | { LimitExceeded: string }
+  | { Overloaded: string }
+  | { ServiceUnavailable: string }
+  | { Incompatible: string }
+  | { Remote: { kind: string; message: string } };
+
+export interface RpcRequest<T> {
+  id: number;
+  value: T;
+  trace?: { traceparent: string; tracestate?: string };
+  metadata?: Record<string, string>;
+}
+
+export interface RpcResponse<T> {
+  id: number | null;
+  value: { Ok: T } | { Err: MrpcError };
+}"#,
+        );
+
+        for service in services {
+            let (name, compact, tagging) = (
+                &service.entry.name,
+                service.entry.service.compact,
+                &service.entry.service.tagging,
+            );
+
+            let mut requests = Vec::new();
+            for method in &service.methods {
+                let request = if compact {
+                    let args = method
+                        .args
+                        .iter()
+                        .map(|(name, ty)| format!("{}: {}", name, ty))
+                        .collect::<Vec<_>>();
+                    let args = if args.is_empty() {
+                        "null".into()
+                    } else {
+                        format!("[{}]", args.join(", "))
+                    };
+                    format!("[{}, {}]", method.method.id.unwrap_or_default(), args)
+                } else {
+                    let required = method.args.len() - method.optional;
+                    let args = method
+                        .args
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (name, ty))| {
+                            let optional = if i < required { "" } else { "?" };
+                            format!("{}{}: {}", quote_key(name), optional, ty)
+                        })
+                        .collect::<Vec<_>>();
+                    let args = format!("{{ {} }}", args.join("; ")).replace("{  }", "{}");
+                    variant_type(tagging, &method.method.wire_name, &args)
+                };
+                requests.push(request);
+            }
+            self.union(&format!("{}Request", name), &requests);
+
+            let mut responses = Vec::new();
+            for method in &service.methods {
+                let response = if compact {
+                    format!(
+                        "[{}, {}]",
+                        method.method.id.unwrap_or_default(),
+                        method.output
+                    )
+                } else {
+                    variant_type(tagging, &method.method.wire_name, &method.output)
+                };
+                responses.push(response);
+            }
+            self.union(&format!("{}Response", name), &responses);
+        }
+
+        for kind in ["Request", "Response"] {
+            let variants = services
+                .iter()
+                .map(|service| {
+                    let entry = service.entry;
+                    let value = format!("{}{}", entry.name, kind);
+                    variant_type(&self.tagging, entry.wire_name(), &value)
+                })
+                .collect::<Vec<_>>();
+            self.union(&format!("{}{}", server, kind), &variants);
+        }
+
+        for service in services {
+            self.line("");
+            self.line(format!("export interface {}Client {{", service.entry.name));
+            for method in &service.methods {
+                self.doc("  ", &method.method.doc);
+                let params = params(method);
+                self.line(format!(
+                    "  {}({}): Promise<{}>;",
+                    camel_case(&method.method.name),
+                    params,
+                    output(&method.output)
+                ));
+            }
+            self.line("}");
+        }
+
+        self.line(
+            r#"
+interface Pending {
+  resolve: (value: any) => void;
+  reject: (reason: Error) => void;
+}"#,
+        );
+    }
+
+    fn emit_client(&mut self, server: &str, services: &[Service]) {
+        let ts = self.ts;
+        let a = |ty: &str| annotation(ts, ty);
+
+        let client = format!(
+            r#"
+/** The error a server answered a call with. */
+export class RpcError extends Error {{
+  error{error};
+
+  constructor(error{error}) {{
+    super(
+      Object.entries(error)
+        .map(([kind, detail]) => `${{kind}}: ${{JSON.stringify(detail)}}`)
+        .join(", "),
+    );
+    this.name = "RpcError";
+    this.error = error;
+  }}
+}}
+
+/**
+ * A client of `{server}` over a WebSocket, see `mrpc::net::websocket`.
+ *
+ * 64-bit integers are plain numbers, so they lose precision above 2^53.
+ */
+export class {server}Client {{"#,
+            error = a("MrpcError"),
+            server = server,
+        );
+        self.line(client);
+
+        for service in services {
+            self.line(format!(
+                "  {}{};",
+                camel_case(&service.entry.name),
+                a(&format!("{}Client", service.entry.name))
+            ));
+        }
+        let pending = if ts { "<number, Pending>" } else { "" };
+        self.line(format!(
+            r#"  #socket{socket};
+  #opened{opened};
+  #pending = new Map{pending}();
+  #nextId = 0;
+
+  constructor(url{url}, WebSocketImpl{impl_} = WebSocket) {{
+    this.#socket = new WebSocketImpl(url);
+    this.#socket.binaryType = "arraybuffer";
+    this.#opened = new Promise((resolve, reject) => {{
+      this.#socket.addEventListener("open", () => resolve());
+      this.#socket.addEventListener("error", () =>
+        reject(new Error(`failed to connect ${{url}}`)),
+      );
+    }});
+    // Only the calls report the failure.
+    this.#opened.catch(() => {{}});
+    this.#socket.addEventListener("message", (event) => this.#receive(event.data));
+    this.#socket.addEventListener("close", () =>
+      this.#failAll(new Error("connection closed")),
+    );"#,
+            socket = a("WebSocket"),
+            opened = a("Promise<void>"),
+            pending = pending,
+            url = a("string"),
+            impl_ = a("typeof WebSocket"),
+        ));
+
+        for service in services {
+            let (name, compact, tagging) = (
+                &service.entry.name,
+                service.entry.service.compact,
+                &service.entry.service.tagging,
+            );
+            let (server, wire_name) = (self.tagging.clone(), service.entry.wire_name());
+            let value = format!("value{}", variant_member(&server, wire_name));
+            self.line("");
+            self.line(format!("    this.{} = {{", camel_case(name)));
+            for method in &service.methods {
+                let names = method
+                    .args
+                    .iter()
+                    .map(|(name, _)| param_name(name))
+                    .collect::<Vec<_>>();
+                let (request, output) = if compact {
+                    let id = method.method.id.unwrap_or_default();
+                    let args = if names.is_empty() {
+                        "null".into()
+                    } else {
+                        format!("[{}]", names.join(", "))
+                    };
+                    (
+                        variant(&server, wire_name, &format!("[{}, {}]", id, args)),
+                        format!("{}[1]", value),
+                    )
+                } else {
+                    let args = method
+                        .args
+                        .iter()
+                        .map(|(name, _)| {
+                            let param = param_name(name);
+                            if param == *name {
+                                param
+                            } else {
+                                format!("{}: {}", quote_key(name), param)
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    let args = if args.is_empty() {
+                        "{}".into()
+                    } else {
+                        format!("{{ {} }}", args.join(", "))
+                    };
+                    let method_name = &method.method.wire_name;
+                    (
+                        variant(&server, wire_name, &variant(tagging, method_name, &args)),
+                        format!("{}{}", value, variant_member(tagging, method_name)),
+                    )
+                };
+                self.line(format!(
+                    "      {}: ({}) =>",
+                    camel_case(&method.method.name),
+                    names.join(", ")
+                ));
+                self.line(format!(
+                    "        this.#call({}).then((value) => {}),",
+                    request, output
+                ));
+            }
+            self.line("    };");
+        }
+
+        self.line(format!(
+            r#"  }}
+
+  /** Closes the connection, failing the calls in flight. */
+  close(){void} {{
+    this.#socket.close();
+  }}
+
+  async #call(value{request}){any} {{
+    await this.#opened;
+    const id = this.#nextId++;
+    const request{rpc_request} = {{ id, value }};
+    return new Promise((resolve, reject) => {{
+      this.#pending.set(id, {{ resolve, reject }});
+      this.#socket.send(JSON.stringify(request));
+    }});
+  }}
+
+  #receive(data{data}){void} {{
+    const text = typeof data === "string" ? data : new TextDecoder().decode(data);
+    const response{rpc_response} = JSON.parse(text);
+    if (response.id === null) {{
+      // The server refused the connection.
+      if ("Err" in response.value) {{
+        this.#failAll(new RpcError(response.value.Err));
+      }}
+      return;
+    }}
+    const pending = this.#pending.get(response.id);
+    if (pending === undefined) {{
+      return;
+    }}
+    this.#pending.delete(response.id);
+    if ("Ok" in response.value) {{
+      pending.resolve(response.value.Ok);
+    }} else {{
+      pending.reject(new RpcError(response.value.Err));
+    }}
+  }}
+
+  #failAll(e{error}){void} {{
+    for (const pending of this.#pending.values()) {{
+      pending.reject(e);
+    }}
+    this.#pending.clear();
+  }}
+}}"#,
+            void = a("void"),
+            request = a(&format!("{}Request", server)),
+            any = a("Promise<any>"),
+            rpc_request = a(&format!("RpcRequest<{}Request>", server)),
+            data = a("string | ArrayBuffer"),
+            rpc_response = a(&format!("RpcResponse<{}Response>", server)),
+            error = a("Error"),
+        ));
+    }
+}
+
+/// The TypeScript type of a JSON Schema, adding the definitions it refers
+/// to to `refs`. What it doesn't understand is `unknown`.
+fn schema_ts(schema: &Value, refs: &mut BTreeSet<String>) -> String {
+    let schema = match schema {
+        Value::Bool(true) => return "unknown".into(),
+        Value::Bool(false) => return "never".into(),
+        Value::Object(schema) => schema,
+        _ => return "unknown".into(),
+    };
+    let union = |schemas: &Vec<Value>, refs: &mut BTreeSet<String>| {
+        let mut tys = Vec::new();
+        for schema in schemas {
+            let ty = schema_ts(schema, refs);
+            if !tys.contains(&ty) {
+                tys.push(ty);
+            }
+        }
+        tys.join(" | ")
+    };
+
+    if let Some(Value::String(path)) = schema.get("$ref") {
+        let name = alias(path.rsplit('/').next().unwrap_or(path));
+        refs.insert(name.clone());
+        return name;
+    }
+    if let Some(value) = schema.get("const") {
+        return value.to_string();
+    }
+    if let Some(Value::Array(values)) = schema.get("enum") {
+        let values = values.iter().map(Value::to_string).collect::<Vec<_>>();
+        return values.join(" | ");
+    }
+    if let Some(Value::Array(schemas)) = schema.get("oneOf").or_else(|| schema.get("anyOf")) {
+        return union(schemas, refs);
+    }
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        let tys = schemas
+            .iter()
+            .map(|schema| parenthesize(schema_ts(schema, refs)))
+            .collect::<Vec<_>>();
+        return tys.join(" & ");
+    }
+
+    match schema.get("type") {
+        Some(Value::Array(tys)) => {
+            // `Option<T>` of a primitive is `"type": ["T", "null"]`.
+            let schemas = tys
+                .iter()
+                .map(|ty| {
+                    let mut schema = schema.clone();
+                    schema.insert("type".into(), ty.clone());
+                    Value::Object(schema)
+                })
+                .collect();
+            union(&schemas, refs)
+        }
+        Some(Value::String(ty)) => match ty.as_str() {
+            "string" => "string".into(),
+            "integer" | "number" => "number".into(),
+            "boolean" => "boolean".into(),
+            "null" => "null".into(),
+            "array" => match schema.get("items") {
+                Some(Value::Array(items)) => {
+                    let items = items
+                        .iter()
+                        .map(|item| schema_ts(item, refs))
+                        .collect::<Vec<_>>();
+                    format!("[{}]", items.join(", "))
+                }
+                Some(item) => array(schema_ts(item, refs)),
+                None => "unknown[]".into(),
+            },
+            "object" => object_ts(schema, refs),
+            _ => "unknown".into(),
+        },
+        _ if schema.contains_key("properties") => object_ts(schema, refs),
+        _ => "unknown".into(),
+    }
+}
+
+fn object_ts(schema: &Map<String, Value>, refs: &mut BTreeSet<String>) -> String {
+    let properties = match schema.get("properties").and_then(Value::as_object) {
+        Some(properties) => properties,
+        None => {
+            let value = match schema.get("additionalProperties") {
+                Some(additional) => schema_ts(additional, refs),
+                None => "unknown".into(),
+            };
+            return format!("Record<string, {}>", value);
+        }
+    };
+    let schema = Value::Object(schema.clone());
+    let properties = properties
+        .iter()
+        .map(|(key, property)| {
+            format!(
+                "{}{}: {}",
+                quote_key(key),
+                optional(&schema, key),
+                schema_ts(property, refs)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", properties.join("; ")).replace("{  }", "{}")
+}
+
+/// `?` when `key` isn't a required property of `schema`.
+fn optional(schema: &Value, key: &str) -> &'static str {
+    let required = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .is_some_and(|required| required.iter().any(|name| name == key));
+    if required {
+        ""
+    } else {
+        "?"
+    }
+}
+
+/// `ty` in parentheses when it is a union.
+fn parenthesize(ty: String) -> String {
+    if ty.contains(" | ") {
+        format!("({})", ty)
+    } else {
+        ty
+    }
+}
+
+/// `: ty` in TypeScript.
+fn annotation(ts: bool, ty: &str) -> String {
+    if ts {
+        format!(": {}", ty)
+    } else {
+        String::new()
+    }
+}
+
+/// The parameters of a method, with their types.
+fn params(method: &Method) -> String {
+    let required = method.args.len() - method.optional;
+    method
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, (name, ty))| {
+            let name = param_name(name);
+            if i < required {
+                format!("{}: {}", name, ty)
+            } else {
+                format!("{}?: {}", name, ty)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn output(ty: &str) -> &str {
+    if ty == "null" {
+        "void"
+    } else {
+        ty
+    }
+}
+
+fn array(elem: String) -> String {
+    if elem.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        format!("{}[]", elem)
+    } else {
+        format!("Array<{}>", elem)
+    }
+}
+
+fn alias(name: &str) -> String {
+    if GLOBALS.contains(&name) {
+        format!("Rust{}", name)
+    } else {
+        name.into()
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// The type `ty` as the variant `name` of enums tagged by `tagging`.
+fn variant_type(tagging: &Tagging, name: &str, ty: &str) -> String {
+    match tagging {
+        Tagging::Internal { tag } => format!("{{ {}: {:?} }} & {}", quote_key(tag), name, ty),
+        _ => variant(tagging, name, ty),
+    }
+}
+
+/// The object `value` as the variant `name` of enums tagged by `tagging`.
+fn variant(tagging: &Tagging, name: &str, value: &str) -> String {
+    match tagging {
+        Tagging::Internal { tag } => format!("{{ {}: {:?}, ...{} }}", quote_key(tag), name, value),
+        Tagging::Adjacent { tag, content } => format!(
+            "{{ {}: {:?}, {}: {} }}",
+            quote_key(tag),
+            name,
+            quote_key(content),
+            value
+        ),
+        _ => format!("{{ {}: {} }}", quote_key(name), value),
+    }
+}
+
+/// The member holding the value of the variant `name` of enums tagged by
+/// `tagging`.
+fn variant_member(tagging: &Tagging, name: &str) -> String {
+    match tagging {
+        Tagging::Internal { .. } => String::new(),
+        Tagging::Adjacent { content, .. } => member(content),
+        _ => member(name),
+    }
+}
+
+fn quote_key(name: &str) -> String {
+    if is_identifier(name) {
+        name.into()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+fn member(name: &str) -> String {
+    if is_identifier(name) {
+        format!(".{}", name)
+    } else {
+        format!("[{:?}]", name)
+    }
+}
+
+fn param_name(name: &str) -> String {
+    let name = name.trim_start_matches("r#");
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.into()
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for (i, c) in name.trim_start_matches("r#").chars().enumerate() {
+        if c == '_' && i > 0 {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else if out.is_empty() {
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// A Rust type, as written in a descriptor.
+enum Ty {
+    /// The last segment of a path, with its type arguments.
+    Path(String, Vec<Ty>),
+    Tuple(Vec<Ty>),
+    /// A slice or an array.
+    Slice(Box<Ty>),
+}
+
+impl Ty {
+    /// Parses what `mrpc-derive` writes. Anything unexpected ends up an
+    /// unknown type rather than an error.
+    fn parse(s: &str) -> Ty {
+        let mut tokens = Vec::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_alphanumeric() || c == '_' || c == '\'' {
+                let mut token = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    token.push(c);
+                }
+                tokens.push(token);
+            } else if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        parser
+            .ty()
+            .unwrap_or_else(|| Ty::Path("unknown".into(), Vec::new()))
+    }
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn ty(&mut self) -> Option<Ty> {
+        let token = self.next()?;
+        match token.as_str() {
+            "&" => {
+                if self.peek().is_some_and(|token| token.starts_with('\'')) {
+                    self.pos += 1;
+                }
+                self.eat("mut");
+                self.ty()
+            }
+            "(" => {
+                let mut elems = Vec::new();
+                let mut trailing_comma = false;
+                while !self.eat(")") {
+                    elems.push(self.ty()?);
+                    trailing_comma = self.eat(",");
+                }
+                Some(match (elems.len(), trailing_comma) {
+                    (1, false) => elems.pop()?,
+                    _ => Ty::Tuple(elems),
+                })
+            }
+            "[" => {
+                let elem = self.ty()?;
+                while !self.eat("]") {
+                    self.next()?;
+                }
+                Some(Ty::Slice(Box::new(elem)))
+            }
+            "dyn" | "impl" => self.ty(),
+            _ => {
+                let mut name = token;
+                while self.eat(":") {
+                    self.eat(":");
+                    name = self.next()?;
+                }
+
+                let mut args = Vec::new();
+                if self.eat("<") {
+                    while !self.eat(">") {
+                        if self.peek().is_some_and(|token| token.starts_with('\'')) {
+                            self.pos += 1;
+                        } else {
+                            args.push(self.ty()?);
+                        }
+                        self.eat(",");
+                    }
+                }
+                Some(Ty::Path(name, args))
+            }
+        }
+    }
+}
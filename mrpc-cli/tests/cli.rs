@@ -0,0 +1,428 @@
+use std::{process::Command, sync::Arc};
+
+use mrpc::{net::ServerConfig, sync::mpsc};
+use tokio::net::TcpListener;
+
+#[mrpc::service(message(serde, schema))]
+trait Greeter {
+    /// Greets `name`.
+    async fn greet(name: String, times: u32) -> String;
+}
+
+#[mrpc::service(message(serde, compact, schema))]
+trait Counter {
+    #[rpc(id = 3)]
+    async fn add(a: i64, b: i64) -> i64;
+}
+
+struct GreeterImpl;
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    async fn greet(self: Arc<Self>, name: String, times: u32) -> String {
+        format!("hello {}", name).repeat(times as usize)
+    }
+}
+
+struct CounterImpl;
+
+#[mrpc::async_trait]
+impl Counter for CounterImpl {
+    async fn add(self: Arc<Self>, a: i64, b: i64) -> i64 {
+        a + b
+    }
+}
+
+#[mrpc::server(message(serde, schema), reflection)]
+enum Server {
+    Greeter(Greeter),
+    Counter(Counter),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+
+    async fn create_counter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Counter>> {
+        Ok(Arc::new(CounterImpl))
+    }
+}
+
+/// A server whose enums serde renames and tags.
+mod tagged {
+    use std::sync::Arc;
+
+    #[mrpc::service(message(serde(
+        rename_all = "snake_case",
+        tag = "method",
+        content = "params"
+    )))]
+    pub trait Store {
+        async fn get_value(key: String) -> Option<String>;
+    }
+
+    pub struct StoreImpl;
+
+    #[mrpc::async_trait]
+    impl Store for StoreImpl {
+        async fn get_value(self: Arc<Self>, key: String) -> Option<String> {
+            Some(key.to_uppercase())
+        }
+    }
+
+    #[mrpc::server(message(serde(rename_all = "lowercase", tag = "service")))]
+    pub enum Server {
+        Store(Store),
+    }
+
+    pub struct ServerImpl;
+
+    #[mrpc::async_trait]
+    impl Server for ServerImpl {
+        async fn create_store(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Store>> {
+            Ok(Arc::new(StoreImpl))
+        }
+    }
+}
+
+async fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+async fn mrpc(args: &[&str]) -> (bool, String, String) {
+    let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+    tokio::task::spawn_blocking(move || {
+        let output = Command::new(env!("CARGO_BIN_EXE_mrpc"))
+            .args(&args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn calls() {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(mrpc::net::tcp::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+
+    let (ok, stdout, _) = mrpc(&[&addr, "list"]).await;
+    assert!(ok);
+    assert!(
+        stdout.contains("Greeter.greet(name: String, times: u32) -> String\n    Greets `name`.")
+    );
+    assert!(stdout.contains("Counter.add(a: i64, b: i64) -> i64"));
+    assert!(stdout.contains("Reflection.describe() -> ServerDescriptor"));
+
+    let (ok, stdout, _) = mrpc(&[
+        &addr,
+        "call",
+        "Greeter.greet",
+        r#"{"name": "a", "times": 2}"#,
+    ])
+    .await;
+    assert!(ok);
+    assert_eq!(stdout, "\"hello ahello a\"\n");
+
+    let (ok, stdout, _) =
+        mrpc(&[&format!("tcp://{}", addr), "call", "Counter.add", "[1, 2]"]).await;
+    assert!(ok);
+    assert_eq!(stdout, "3\n");
+
+    let (ok, _, stderr) = mrpc(&[&addr, "call", "Greeter.wave"]).await;
+    assert!(!ok);
+    assert!(stderr.contains("no method `wave` in `Greeter`"));
+
+    let (ok, _, stderr) = mrpc(&[&addr, "call", "Greeter.greet", r#"{"name": "a"}"#]).await;
+    assert!(!ok);
+    assert!(stderr.contains("protocol error"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_and_descriptor_file() {
+    let dir = std::env::temp_dir().join(format!("mrpc-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (socket, descriptor) = (dir.join("socket"), dir.join("descriptor.json"));
+    std::fs::remove_file(&socket).ok();
+    std::fs::write(
+        &descriptor,
+        serde_json::to_vec(&ServerRequest::descriptor()).unwrap(),
+    )
+    .unwrap();
+
+    let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(mrpc::net::unix::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+
+    let address = format!("unix:{}", socket.display());
+    let (ok, stdout, stderr) = mrpc(&[
+        &address,
+        "--descriptor",
+        descriptor.to_str().unwrap(),
+        "call",
+        "Counter.add",
+        r#"{"a": 40, "b": 2}"#,
+    ])
+    .await;
+    assert!(ok, "{}", stderr);
+    assert_eq!(stdout, "42\n");
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn tagged_server() {
+    let dir = std::env::temp_dir().join(format!("mrpc-cli-tagged-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let descriptor = dir.join("descriptor.json");
+    std::fs::write(
+        &descriptor,
+        serde_json::to_vec(&tagged::ServerRequest::descriptor()).unwrap(),
+    )
+    .unwrap();
+    let descriptor = descriptor.to_str().unwrap();
+
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(mrpc::net::tcp::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+    tokio::spawn(tagged::Server::serve(Arc::new(tagged::ServerImpl), rx));
+
+    let (ok, stdout, stderr) = mrpc(&[
+        &addr,
+        "--descriptor",
+        descriptor,
+        "call",
+        "Store.get_value",
+        r#"{"key": "a"}"#,
+    ])
+    .await;
+    assert!(ok, "{}", stderr);
+    assert_eq!(stdout, "\"A\"\n");
+
+    let (ok, stdout, stderr) = mrpc(&["--descriptor", descriptor, "typescript"]).await;
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains(
+        "export type StoreRequest =\n  | { method: \"get_value\", params: { key: string } };"
+    ));
+    assert!(
+        stdout.contains("export type ServerRequest =\n  | { service: \"store\" } & StoreRequest;")
+    );
+    assert!(
+        stdout.contains(
+            "this.#call({ service: \"store\", ...{ method: \"get_value\", params: { key } } })\
+         .then((value) => value.params),"
+        ),
+        "{}",
+        stdout
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Runs `node` with a global `WebSocket`.
+fn node(args: &[&str]) -> std::process::Output {
+    let probe = Command::new("node")
+        .args(["-p", "typeof WebSocket"])
+        .output()
+        .expect("node not found");
+    let mut node = Command::new("node");
+    if String::from_utf8_lossy(&probe.stdout).trim() == "undefined" {
+        node.arg("--experimental-websocket");
+    }
+    node.args(args).output().unwrap()
+}
+
+const ROUND_TRIP: &str = r#"
+import { RpcError, ServerClient } from "./client.mjs";
+
+const client = new ServerClient(process.argv[2]);
+const results = {
+  greet: await client.greeter.greet("a", 2),
+  add: await client.counter.add(40, 2),
+  server: (await client.reflection.describe()).name,
+};
+try {
+  await client.greeter.greet("a", -1);
+} catch (e) {
+  results.error = e instanceof RpcError ? Object.keys(e.error) : String(e);
+}
+client.close();
+console.log(JSON.stringify(results));
+"#;
+
+/// Writes the descriptor and the OpenRPC document of `Server` to a new
+/// directory, returning it and their paths.
+fn descriptor_dir(name: &str) -> (std::path::PathBuf, String, String) {
+    let dir = std::env::temp_dir().join(format!("mrpc-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |file: &str, value: Vec<u8>| {
+        let path = dir.join(file);
+        std::fs::write(&path, value).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let descriptor = write(
+        "descriptor.json",
+        serde_json::to_vec(&ServerRequest::descriptor()).unwrap(),
+    );
+    let openrpc = write(
+        "openrpc.json",
+        serde_json::to_vec(&ServerRequest::openrpc()).unwrap(),
+    );
+    (dir, descriptor, openrpc)
+}
+
+#[tokio::test]
+async fn typescript() {
+    let (dir, descriptor, openrpc) = descriptor_dir("ts");
+
+    let (ok, stdout, stderr) = mrpc(&["--descriptor", &descriptor, "typescript"]).await;
+    assert!(ok, "{}", stderr);
+    assert!(stdout.contains("export type CounterRequest =\n  | [3, [a: number, b: number]];"));
+    assert!(stdout.contains(
+        "  /** Greets `name`. */\n  greet(name: string, times: number): Promise<string>;"
+    ));
+    assert!(stdout.contains("export type ServerDescriptor = unknown;"));
+    assert!(
+        stderr.contains("warning: `ServerDescriptor` has no JSON Schema"),
+        "{}",
+        stderr
+    );
+
+    let (ok, stdout, stderr) = mrpc(&[
+        "--descriptor",
+        &descriptor,
+        "typescript",
+        "--openrpc",
+        &openrpc,
+    ])
+    .await;
+    assert!(ok, "{}", stderr);
+    assert!(!stderr.contains("warning"), "{}", stderr);
+    assert!(
+        stdout.contains(
+            "/** A `#[mrpc::server]` enum. */\nexport interface ServerDescriptor {\n  name: string;"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("  services: ServiceEntry[];"), "{}", stdout);
+    assert!(stdout.contains("  type_params?: string[];"), "{}", stdout);
+    assert!(
+        stdout
+            .contains("export type Tagging =\n  | \"external\"\n  | { internal: { tag: string } }"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("  id?: number | null;"), "{}", stdout);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+/// Checks the generated types with `tsc` and calls a server with the
+/// generated client in `node`, which both have to be installed.
+#[tokio::test]
+#[ignore = "needs node and tsc, run with `cargo test -- --ignored`"]
+async fn typescript_round_trip() {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(mrpc::net::websocket::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+
+    let (dir, descriptor, openrpc) = descriptor_dir("ts-round-trip");
+    let client_ts = dir.join("client.ts");
+    let (ok, _, stderr) = mrpc(&[
+        "--descriptor",
+        &descriptor,
+        "typescript",
+        "--openrpc",
+        &openrpc,
+        "--out",
+        client_ts.to_str().unwrap(),
+    ])
+    .await;
+    assert!(ok, "{}", stderr);
+    let client_mjs = dir.join("client.mjs");
+    let (ok, _, stderr) = mrpc(&[
+        "--descriptor",
+        &descriptor,
+        "typescript",
+        "--javascript",
+        "--out",
+        client_mjs.to_str().unwrap(),
+    ])
+    .await;
+    assert!(ok, "{}", stderr);
+    std::fs::write(dir.join("round_trip.mjs"), ROUND_TRIP).unwrap();
+
+    let output = Command::new("tsc")
+        .args([
+            "--strict",
+            "--noEmit",
+            "--target",
+            "es2022",
+            "--lib",
+            "es2022,dom",
+        ])
+        .arg(&client_ts)
+        .output()
+        .expect("tsc not found");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let script = dir.join("round_trip.mjs");
+    let url = format!("ws://{}", addr);
+    let output = tokio::task::spawn_blocking(move || node(&[script.to_str().unwrap(), &url]))
+        .await
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        results,
+        serde_json::json!({
+            "greet": "hello ahello a",
+            "add": 42,
+            "server": "Server",
+            "error": ["Protocol"],
+        })
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
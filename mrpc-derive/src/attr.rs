@@ -1,10 +1,11 @@
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
 use syn::{
     parenthesized,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
-    token,
-    Ident, NestedMeta, Token,
+    spanned::Spanned,
+    token, Attribute, Ident, Lit, LitInt, Meta, NestedMeta, Token,
 };
 
 pub enum IdentMeta {
@@ -13,7 +14,7 @@ pub enum IdentMeta {
 }
 
 impl IdentMeta {
-    fn get_ident(&self) -> Ident {
+    pub fn get_ident(&self) -> Ident {
         match self {
             IdentMeta::Ident(ident) => ident.clone(),
             IdentMeta::IdentMetaList(ml) => ml.ident.clone(),
@@ -48,6 +49,7 @@ fn parse_ident_meta_list_after_ident(
     })
 }
 
+#[allow(dead_code)]
 pub struct IdentMetaList {
     pub ident: Ident,
     pub paren_token: token::Paren,
@@ -65,9 +67,18 @@ impl Parse for IdentMetaList {
     }
 }
 
+/// `message(debug, derive(..), serde(..), compact, schema)`.
 pub struct MessageAttr {
     pub serde: Option<IdentMeta>,
     pub debug: Option<IdentMeta>,
+    pub derive: Option<IdentMeta>,
+    /// Encode the methods by their `#[rpc(id = ..)]` instead of their name.
+    pub compact: Option<IdentMeta>,
+    /// Implement `mrpc::openrpc::MethodSchemas` on services, and
+    /// `openrpc()` on servers.
+    pub schema: Option<IdentMeta>,
+    /// How the `serde(..)` arguments name and tag the variants.
+    pub names: SerdeNames,
 }
 
 impl MessageAttr {
@@ -75,6 +86,207 @@ impl MessageAttr {
         Self {
             serde: None,
             debug: None,
+            derive: None,
+            compact: None,
+            schema: None,
+            names: SerdeNames::default(),
+        }
+    }
+
+    /// The `serde(..)` arguments, applied to the container or variant.
+    fn gen_serde_list(&self) -> TokenStream2 {
+        match &self.serde {
+            Some(IdentMeta::IdentMetaList(ml)) => {
+                let list = &ml.list;
+                quote! { #[serde(#list)] }
+            }
+            _ => TokenStream2::new(),
+        }
+    }
+
+    /// Attributes of the generated message enums.
+    pub fn gen_attrs(&self) -> TokenStream2 {
+        let mut token = TokenStream2::new();
+
+        if self.debug.is_some() {
+            token.extend(quote! {
+                #[derive(Debug)]
+            });
+        }
+
+        if let Some(IdentMeta::IdentMetaList(ml)) = &self.derive {
+            let list = &ml.list;
+            token.extend(quote! {
+                #[derive(#list)]
+            });
+        }
+
+        if self.serde.is_some() && self.compact.is_none() {
+            token.extend(quote! {
+                #[derive(mrpc::serde::Serialize,mrpc::serde::Deserialize)]
+                #[serde(crate = "mrpc::serde")]
+            });
+            token.extend(self.gen_serde_list());
+        }
+
+        token
+    }
+
+    /// Attributes of the borrowed requests, which are only serialized.
+    pub fn gen_serialize_attrs(&self) -> TokenStream2 {
+        let mut token = TokenStream2::new();
+
+        if self.serde.is_some() && self.compact.is_none() {
+            token.extend(quote! {
+                #[derive(mrpc::serde::Serialize)]
+                #[serde(crate = "mrpc::serde")]
+            });
+            token.extend(self.gen_serde_list());
+        }
+
+        token
+    }
+
+    /// Attributes of the variant of a method.
+    pub fn gen_item_attrs(&self) -> TokenStream2 {
+        self.gen_serde_list()
+    }
+}
+
+/// The `serde(..)` arguments which change the variants on the wire, which
+/// the descriptors record.
+#[derive(Default)]
+pub struct SerdeNames {
+    /// `rename = ".."` of a variant.
+    pub rename: Option<String>,
+    /// `rename_all = ".."` of the enums.
+    pub rename_all: Option<String>,
+    pub tag: Option<String>,
+    pub content: Option<String>,
+    pub untagged: bool,
+}
+
+/// The `rename_all` rules of serde.
+const RENAME_RULES: &[&str] = &[
+    "lowercase",
+    "UPPERCASE",
+    "PascalCase",
+    "camelCase",
+    "snake_case",
+    "SCREAMING_SNAKE_CASE",
+    "kebab-case",
+    "SCREAMING-KEBAB-CASE",
+];
+
+impl SerdeNames {
+    fn parse(list: &Punctuated<NestedMeta, Token![,]>) -> syn::Result<Self> {
+        let mut names = Self::default();
+
+        for meta in list {
+            let meta = match meta {
+                NestedMeta::Meta(meta) => meta,
+                NestedMeta::Lit(_) => continue,
+            };
+            let name = match meta.path().get_ident() {
+                Some(ident) => ident.to_string(),
+                None => continue,
+            };
+            let field = match name.as_str() {
+                "rename" => &mut names.rename,
+                "rename_all" => &mut names.rename_all,
+                "tag" => &mut names.tag,
+                "content" => &mut names.content,
+                "untagged" => {
+                    names.untagged = true;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let value = match meta {
+                Meta::NameValue(nv) => match &nv.lit {
+                    Lit::Str(value) => value,
+                    lit => return Err(syn::Error::new(lit.span(), "Expect string")),
+                },
+                _ => {
+                    return Err(syn::Error::new(
+                        meta.span(),
+                        format!("Expect `{} = \"..\"`, the same for both directions", name),
+                    ))
+                }
+            };
+            if name == "rename_all" && !RENAME_RULES.contains(&value.value().as_str()) {
+                return Err(syn::Error::new(
+                    value.span(),
+                    format!("Expect one of {}", RENAME_RULES.join(", ")),
+                ));
+            }
+            set_only_none(field, value.value(), meta.span())?;
+        }
+
+        Ok(names)
+    }
+
+    /// The `rename = ".."` among the `#[serde(..)]` attributes of a variant.
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut names = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("serde")) {
+            if let Meta::List(list) = attr.parse_meta()? {
+                if let Some(rename) = Self::parse(&list.nested)?.rename {
+                    set_only_none(&mut names.rename, rename, attr.span())?;
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// The name on the wire of `variant`, a variant of the enums with these
+    /// arguments, as serde renames it.
+    pub fn variant_name(&self, variant: &str) -> String {
+        let snake_case = || {
+            let mut name = String::new();
+            for (i, c) in variant.char_indices() {
+                if i > 0 && c.is_uppercase() {
+                    name.push('_');
+                }
+                name.push(c.to_ascii_lowercase());
+            }
+            name
+        };
+
+        match self.rename_all.as_deref() {
+            Some("lowercase") => variant.to_ascii_lowercase(),
+            Some("UPPERCASE") => variant.to_ascii_uppercase(),
+            Some("camelCase") => {
+                let mut chars = variant.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_lowercase().chain(chars).collect()
+                })
+            }
+            Some("snake_case") => snake_case(),
+            Some("SCREAMING_SNAKE_CASE") => snake_case().to_ascii_uppercase(),
+            Some("kebab-case") => snake_case().replace('_', "-"),
+            Some("SCREAMING-KEBAB-CASE") => snake_case().to_ascii_uppercase().replace('_', "-"),
+            _ => variant.to_string(),
+        }
+    }
+
+    /// The `mrpc::descriptor::Tagging` of the enums.
+    pub fn gen_tagging(&self) -> TokenStream2 {
+        match (&self.tag, &self.content, self.untagged) {
+            (_, _, true) => quote! { mrpc::descriptor::Tagging::Untagged },
+            (Some(tag), Some(content), _) => quote! {
+                mrpc::descriptor::Tagging::Adjacent {
+                    tag: #tag.to_string(),
+                    content: #content.to_string(),
+                }
+            },
+            (Some(tag), None, _) => quote! {
+                mrpc::descriptor::Tagging::Internal {
+                    tag: #tag.to_string(),
+                }
+            },
+            (None, _, _) => quote! { mrpc::descriptor::Tagging::External },
         }
     }
 }
@@ -112,13 +324,118 @@ impl Parse for MessageAttr {
                     set_only_none(&mut attr.debug, ident_meta, ident.span())?;
                 }
                 "serde" => {
+                    if let IdentMeta::IdentMetaList(ml) = &ident_meta {
+                        attr.names = SerdeNames::parse(&ml.list)?;
+                    }
                     set_only_none(&mut attr.serde, ident_meta, ident.span())?;
                 }
+                "compact" => {
+                    if !matches!(ident_meta, IdentMeta::Ident(_)) {
+                        return Err(syn::Error::new(ident.span(), "Expect `compact`"));
+                    }
+                    set_only_none(&mut attr.compact, ident_meta, ident.span())?;
+                }
+                "schema" => {
+                    if !matches!(ident_meta, IdentMeta::Ident(_)) {
+                        return Err(syn::Error::new(ident.span(), "Expect `schema`"));
+                    }
+                    set_only_none(&mut attr.schema, ident_meta, ident.span())?;
+                }
+                "derive" => {
+                    if !matches!(ident_meta, IdentMeta::IdentMetaList(_)) {
+                        return Err(syn::Error::new(ident.span(), "Expect `derive(..)`"));
+                    }
+                    set_only_none(&mut attr.derive, ident_meta, ident.span())?;
+                }
                 _ => {
                     return Err(syn::Error::new(ident.span(), "Unknown IdentMeta attr"));
                 }
             }
         }
+
+        if let Some(compact) = &attr.compact {
+            match &attr.serde {
+                Some(IdentMeta::Ident(_)) => {}
+                Some(IdentMeta::IdentMetaList(ml)) => {
+                    return Err(syn::Error::new(
+                        ml.ident.span(),
+                        "`compact` doesn't accept `serde(..)` arguments",
+                    ));
+                }
+                None => {
+                    return Err(syn::Error::new(
+                        compact.get_ident().span(),
+                        "`compact` requires `serde`",
+                    ));
+                }
+            }
+        }
+
+        if let (Some(schema), None) = (&attr.schema, &attr.serde) {
+            return Err(syn::Error::new(
+                schema.get_ident().span(),
+                "`schema` requires `serde`",
+            ));
+        }
+
+        Ok(attr)
+    }
+}
+
+/// `limit = .., queue = ..`, the arguments of `concurrency(..)`.
+pub struct ConcurrencyAttr {
+    pub limit: Option<LitInt>,
+    pub queue: Option<LitInt>,
+}
+
+impl ConcurrencyAttr {
+    pub fn gen_concurrency(attr: &Option<Self>) -> TokenStream2 {
+        let option = |v: Option<&LitInt>| match v {
+            Some(v) => quote! { Some(#v) },
+            None => quote! { None },
+        };
+
+        let (limit, queue) = match attr {
+            Some(attr) => (option(attr.limit.as_ref()), option(attr.queue.as_ref())),
+            None => (option(None), option(None)),
+        };
+
+        quote! {
+            mrpc::server::Concurrency {
+                limit: #limit,
+                queue: #queue,
+            }
+        }
+    }
+}
+
+impl Parse for ConcurrencyAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut attr = Self {
+            limit: None,
+            queue: None,
+        };
+
+        for meta in input.parse_terminated::<NestedMeta, Token![,]>(NestedMeta::parse)? {
+            let nv = match &meta {
+                NestedMeta::Meta(Meta::NameValue(nv)) => nv,
+                _ => return Err(syn::Error::new(meta.span(), "Expect `name = value`")),
+            };
+
+            let v = match &nv.lit {
+                Lit::Int(v) => v.clone(),
+                lit => return Err(syn::Error::new(lit.span(), "Expect integer")),
+            };
+
+            if nv.path.is_ident("limit") {
+                set_only_none(&mut attr.limit, v, nv.span())?;
+            } else if nv.path.is_ident("queue") {
+                set_only_none(&mut attr.queue, v, nv.span())?;
+            } else {
+                return Err(syn::Error::new(nv.path.span(), "Unknown concurrency attr"));
+            }
+        }
+
         Ok(attr)
     }
 }
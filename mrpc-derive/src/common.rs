@@ -1,7 +1,65 @@
 use convert_case::{Case, Casing};
 use proc_macro2::Ident;
-use quote::format_ident;
+use quote::{format_ident, ToTokens};
+use syn::{Attribute, Lit, Meta, NestedMeta};
 
 pub fn ident_to_case(ident: &Ident, case: Case) -> Ident {
     format_ident!("{}", ident.to_string().to_case(case))
 }
+
+/// The tokens of a type as written, without the spaces `to_string` puts
+/// around punctuation: `Vec<(u8, String)>`, `&'a str`.
+pub fn type_to_string(ty: &impl ToTokens) -> String {
+    let tokens = ty.to_token_stream().to_string();
+    let tight = |c: char| "<>()[]&:".contains(c);
+
+    let mut out = String::with_capacity(tokens.len());
+    let mut chars = tokens.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ' ' {
+            let prev = out.chars().last();
+            let next = chars.peek().copied();
+            if prev.is_some_and(|prev| tight(prev) && prev != ',')
+                || next.is_some_and(|next| tight(next) || next == ',')
+            {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The `///` comments of an item, one line each.
+pub fn doc_string(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(meta)) => match meta.lit {
+                Lit::Str(lit) => Some(lit.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a field has `#[serde(default)]` or `#[serde(default = "..")]`.
+pub fn has_serde_default(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|nested| match nested {
+            NestedMeta::Meta(Meta::Path(path)) => path.is_ident("default"),
+            NestedMeta::Meta(Meta::NameValue(meta)) => meta.path.is_ident("default"),
+            _ => false,
+        })
+}
@@ -6,7 +6,8 @@ use syn::{
     parse_quote,
     punctuated::Punctuated,
     spanned::Spanned,
-    token, Attribute, FnArg, PatType, ReturnType, Token, Type,
+    token, Attribute, FnArg, Lifetime, LitInt, LitStr, PatType, PathArguments, Receiver,
+    ReturnType, Token, Type, TypePath,
 };
 
 use crate::attr::set_only_none;
@@ -18,10 +19,40 @@ pub struct RpcSignature {
     pub fn_token: Token![fn],
     pub ident: Ident,
     pub paren_token: token::Paren,
+    /// `&self` or `&mut self`. Without it the method takes
+    /// `self: Arc<Self>`.
+    pub receiver: Option<Receiver>,
     pub inputs: Punctuated<PatType, Token![,]>,
     pub output: Type,
 }
 
+impl RpcSignature {
+    pub fn is_mut(&self) -> bool {
+        self.receiver
+            .as_ref()
+            .is_some_and(|receiver| receiver.mutability.is_some())
+    }
+}
+
+fn parse_receiver(input: ParseStream) -> syn::Result<Option<Receiver>> {
+    if !(input.peek(Token![&]) && (input.peek2(Token![self]) || input.peek2(Token![mut]))) {
+        return Ok(None);
+    }
+
+    let receiver: Receiver = input.parse()?;
+    if receiver.reference.as_ref().is_some_and(|(_, lifetime)| lifetime.is_some()) {
+        return Err(syn::Error::new(
+            receiver.span(),
+            "Expect `&self` or `&mut self`",
+        ));
+    }
+    if !input.is_empty() {
+        input.parse::<Token![,]>()?;
+    }
+
+    Ok(Some(receiver))
+}
+
 impl Parse for RpcSignature {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let content;
@@ -30,10 +61,11 @@ impl Parse for RpcSignature {
             fn_token: input.parse()?,
             ident: input.parse()?,
             paren_token: parenthesized!(content in input),
+            receiver: parse_receiver(&content)?,
             inputs: content.parse_terminated(|input| match FnArg::parse(input)? {
                 FnArg::Receiver(arg) => Err(syn::Error::new(
                     arg.span(),
-                    "method args cannot start with self",
+                    "Expect `&self` or `&mut self` as the first argument",
                 )),
                 FnArg::Typed(arg) => match *arg.pat {
                     syn::Pat::Ident(_) => Ok(arg),
@@ -54,36 +86,47 @@ impl Parse for RpcSignature {
 #[allow(dead_code)]
 pub struct RpcMethod {
     pub attrs: RpcAttrs,
+    /// Attributes other than `#[rpc]`, kept on the trait and client methods.
+    pub fn_attrs: Vec<Attribute>,
     pub sig: RpcSignature,
     pub semi_token: Option<Token![;]>,
 }
 
+impl RpcMethod {
+    /// `#[cfg]` attributes, kept on everything generated for the method.
+    pub fn cfg_attrs(&self) -> impl Iterator<Item = &Attribute> {
+        self.fn_attrs.iter().filter(|attr| attr.path.is_ident("cfg"))
+    }
+
+    /// Attributes kept on the request and response variants. Not
+    /// `#[deprecated]`, which serde's derive would trip on.
+    pub fn variant_attrs(&self) -> impl Iterator<Item = &Attribute> {
+        self.fn_attrs
+            .iter()
+            .filter(|attr| ["cfg", "doc", "allow"].iter().any(|name| attr.path.is_ident(name)))
+    }
+}
+
 impl Parse for RpcMethod {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut rpc_attrs = RpcAttrs::new();
-        let attrs = input.call(Attribute::parse_outer)?;
-        if attrs.len() > 1 {
-            return Err(syn::Error::new(input.span(), "Expect one attr"));
-        }
+        let mut rpc_attrs = None;
+        let mut fn_attrs = Vec::new();
 
-        if let Some(attr) = attrs.first() {
-            if let Some(ident) = attr.path.get_ident() {
-                if ident != "rpc" {
-                    return Err(syn::Error::new(attr.span(), "Expect rpc attr"));
-                } else {
-                    let ParenRpcAttrs {
-                        paren_token: _,
-                        inner,
-                    } = syn::parse2::<ParenRpcAttrs>(attr.tokens.clone())?;
-                    rpc_attrs = inner;
-                }
+        for attr in input.call(Attribute::parse_outer)? {
+            if attr.path.is_ident("rpc") {
+                let ParenRpcAttrs {
+                    paren_token: _,
+                    inner,
+                } = syn::parse2::<ParenRpcAttrs>(attr.tokens.clone())?;
+                set_only_none(&mut rpc_attrs, inner, attr.span())?;
             } else {
-                return Err(syn::Error::new(attr.span(), "Expect rpc attr"));
+                fn_attrs.push(attr);
             }
         }
 
         Ok(Self {
-            attrs: rpc_attrs,
+            attrs: rpc_attrs.unwrap_or_else(RpcAttrs::new),
+            fn_attrs,
             sig: input.parse()?,
             semi_token: input.parse()?,
         })
@@ -92,25 +135,72 @@ impl Parse for RpcMethod {
 
 pub struct RpcAttrs {
     pub message: Option<MessageAttr>,
+    pub sequential: bool,
+    /// The client method takes its arguments by reference.
+    pub borrow: bool,
+    /// Name of the variants on the wire.
+    pub name: Option<LitStr>,
+    /// Number of the method on the wire with `message(compact)`.
+    pub id: Option<LitInt>,
 }
 
 impl RpcAttrs {
     fn new() -> Self {
-        Self { message: None }
+        Self {
+            message: None,
+            sequential: false,
+            borrow: false,
+            name: None,
+            id: None,
+        }
+    }
+}
+
+fn set_flag(flag: &mut bool, ident: &Ident) -> syn::Result<()> {
+    if *flag {
+        return Err(syn::Error::new(ident.span(), "Duplicate identifier"));
     }
+    *flag = true;
+    Ok(())
 }
 
 impl Parse for RpcAttrs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let attr_vec = input.parse_terminated::<MessageAttr, Token![,]>(MessageAttr::parse)?;
+        let mut attrs = Self::new();
 
-        let mut message = None;
+        while !input.is_empty() {
+            let ident = input.fork().parse::<Ident>()?;
+            match ident.to_string().as_str() {
+                "sequential" => {
+                    set_flag(&mut attrs.sequential, &input.parse()?)?;
+                }
+                "borrow" => {
+                    set_flag(&mut attrs.borrow, &input.parse()?)?;
+                }
+                "name" => {
+                    input.parse::<Ident>()?;
+                    input.parse::<Token![=]>()?;
+                    set_only_none(&mut attrs.name, input.parse()?, ident.span())?;
+                }
+                "id" => {
+                    input.parse::<Ident>()?;
+                    input.parse::<Token![=]>()?;
+                    let id: LitInt = input.parse()?;
+                    id.base10_parse::<u32>()?;
+                    set_only_none(&mut attrs.id, id, ident.span())?;
+                }
+                _ => {
+                    set_only_none(&mut attrs.message, input.parse()?, input.span())?;
+                }
+            }
 
-        for attr in attr_vec {
-            set_only_none(&mut message, attr, input.span())?;
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
         }
 
-        Ok(Self { message })
+        Ok(attrs)
     }
 }
 
@@ -129,3 +219,26 @@ impl Parse for ParenRpcAttrs {
         })
     }
 }
+
+/// The type a client passes for an argument of type `ty` in a
+/// `#[rpc(borrow)]` method: `&str` for `String`, `&[T]` for `Vec<T>`,
+/// `&T` otherwise. `None` for primitives, which are passed by value.
+pub fn borrowed_ty(ty: &Type, lifetime: Option<&Lifetime>) -> Option<Type> {
+    if let Type::Path(TypePath { qself: None, path }) = ty {
+        let last = path.segments.last().expect("");
+        match (last.ident.to_string().as_str(), &last.arguments) {
+            ("String", PathArguments::None) => return Some(parse_quote!(&#lifetime str)),
+            ("Vec", PathArguments::AngleBracketed(args)) if args.args.len() == 1 => {
+                let arg = &args.args[0];
+                return Some(parse_quote!(&#lifetime [#arg]));
+            }
+            (
+                "bool" | "char" | "f32" | "f64" | "i8" | "i16" | "i32" | "i64" | "i128"
+                | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize",
+                PathArguments::None,
+            ) if path.segments.len() == 1 => return None,
+            _ => {}
+        }
+    }
+    Some(parse_quote!(&#lifetime #ty))
+}
@@ -1,78 +1,228 @@
-use crate::{common::*, attr::{MessageAttr, set_only_none}};
+use crate::{
+    attr::{set_only_none, ConcurrencyAttr, MessageAttr, SerdeNames},
+    common::*,
+};
 use convert_case::Case;
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote, ToTokens};
 use syn::{
     braced, parenthesized,
     parse::{Parse, ParseStream},
     parse_macro_input,
     punctuated::Punctuated,
-    token, Attribute, Ident, Path, Token, Visibility,
+    spanned::Spanned,
+    parse_quote, token, Attribute, Ident, Path, PathArguments, Token, Visibility,
 };
 
+/// How long a service instance created by `create_<service>` lives.
+enum Scope {
+    /// One instance for the server.
+    Singleton,
+    /// One instance for each connection, dropped when it closes.
+    Connection,
+    /// One instance for each call.
+    Call,
+}
+
+impl Parse for Scope {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "singleton" => Ok(Scope::Singleton),
+            "connection" => Ok(Scope::Connection),
+            "call" => Ok(Scope::Call),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "Expect `singleton`, `connection` or `call`",
+            )),
+        }
+    }
+}
+
 #[allow(dead_code)]
 struct ServiceItem {
     pub attrs: Vec<Attribute>,
+    pub concurrency: Option<ConcurrencyAttr>,
+    pub sequential: bool,
+    pub scope: Option<Scope>,
+    pub eager: bool,
     pub ident: Ident,
     pub paren_token: token::Paren,
     pub ty: Path,
+    /// Body of `create_<service>` for the services added by the server
+    /// attributes.
+    pub default_create: Option<TokenStream2>,
+}
+
+impl ServiceItem {
+    /// The `mrpc::reflection::Reflection` service added by `reflection`,
+    /// named `Reflection` on the wire whatever the `rename_all` of the server.
+    fn reflection(request_ident: &Ident, serde: bool) -> Self {
+        let attrs = if serde {
+            vec![parse_quote!(#[serde(rename = "Reflection")])]
+        } else {
+            Vec::new()
+        };
+        Self {
+            attrs,
+            concurrency: None,
+            sequential: false,
+            scope: None,
+            eager: false,
+            ident: format_ident!("Reflection"),
+            paren_token: Default::default(),
+            ty: parse_quote!(mrpc::reflection::Reflection),
+            default_create: Some(quote! {
+                Ok(std::sync::Arc::new(mrpc::reflection::ServerReflection::new(
+                    #request_ident::descriptor(),
+                )))
+            }),
+        }
+    }
 }
 
 impl Parse for ServiceItem {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut attrs = Vec::new();
+        let mut concurrency = None;
+        let mut sequential = false;
+        let mut scope = None;
+        let mut eager = false;
+
+        for attr in input.call(Attribute::parse_outer)? {
+            if attr.path.is_ident("scope") {
+                set_only_none(&mut scope, attr.parse_args()?, attr.span())?;
+            } else if attr.path.is_ident("concurrency") {
+                set_only_none(&mut concurrency, attr.parse_args()?, attr.span())?;
+            } else if attr.path.is_ident("sequential") {
+                if !attr.tokens.is_empty() || sequential {
+                    return Err(syn::Error::new(attr.span(), "Expect one `#[sequential]`"));
+                }
+                sequential = true;
+            } else if attr.path.is_ident("eager") {
+                if !attr.tokens.is_empty() || eager {
+                    return Err(syn::Error::new(attr.span(), "Expect one `#[eager]`"));
+                }
+                eager = true;
+            } else {
+                attrs.push(attr);
+            }
+        }
+
+        if eager && !matches!(scope, None | Some(Scope::Singleton)) {
+            return Err(syn::Error::new(
+                input.span(),
+                "Only singleton services can be `#[eager]`",
+            ));
+        }
+
         let content;
         Ok(Self {
-            attrs: input.call(Attribute::parse_outer)?,
+            attrs,
+            concurrency,
+            sequential,
+            scope,
+            eager,
             ident: input.parse()?,
             paren_token: parenthesized!(content in input),
             ty: content.parse::<Path>()?,
+            default_create: None,
         })
     }
 }
 
 struct ServerAttrs {
     message: Option<MessageAttr>,
+    concurrency: Option<ConcurrencyAttr>,
+    /// Serve `mrpc::reflection::Reflection` as the `Reflection` service.
+    reflection: Option<Span>,
 }
 
 impl ServerAttrs {
     fn new() -> Self {
-        Self { message: None }
+        Self {
+            message: None,
+            concurrency: None,
+            reflection: None,
+        }
     }
 
     fn gen_message_attr(&self) -> TokenStream2 {
-        let mut token = TokenStream2::new();
+        self.message
+            .as_ref()
+            .map(MessageAttr::gen_attrs)
+            .unwrap_or_default()
+    }
 
-        if let Some(attr) = &self.message {
-            if let Some(_) = &attr.debug {
-                token.extend(quote! {
-                    #[derive(Debug)]
-                });
-            }
+    fn gen_serialize_attr(&self) -> TokenStream2 {
+        self.message
+            .as_ref()
+            .map(MessageAttr::gen_serialize_attrs)
+            .unwrap_or_default()
+    }
 
-            if let Some(_) = &attr.serde {
-                token.extend(quote! {
-                    #[derive(mrpc::serde::Serialize,mrpc::serde::Deserialize)]
-                    #[serde(crate = "mrpc::serde")]
-                });
-            }
-        }
+    fn is_serde(&self) -> bool {
+        self.message
+            .as_ref()
+            .is_some_and(|message| message.serde.is_some())
+    }
 
-        token
+    fn is_schema(&self) -> bool {
+        self.message
+            .as_ref()
+            .is_some_and(|message| message.schema.is_some())
     }
 }
 
 impl Parse for ServerAttrs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let attr_vec = input.parse_terminated::<MessageAttr, Token![,]>(MessageAttr::parse)?;
+        let mut attrs = Self::new();
+
+        while !input.is_empty() {
+            let ident = input.fork().parse::<Ident>()?;
+            match ident.to_string().as_str() {
+                "concurrency" => {
+                    input.parse::<Ident>()?;
+                    let content;
+                    parenthesized!(content in input);
+                    set_only_none(&mut attrs.concurrency, content.parse()?, ident.span())?;
+                }
+                "reflection" => {
+                    input.parse::<Ident>()?;
+                    set_only_none(&mut attrs.reflection, ident.span(), ident.span())?;
+                }
+                _ => {
+                    let message: MessageAttr = input.parse()?;
+                    if let Some(compact) = &message.compact {
+                        return Err(syn::Error::new(
+                            compact.get_ident().span(),
+                            "`compact` is only supported on services",
+                        ));
+                    }
+                    set_only_none(&mut attrs.message, message, ident.span())?;
+                }
+            }
 
-        let mut message = None;
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
 
-        for attr in attr_vec {
-            set_only_none(&mut message, attr, input.span())?;
+        // Clients find the reflection service before knowing the server, so
+        // it is always `{"Reflection": ..}`, see `mrpc::reflection`.
+        let names = attrs.message.as_ref().map(|message| &message.names);
+        if let (Some(span), Some(names)) = (attrs.reflection, names) {
+            if names.tag.is_some() || names.untagged {
+                return Err(syn::Error::new(
+                    span,
+                    "`reflection` needs the services to be externally tagged",
+                ));
+            }
         }
 
-        Ok(Self { message })
+        Ok(attrs)
     }
 }
 
@@ -116,6 +266,17 @@ impl Server {
         format_ident!("{}Request", self.ident)
     }
 
+    /// The request of the messages to the server: a `mrpc::Payload` with
+    /// `message(serde)`, whose clients serialize borrowed requests.
+    fn channel_request_ty(&self) -> TokenStream2 {
+        let request_ident = self.request_ident();
+        if self.server_attrs.is_serde() {
+            quote! { mrpc::Payload<#request_ident> }
+        } else {
+            quote! { #request_ident }
+        }
+    }
+
     fn response_ident(&self) -> Ident {
         format_ident!("{}Response", self.ident)
     }
@@ -136,26 +297,40 @@ impl Server {
         format_ident!("{}_var", ident_to_case(ident, Case::Snake))
     }
 
+    fn lane_ident(ident: &Ident) -> Ident {
+        format_ident!("{}_lane", ident_to_case(ident, Case::Snake))
+    }
+
+    fn backoff_ident(ident: &Ident) -> Ident {
+        format_ident!("{}_backoff", ident_to_case(ident, Case::Snake))
+    }
+
     fn service_request_ty(mut ty: Path) -> Path {
-        let mut last = ty.segments.last_mut().expect("");
+        let last = ty.segments.last_mut().expect("");
         last.ident = format_ident!("{}Request", last.ident);
         ty
     }
 
     fn service_response_ty(mut ty: Path) -> Path {
-        let mut last = ty.segments.last_mut().expect("");
+        let last = ty.segments.last_mut().expect("");
         last.ident = format_ident!("{}Response", last.ident);
         ty
     }
 
-    fn service_client_ty(mut ty: Path) -> Path {
-        let mut last = ty.segments.last_mut().expect("");
+    /// The client type of a service, its type arguments followed by the
+    /// poster.
+    fn service_client_ty(mut ty: Path, poster: &Ident) -> Path {
+        let last = ty.segments.last_mut().expect("");
         last.ident = format_ident!("{}Client", last.ident);
+        match &mut last.arguments {
+            PathArguments::AngleBracketed(args) => args.args.push(parse_quote!(#poster)),
+            arguments => *arguments = PathArguments::AngleBracketed(parse_quote!(<#poster>)),
+        }
         ty
     }
 
     fn service_poster_ty(mut ty: Path) -> Path {
-        let mut last = ty.segments.last_mut().expect("");
+        let last = ty.segments.last_mut().expect("");
         last.ident = format_ident!("{}Poster", last.ident);
         ty
     }
@@ -165,18 +340,16 @@ impl Server {
             .services
             .iter()
             .map(
-                |ServiceItem {
-                     attrs: _,
-                     ident,
-                     paren_token: _,
-                     ty,
-                 }| {
+                |ServiceItem { ident, ty, default_create, .. }| {
                     let create_service_ident =
                         Self::create_service_ident(ident);
+                    let body = default_create.clone().unwrap_or_else(|| {
+                        quote! { Err(mrpc::anyhow::anyhow!("service is not implemented")) }
+                    });
 
                     quote! {
-                        async fn #create_service_ident(self: std::sync::Arc<Self>) -> mrpc::anyhow::Result<std::sync::Arc<dyn #ty>> {
-                            mrpc::anyhow::bail!("service is not implemented");
+                        async fn #create_service_ident(self: std::sync::Arc<Self>) -> mrpc::anyhow::Result<std::sync::Arc<<dyn #ty as mrpc::server::Dispatch>::Target>> {
+                            #body
                         }
                     }
                 },
@@ -185,51 +358,132 @@ impl Server {
     }
 
     fn gen_server_serve(&self) -> TokenStream2 {
-        let (request_ident, response_ident) = (self.request_ident(), self.response_ident());
+        let (request_ident, response_ident, server_ident) = (
+            self.request_ident(),
+            self.response_ident(),
+            self.server_ident(),
+        );
+
+        let global_concurrency = ConcurrencyAttr::gen_concurrency(&self.server_attrs.concurrency);
+
+        let eager_services = self
+            .services
+            .iter()
+            .filter(|service| service.eager)
+            .map(|ServiceItem { ident, .. }| {
+                let create_service_ident = Self::create_service_ident(ident);
+                let service_var_ident = Self::service_var_ident(ident);
+                let backoff_ident = Self::backoff_ident(ident);
+
+                quote! {
+                    #service_var_ident
+                        .lock()
+                        .await
+                        .get(stringify!(#ident), &options, &#backoff_ident, || Self::#create_service_ident(self.clone()))
+                        .await?;
+                }
+            });
 
         let (service_vars, match_items): (Vec<_>, Vec<_>) = self
             .services
             .iter()
             .map(
                 |ServiceItem {
-                     attrs: _,
                      ident,
-                     paren_token: _,
-                     ty: _,
+                     concurrency,
+                     sequential,
+                     scope,
+                     ty,
+                     ..
                  }| {
                     let create_service_ident = Self::create_service_ident(ident);
                     let service_var_ident = Self::service_var_ident(ident);
+                    let lane_ident = Self::lane_ident(ident);
+                    let backoff_ident = Self::backoff_ident(ident);
+                    let concurrency = ConcurrencyAttr::gen_concurrency(concurrency);
 
                     let service_var_ident_tmp = format_ident!("{}_tmp", service_var_ident);
-                    
+
+                    let (service_var, service_slot) = match scope.as_ref().unwrap_or(&Scope::Singleton) {
+                        Scope::Singleton => (
+                            quote! {
+                                let #service_var_ident = std::sync::Arc::new(mrpc::sync::Mutex::new(mrpc::server::Instance::default()));
+                            },
+                            quote! { #service_var_ident_tmp },
+                        ),
+                        Scope::Connection => (
+                            quote! {},
+                            quote! {
+                                conn.slot(concat!(module_path!(), "::", stringify!(#server_ident), "::", stringify!(#ident)))
+                            },
+                        ),
+                        Scope::Call => (
+                            quote! {},
+                            quote! { std::sync::Arc::new(mrpc::sync::Mutex::new(mrpc::server::Instance::default())) },
+                        ),
+                    };
+                    let service_var_tmp = matches!(scope, None | Some(Scope::Singleton)).then(|| {
+                        quote! {
+                            let #service_var_ident_tmp = #service_var_ident.clone();
+                        }
+                    });
+
                     (
                         quote! {
-                            let mut #service_var_ident = std::sync::Arc::new(mrpc::sync::Mutex::new(None));
+                            #service_var
+                            let #lane_ident = scheduler.lane(stringify!(#ident), #concurrency, #sequential);
+                            let #backoff_ident = std::sync::Arc::new(mrpc::server::Backoff::default());
                         },
                         quote! {
                             #request_ident::#ident(req) => {
-                                let #service_var_ident_tmp = #service_var_ident.clone();
+                                span.record_method(stringify!(#ident), req.method_name());
+                                let call = mrpc::metrics::Call::server(stringify!(#ident), req.method_name());
+                                let ticket = match #lane_ident.admit(req.is_sequential()) {
+                                    Ok(ticket) => ticket,
+                                    Err(e) => {
+                                        span.record_outcome::<(), _>(&Err(&e));
+                                        call.finish::<(), _>(&Err(&e));
+                                        if resp.send(Err(e)).is_err() {
+                                            mrpc::log::warn!("Failed to send response: {}", stringify!(#ident));
+                                        }
+                                        continue;
+                                    }
+                                };
+
+                                #service_var_tmp
+                                let conn = conn.clone();
+                                let options = options.clone();
                                 let self_ = self.clone();
-                                mrpc::spawn(async move {
-                                    let service = {
-                                        let mut lock = #service_var_ident_tmp.lock().await;
-                                        if lock.is_none() {
-                                            *lock = match Self::#create_service_ident(self_).await {
-                                                Ok(service) => Some(service),
+                                let backoff = #backoff_ident.clone();
+                                ticket.spawn(span.clone().instrument(async move {
+                                    let slot: std::sync::Arc<mrpc::sync::Mutex<mrpc::server::Instance<std::sync::Arc<<dyn #ty as mrpc::server::Dispatch>::Target>>>> = #service_slot;
+                                    let service = slot
+                                        .lock()
+                                        .await
+                                        .get(stringify!(#ident), &options, &backoff, || Self::#create_service_ident(self_))
+                                        .await;
+
+                                    let result = match service {
+                                        Ok(service) => {
+                                            let method = req.method_name();
+                                            match mrpc::server::catch_panic(stringify!(#ident), method, <dyn #ty as mrpc::server::Dispatch>::dispatch(service.clone(), req)).await {
+                                                Ok(v) => Ok(#response_ident::#ident(v)),
                                                 Err(e) => {
-                                                    mrpc::log::warn!("Failed to create {}: {:?}", stringify!(#ident), e);
-                                                    None
+                                                    if options.recreate_on_panic {
+                                                        slot.lock().await.poison(|s| std::sync::Arc::ptr_eq(s, &service));
+                                                    }
+                                                    Err(e)
                                                 }
-                                            };
+                                            }
                                         }
-                                        lock.as_ref().unwrap().clone()
+                                        Err(e) => Err(e),
                                     };
-
-                                    let result = #response_ident::#ident(service.clone().serve(req).await);
-                                    if let Err(_) = msg.resp.send(result) {
+                                    span.record_outcome(&result);
+                                    call.finish(&result);
+                                    if resp.send(result).is_err() {
                                         mrpc::log::warn!("Failed to send response: {}", stringify!(#ident));
                                     }
-                                });
+                                }));
                             }
                         },
                     )
@@ -237,17 +491,38 @@ impl Server {
             )
             .unzip();
 
+        let decode_payload = self.server_attrs.is_serde().then(|| {
+            quote! {
+                let req = match req.decode() {
+                    Ok(req) => req,
+                    Err(e) => {
+                        span.record_outcome::<(), _>(&Err(&e));
+                        if resp.send(Err(e)).is_err() {
+                            mrpc::log::warn!("Failed to send response");
+                        }
+                        continue;
+                    }
+                };
+            }
+        });
+        let channel_request = self.channel_request_ty();
+
         quote! {
             async fn serve(self: std::sync::Arc<Self>,
-                           mut rx: mrpc::sync::mpsc::Receiver<mrpc::Message<#request_ident, #response_ident>>)
+                           mut rx: mrpc::sync::mpsc::Receiver<mrpc::Message<#channel_request, #response_ident>>)
                            -> mrpc::anyhow::Result<()>
             where Self: 'static {
 
+                let options = self.serve_options();
+                let scheduler = mrpc::server::Scheduler::new(#global_concurrency);
+
                 #( #service_vars )*
+                #( #eager_services )*
 
-                while let Some(msg) = rx.recv().await {
-                    match msg.req {
-                        #( #match_items )*     
+                while let Some(mrpc::Message { req, resp, conn, span, .. }) = rx.recv().await {
+                    #decode_payload
+                    match req {
+                        #( #match_items )*
                     };
                 }
 
@@ -266,6 +541,12 @@ impl Server {
             #[mrpc::async_trait]
             #vis trait #server_ident: Send + Sync {
                 #( #fn_create_services )*
+
+                fn serve_options(&self) -> mrpc::server::ServeOptions {
+                    Default::default()
+                }
+
+
                 #fn_serve
             }
         }
@@ -276,10 +557,7 @@ impl Server {
 
         let services = self.services.iter().map(
             |ServiceItem {
-                 attrs,
-                 ident,
-                 paren_token: _,
-                 ty,
+                 attrs, ident, ty, ..
              }| {
                 let service_request = Self::service_request_ty(ty.clone());
 
@@ -290,11 +568,116 @@ impl Server {
             },
         );
 
+        let descriptor = self.gen_descriptor();
+
         quote! {
             #message_attr
             #vis enum #request_ident {
                 #( #services ),*
             }
+
+            #descriptor
+        }
+    }
+
+    /// `descriptor()` of the request, see `mrpc::descriptor`.
+    fn gen_descriptor(&self) -> TokenStream2 {
+        let (vis, name, request_ident) = (
+            &self.vis,
+            self.ident.to_string(),
+            self.request_ident(),
+        );
+
+        let names = self.server_attrs.message.as_ref().map(|message| &message.names);
+        let tagging = match names {
+            Some(names) => names.gen_tagging(),
+            None => quote! { mrpc::descriptor::Tagging::External },
+        };
+
+        let services = self.services.iter().map(|ServiceItem { attrs, ident, ty, .. }| {
+            let cfg_attrs = attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+            let name = ident.to_string();
+            let wire_name = match SerdeNames::from_attrs(attrs) {
+                Ok(SerdeNames {
+                    rename: Some(rename),
+                    ..
+                }) => rename,
+                Ok(_) => names.map_or_else(|| name.clone(), |names| names.variant_name(&name)),
+                Err(e) => return e.to_compile_error(),
+            };
+            let wire_name = if wire_name == name {
+                quote! { None }
+            } else {
+                quote! { Some(#wire_name.to_string()) }
+            };
+            let type_args = match &ty.segments.last().expect("").arguments {
+                PathArguments::AngleBracketed(args) => {
+                    args.args.iter().map(type_to_string).collect()
+                }
+                _ => Vec::new(),
+            };
+
+            quote! {
+                #( #cfg_attrs )*
+                services.push(mrpc::descriptor::ServiceEntry {
+                    name: #name.to_string(),
+                    wire_name: #wire_name,
+                    type_args: vec![#( #type_args.to_string() ),*],
+                    service: <dyn #ty as mrpc::server::Dispatch>::descriptor(),
+                });
+            }
+        });
+
+        quote! {
+            impl #request_ident {
+                /// Describes the services of the server, see
+                /// [`mrpc::descriptor`].
+                #[allow(clippy::vec_init_then_push)]
+                #vis fn descriptor() -> mrpc::descriptor::ServerDescriptor {
+                    let mut services = Vec::new();
+                    #( #services )*
+
+                    mrpc::descriptor::ServerDescriptor {
+                        name: #name.to_string(),
+                        tagging: #tagging,
+                        services,
+                    }
+                }
+            }
+        }
+    }
+
+    /// `openrpc()` of the request, see `mrpc::openrpc`.
+    fn gen_openrpc(&self) -> TokenStream2 {
+        let (vis, request_ident) = (&self.vis, self.request_ident());
+
+        let services = self.services.iter().map(|ServiceItem { attrs, ty, .. }| {
+            let cfg_attrs = attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+
+            quote! {
+                #( #cfg_attrs )*
+                services.push(<<dyn #ty as mrpc::server::Dispatch>::Request as mrpc::openrpc::MethodSchemas>::method_schemas(&mut gen));
+            }
+        });
+
+        quote! {
+            impl #request_ident {
+                /// Describes the services of the server as an OpenRPC
+                /// document, see [`mrpc::openrpc`].
+                #[allow(clippy::vec_init_then_push)]
+                #vis fn openrpc() -> mrpc::openrpc::OpenRpc {
+                    let mut gen = mrpc::openrpc::generator();
+                    let mut services = Vec::new();
+                    #( #services )*
+
+                    mrpc::openrpc::document(
+                        &Self::descriptor(),
+                        env!("CARGO_PKG_VERSION"),
+                        services,
+                        gen,
+                    )
+                }
+            }
         }
     }
 
@@ -303,10 +686,7 @@ impl Server {
 
         let services = self.services.iter().map(
             |ServiceItem {
-                 attrs,
-                 ident,
-                 paren_token: _,
-                 ty,
+                 attrs, ident, ty, ..
              }| {
                 let service_response = Self::service_response_ty(ty.clone());
 
@@ -333,73 +713,121 @@ impl Server {
             self.response_ident(),
         );
 
+        let channel_request = self.channel_request_ty();
         let sender_ty = quote! {
-            mrpc::sync::mpsc::Sender<mrpc::Message<#request_ident, #response_ident>>
+            mrpc::sync::mpsc::Sender<mrpc::Message<#channel_request, #response_ident>>
         };
+        let serialize_attr = self.server_attrs.gen_serialize_attr();
 
         let (posters, rpcs): (Vec<TokenStream2>, Vec<TokenStream2>) = self.services.iter().map(
-            |ServiceItem {
-                 attrs: _,
-                 ident,
-                 paren_token: _,
-                 ty,
-             }| {
+            |ServiceItem { attrs, ident, ty, .. }| {
                 let service_ident = ident_to_case(ident, Case::Snake);
-                let service_client = Self::service_client_ty(ty.clone());
+
                 let service_request = Self::service_request_ty(ty.clone());
                 let service_response = Self::service_response_ty(ty.clone());
                 let service_poster_ty = Self::service_poster_ty(ty.clone());
                 let service_poster_impl_ident = format_ident!("{}{}PosterImpl", self.server_ident(), ident);
+                let service_client = Self::service_client_ty(ty.clone(), &service_poster_impl_ident);
+                let post_ref_ty = quote! {
+                    fn post_ref(&self, req: R,
+                                resp: mrpc::sync::oneshot::Sender<
+                                        std::result::Result<#service_response, mrpc::Error>
+                                    >) -> std::pin::Pin<Box<dyn std::future::Future<Output = mrpc::anyhow::Result<()>> + Send>>
+                };
+                let post_ref = if self.server_attrs.is_serde() {
+                    quote! {
+                        impl<R> mrpc::PostRef<R, #service_response> for #service_poster_impl_ident
+                        where R: mrpc::serde::Serialize {
+                            #post_ref_ty {
+                                /// The request of the server around the borrowed one.
+                                #serialize_attr
+                                enum Wire<R> {
+                                    #( #attrs )*
+                                    #ident(R),
+                                }
+
+                                let (req, poster) = (mrpc::Encoded::new(&Wire::#ident(req)), self.clone());
+                                Box::pin(async move { poster.send(mrpc::Payload::Encoded(req?), resp).await })
+                            }
+                        }
+                    }
+                } else {
+                    quote! {
+                        impl<R> mrpc::PostRef<R, #service_response> for #service_poster_impl_ident
+                        where R: mrpc::IntoOwned<Owned = #service_request> {
+                            #post_ref_ty {
+                                let (req, poster) = (req.into_owned(), self.clone());
+                                Box::pin(async move { mrpc::Poster::post(&poster, req, resp).await })
+                            }
+                        }
+                    }
+                };
 
                 (
                     quote! {
                         #[derive(Clone)]
                         #vis struct #service_poster_impl_ident {
-                            pub sender: #sender_ty
+                            pub sender: #sender_ty,
+                            pub conn: std::sync::Arc<mrpc::Connection>,
                         }
 
                         impl #service_poster_ty for #service_poster_impl_ident {}
 
+                        #post_ref
+
                         #[mrpc::async_trait]
                         impl mrpc::Poster<#service_request, #service_response> for #service_poster_impl_ident {
                             async fn post(&self, req: #service_request,
                                           resp: mrpc::sync::oneshot::Sender<
-                                                  #service_response
+                                                  std::result::Result<#service_response, mrpc::Error>
+                                              >) -> mrpc::anyhow::Result<()> {
+                                self.send(#request_ident::#ident(req).into(), resp).await
+                            }
+                        }
+
+                        impl #service_poster_impl_ident {
+                            async fn send(&self, req: #channel_request,
+                                          resp: mrpc::sync::oneshot::Sender<
+                                                  std::result::Result<#service_response, mrpc::Error>
                                               >) -> mrpc::anyhow::Result<()> {
                                 let (tx, rx) = mrpc::sync::oneshot::channel();
 
                                 if let Err(e) = self.sender.send(mrpc::Message {
-                                    req: #request_ident::#ident(req),
-                                    resp: tx
+                                    req,
+                                    resp: tx,
+                                    conn: self.conn.clone(),
+                                    span: mrpc::trace::Span::current(),
+                                    metadata: mrpc::Metadata::new(),
                                 }).await {
                                     mrpc::anyhow::bail!("Failed to send message: {}", e);
                                 }
 
-                                match rx.await {
-                                    Ok(recv) => {
-                                        match recv {
-                                            #response_ident::#ident(v) => {
-                                                if let Err(e) = resp.send(v) {
-                                                    mrpc::anyhow::bail!("Failed to send message to {}", stringify!(#ident));    
-                                                } else {
-                                                    return Ok(())
-                                                }
-                                            }
-                                            _ => {
-                                                mrpc::anyhow::bail!("Failed to match response, require {}", stringify!(#ident));
-                                            }
-                                        }
+                                let result = match rx.await {
+                                    Ok(Ok(#response_ident::#ident(v))) => Ok(v),
+                                    Ok(Err(e)) => Err(e),
+                                    #[allow(unreachable_patterns)]
+                                    Ok(Ok(_)) => {
+                                        mrpc::anyhow::bail!("Failed to match response, require {}", stringify!(#ident));
                                     }
                                     Err(e) => {
                                         mrpc::anyhow::bail!("Failed to wait response: {}", e);
                                     }
+                                };
+
+                                if resp.send(result).is_err() {
+                                    mrpc::anyhow::bail!("Failed to send message to {}", stringify!(#ident));
                                 }
+
+                                Ok(())
                             }
                         }
                     },
                     quote! {
-                        #vis fn #service_ident(&self) -> #service_client<#service_poster_impl_ident> {
-                            #service_client { poster: #service_poster_impl_ident { sender: self.sender.clone() } }
+                        #vis fn #service_ident(&self) -> #service_client {
+                            <#service_client>::new(#service_poster_impl_ident {
+                                sender: self.sender.clone(),
+                                conn: self.conn.clone(),
+                            })
                         }
                     },
                 )
@@ -407,9 +835,12 @@ impl Server {
         ).unzip();
 
         quote! {
+            /// Built with `new`, each client being a connection of its own.
+            /// Clones share the connection of the client they were cloned from.
             #[derive(Clone)]
             #vis struct #client_ident {
                 sender: #sender_ty,
+                conn: std::sync::Arc<mrpc::Connection>,
             }
 
             #( #posters )*
@@ -418,10 +849,11 @@ impl Server {
 
                 #vis fn new(sender: #sender_ty) -> Self {
                     Self {
-                        sender
+                        sender,
+                        conn: mrpc::Connection::new(None),
                     }
                 }
-                
+
                 #( #rpcs )*
             }
         }
@@ -435,12 +867,26 @@ impl ToTokens for Server {
             self.gen_response(),
             self.gen_server(),
             self.gen_client(),
-        ])
+        ]);
+
+        if self.server_attrs.is_schema() {
+            tokens.extend(self.gen_openrpc());
+        }
     }
 }
 
 pub fn parse(attrs: TokenStream, input: TokenStream) -> TokenStream {
     let mut server = parse_macro_input!(input as Server);
     server.server_attrs = parse_macro_input!(attrs as ServerAttrs);
+    if server.server_attrs.reflection.is_some() {
+        let serde = server.server_attrs.is_serde();
+        let item = ServiceItem::reflection(&server.request_ident(), serde);
+        if let Some(other) = server.services.iter().find(|other| other.ident == item.ident) {
+            return syn::Error::new(other.ident.span(), "`Reflection` is taken by `reflection`")
+                .to_compile_error()
+                .into();
+        }
+        server.services.push(item);
+    }
     server.into_token_stream().into()
 }
@@ -1,17 +1,20 @@
 use crate::{
-    attr::{set_only_none, IdentMeta, MessageAttr},
+    attr::{set_only_none, MessageAttr},
     common::*,
-    rpc::{RpcAttrs, RpcMethod, RpcSignature},
+    rpc::{borrowed_ty, RpcAttrs, RpcMethod, RpcSignature},
 };
 use convert_case::Case;
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, TokenStream as TokenStream2};
 use quote::{format_ident, quote, ToTokens};
-use syn::Visibility;
+use std::collections::HashSet;
 use syn::{
     braced,
     parse::{Parse, ParseStream},
-    parse_macro_input, token, Token,
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, GenericParam, Generics, Lifetime, LitInt, PatType, Token, TypeParamBound, Visibility,
 };
 
 struct ServiceAttrs {
@@ -24,24 +27,35 @@ impl ServiceAttrs {
     }
 
     fn gen_message_attr(&self) -> TokenStream2 {
-        let mut token = TokenStream2::new();
+        self.message
+            .as_ref()
+            .map(MessageAttr::gen_attrs)
+            .unwrap_or_default()
+    }
 
-        if let Some(attr) = &self.message {
-            if let Some(_) = &attr.debug {
-                token.extend(quote! {
-                    #[derive(Debug)]
-                });
-            }
+    fn gen_serialize_attr(&self) -> TokenStream2 {
+        self.message
+            .as_ref()
+            .map(MessageAttr::gen_serialize_attrs)
+            .unwrap_or_default()
+    }
 
-            if let Some(_) = &attr.serde {
-                token.extend(quote! {
-                    #[derive(mrpc::serde::Serialize,mrpc::serde::Deserialize)]
-                    #[serde(crate = "mrpc::serde")]
-                });
-            }
-        }
+    fn is_serde(&self) -> bool {
+        self.message
+            .as_ref()
+            .is_some_and(|message| message.serde.is_some())
+    }
 
-        token
+    fn is_compact(&self) -> bool {
+        self.message
+            .as_ref()
+            .is_some_and(|message| message.compact.is_some())
+    }
+
+    fn is_schema(&self) -> bool {
+        self.message
+            .as_ref()
+            .is_some_and(|message| message.schema.is_some())
     }
 }
 
@@ -65,35 +79,177 @@ struct Service {
     pub vis: Visibility,
     pub trait_token: Token![trait],
     pub ident: Ident,
+    /// Type parameters, each bounded by `Send + Sync + 'static` on top of
+    /// the declared bounds.
+    pub generics: Generics,
+    pub supertraits: Punctuated<TypeParamBound, Token![+]>,
     pub brace_token: token::Brace,
     pub items: Vec<RpcMethod>,
 }
 
+fn parse_generics(input: ParseStream) -> syn::Result<Generics> {
+    let mut generics: Generics = input.parse()?;
+
+    for param in generics.params.iter_mut() {
+        match param {
+            GenericParam::Type(param) => {
+                param.bounds.push(parse_quote!(Send));
+                param.bounds.push(parse_quote!(Sync));
+                param.bounds.push(parse_quote!('static));
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    param.span(),
+                    "Only type parameters are allowed",
+                ))
+            }
+        }
+    }
+
+    Ok(generics)
+}
+
+fn parse_supertraits(input: ParseStream) -> syn::Result<Punctuated<TypeParamBound, Token![+]>> {
+    let mut supertraits = Punctuated::new();
+    if input.parse::<Option<Token![:]>>()?.is_some() {
+        while !input.peek(Token![where]) && !input.peek(token::Brace) {
+            supertraits.push_value(input.parse()?);
+            if !input.peek(Token![+]) {
+                break;
+            }
+            supertraits.push_punct(input.parse()?);
+        }
+    }
+    Ok(supertraits)
+}
+
 impl Parse for Service {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        let trait_token = input.parse()?;
+        let ident = input.parse()?;
+        let mut generics = parse_generics(input)?;
+        let supertraits = parse_supertraits(input)?;
+        generics.where_clause = input.parse()?;
+
         let content;
+        let brace_token = braced!(content in input);
+
+        let mut items: Vec<RpcMethod> = Vec::new();
+        while !content.is_empty() {
+            if content.peek(Token![type]) {
+                return Err(syn::Error::new(
+                    content.span(),
+                    "Associated types aren't allowed, use type parameters",
+                ));
+            }
+            items.push(content.parse()?);
+        }
+
+        if items.iter().any(|item| item.sig.is_mut()) {
+            if let Some(item) = items.iter().find(|item| item.sig.receiver.is_none()) {
+                return Err(syn::Error::new(
+                    item.sig.ident.span(),
+                    "Expect `&self` or `&mut self` in a service with `&mut self` methods",
+                ));
+            }
+        }
+
         Ok(Self {
             service_attrs: ServiceAttrs::new(),
-            vis: input.parse()?,
-            trait_token: input.parse()?,
-            ident: input.parse()?,
-            brace_token: braced!(content in input),
-            items: {
-                let mut items = Vec::new();
-                while !content.is_empty() {
-                    items.push(content.parse()?);
-                }
-                items
-            },
+            vis,
+            trait_token,
+            ident,
+            generics,
+            supertraits,
+            brace_token,
+            items,
         })
     }
 }
 
 impl Service {
+    /// Checks the wire names and ids, which can only be done once the
+    /// service attributes are known.
+    fn check(&self) -> syn::Result<()> {
+        let (mut names, mut ids) = (HashSet::new(), HashSet::new());
+        let compact = self.service_attrs.is_compact();
+
+        for RpcMethod { attrs, sig, .. } in &self.items {
+            let (name, span) = match &attrs.name {
+                Some(name) => (name.value(), name.span()),
+                None => (self.wire_name(attrs, &sig.ident), sig.ident.span()),
+            };
+            if !names.insert(name.clone()) {
+                return Err(syn::Error::new(
+                    span,
+                    format!("Duplicate method name `{}`", name),
+                ));
+            }
+
+            if let Some(id) = &attrs.id {
+                if !ids.insert(id.base10_parse::<u32>()?) {
+                    return Err(syn::Error::new(
+                        id.span(),
+                        format!("Duplicate method id `{}`", id),
+                    ));
+                }
+            }
+
+            if compact {
+                if attrs.id.is_none() {
+                    return Err(syn::Error::new(
+                        sig.ident.span(),
+                        "Expect `#[rpc(id = ..)]` with `message(compact)`",
+                    ));
+                }
+                if attrs.message.is_some() {
+                    return Err(syn::Error::new(
+                        sig.ident.span(),
+                        "`message(..)` isn't allowed on methods with `message(compact)`",
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The name of the variants of the method `ident` on the wire, as serde
+    /// renames them.
+    fn wire_name(&self, attrs: &RpcAttrs, ident: &Ident) -> String {
+        if let Some(name) = &attrs.name {
+            return name.value();
+        }
+        if let Some(rename) = attrs
+            .message
+            .as_ref()
+            .and_then(|message| message.names.rename.clone())
+        {
+            return rename;
+        }
+
+        let variant = Self::request_item_ident(ident).to_string();
+        match &self.service_attrs.message {
+            Some(message) => message.names.variant_name(&variant),
+            None => variant,
+        }
+    }
+
     fn request_ident(&self) -> Ident {
         format_ident!("{}Request", self.ident)
     }
 
+    fn request_ref_ident(&self) -> Ident {
+        format_ident!("{}RequestRef", self.ident)
+    }
+
+    /// Whether some method is `#[rpc(borrow)]`, and so needs the borrowed
+    /// request.
+    fn has_borrow(&self) -> bool {
+        self.items.iter().any(|item| item.attrs.borrow)
+    }
+
     fn request_item_ident(rpc_ident: &Ident) -> Ident {
         ident_to_case(rpc_ident, Case::UpperCamel)
     }
@@ -114,39 +270,103 @@ impl Service {
         format_ident!("{}Poster", self.ident)
     }
 
+    fn is_mut(&self) -> bool {
+        self.items.iter().any(|item| item.sig.is_mut())
+    }
+
+    fn type_params(&self) -> Vec<&Ident> {
+        self.generics
+            .type_params()
+            .map(|param| &param.ident)
+            .collect()
+    }
+
+    /// `PhantomData` of the type parameters, some of which may not be used
+    /// by every generated type.
+    fn gen_phantom_ty(&self) -> TokenStream2 {
+        let params = self.type_params();
+        quote! { std::marker::PhantomData<fn() -> (#( #params, )*)> }
+    }
+
+    /// Whether the message enums derive the serde traits, as opposed to
+    /// the `message(compact)` ones implementing them by hand.
+    fn derives_serde(&self) -> bool {
+        self.service_attrs.is_serde() && !self.service_attrs.is_compact()
+    }
+
+    fn gen_serde_skip(&self) -> Option<TokenStream2> {
+        self.derives_serde().then(|| quote! { #[serde(skip)] })
+    }
+
+    /// Uninhabited variant using the type parameters of the message enums.
+    fn gen_phantom_item(&self) -> Option<TokenStream2> {
+        if self.generics.params.is_empty() {
+            return None;
+        }
+
+        let (phantom_ty, serde_skip) = (self.gen_phantom_ty(), self.gen_serde_skip());
+
+        Some(quote! {
+            #[doc(hidden)]
+            #serde_skip
+            __Phantom(#phantom_ty, std::convert::Infallible),
+        })
+    }
+
+    fn gen_phantom_arm(&self, enum_ty: TokenStream2) -> Option<TokenStream2> {
+        (!self.generics.params.is_empty()).then(|| {
+            quote! {
+                #enum_ty::__Phantom(_, never) => match never {},
+            }
+        })
+    }
+
     fn gen_service(&self) -> TokenStream2 {
         let Self {
             service_attrs: _,
             vis,
             trait_token: _,
             ident,
+            generics,
+            supertraits,
             brace_token: _,
             items,
         } = self;
+        let (_, ty_generics, where_clause) = generics.split_for_impl();
 
-        let rpcs = items.iter().map(|RpcMethod { attrs: _, sig, .. }| {
+        let rpcs = items.iter().map(|RpcMethod { fn_attrs, sig, .. }| {
             let RpcSignature {
                 asyncness,
                 fn_token: _,
                 ident,
                 paren_token: _,
+                receiver,
                 inputs,
                 output,
             } = sig;
 
-            let args = inputs.iter();
+            let args = inputs.iter().map(|PatType { pat, ty, .. }| quote! { #pat: #ty });
+            let receiver = match receiver {
+                Some(receiver) => quote! { #receiver },
+                None => quote! { self: std::sync::Arc<Self> },
+            };
 
             quote! {
-                #asyncness fn #ident(self: std::sync::Arc<Self>,  #( #args ),*) -> #output;
+                #( #fn_attrs )*
+                #asyncness fn #ident(#receiver, #( #args ),*) -> #output;
             }
         });
 
+        let is_mut = self.is_mut();
+
         let fn_serve = {
             let request_ident = &self.request_ident();
             let response_ident = &self.response_ident();
+            let phantom_arm = self.gen_phantom_arm(quote! { #request_ident });
 
             let match_items =
-                items.iter().map(|RpcMethod { attrs: _, sig, .. }| {
+                items.iter().map(|item| {
+                    let (sig, cfg_attrs) = (&item.sig, item.cfg_attrs());
                     let (
                         method_ident,
                         request_item_ident,
@@ -167,30 +387,46 @@ impl Service {
                     );
 
                     let arg_pats = &input_pats;
+                    let this = if sig.receiver.is_some() && !is_mut {
+                        quote! { &self }
+                    } else {
+                        quote! { self }
+                    };
 
                     quote! {
+                        #( #cfg_attrs )*
                         #request_ident::#request_item_ident{ #( #arg_pats ),* } => {
                             #response_ident::#response_item_ident(
                                 Self::#method_ident(
-                                    self, #( #arg_pats ),*
+                                    #this, #( #arg_pats ),*
                                 )#do_await
                             )
                         }
                     }
                 });
 
+            let receiver = if is_mut {
+                quote! { &mut self }
+            } else {
+                quote! { self: std::sync::Arc<Self> }
+            };
+
             quote! {
-                async fn serve(self: std::sync::Arc<Self>, req: #request_ident) -> #response_ident {
+                #[allow(deprecated)]
+                async fn serve(#receiver, req: #request_ident #ty_generics) -> #response_ident #ty_generics {
                     match req {
                         #( #match_items )*
+                        #phantom_arm
                     }
                 }
             }
         };
 
+        let supertraits = supertraits.iter();
+
         quote! {
             #[mrpc::async_trait]
-            #vis trait #ident: Send + Sync {
+            #vis trait #ident #generics: Send + Sync #( + #supertraits )* #where_clause {
                 #( #rpcs )*
 
                 #fn_serve
@@ -198,20 +434,50 @@ impl Service {
         }
     }
 
-    fn gen_message_item_attr(attrs: &RpcAttrs) -> TokenStream2 {
-        let mut attr = TokenStream2::new();
-        if let Some(message) = &attrs.message {
-            if let Some(serde) = &message.serde {
-                if let IdentMeta::IdentMetaList(ml) = serde {
-                    let token = ml.list.to_token_stream();
-                    attr.extend(quote! {
-                        #[serde(#token)]
-                    })
+    fn gen_dispatch(&self) -> TokenStream2 {
+        let (ident, request_ident, response_ident) =
+            (&self.ident, self.request_ident(), self.response_ident());
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+
+        let (target, call) = if self.is_mut() {
+            (
+                quote! { mrpc::sync::Mutex<dyn #ident #ty_generics> },
+                quote! { service.lock().await.serve(req).await },
+            )
+        } else {
+            (quote! { dyn #ident #ty_generics }, quote! { service.serve(req).await })
+        };
+
+        quote! {
+            #[mrpc::async_trait]
+            impl #impl_generics mrpc::server::Dispatch for dyn #ident #ty_generics #where_clause {
+                type Request = #request_ident #ty_generics;
+                type Response = #response_ident #ty_generics;
+                type Target = #target;
+
+                async fn dispatch(service: std::sync::Arc<Self::Target>, req: Self::Request) -> Self::Response {
+                    #call
+                }
+
+                fn descriptor() -> mrpc::descriptor::ServiceDescriptor {
+                    <#request_ident #ty_generics>::descriptor()
                 }
             }
         }
+    }
+
+    fn gen_message_item_attr(&self, attrs: &RpcAttrs) -> TokenStream2 {
+        let mut token = attrs
+            .message
+            .as_ref()
+            .map(MessageAttr::gen_item_attrs)
+            .unwrap_or_default();
 
-        attr
+        if let (Some(name), true) = (&attrs.name, self.derives_serde()) {
+            token.extend(quote! { #[serde(rename = #name)] });
+        }
+
+        token
     }
 
     fn gen_request(&self) -> TokenStream2 {
@@ -221,22 +487,247 @@ impl Service {
             self.request_ident(),
         );
 
-        let items = self.items.iter().map(|RpcMethod { attrs, sig, .. }| {
-            let (request_item_ident, args) =
-                (Self::request_item_ident(&sig.ident), sig.inputs.iter());
+        let items = self.items.iter().map(|item| {
+            let (request_item_ident, args, variant_attrs) = (
+                Self::request_item_ident(&item.sig.ident),
+                item.sig.inputs.iter(),
+                item.variant_attrs(),
+            );
 
-            let attr = Self::gen_message_item_attr(attrs);
+            let attr = self.gen_message_item_attr(&item.attrs);
 
             quote! {
+                #( #variant_attrs )*
                 #attr
                 #request_item_ident{ #( #args ),* }
             }
         });
 
+        let sequential_items = self.items.iter().map(|item| {
+            let (request_item_ident, sequential, cfg_attrs) = (
+                Self::request_item_ident(&item.sig.ident),
+                item.attrs.sequential,
+                item.cfg_attrs(),
+            );
+
+            quote! {
+                #( #cfg_attrs )*
+                Self::#request_item_ident{ .. } => #sequential,
+            }
+        });
+
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+        let (generics, phantom_item, phantom_arm) = (
+            &self.generics,
+            self.gen_phantom_item(),
+            self.gen_phantom_arm(quote! { Self }),
+        );
+
+        let method_items = self.items.iter().map(|item| {
+            let (request_item_ident, method_ident, cfg_attrs) = (
+                Self::request_item_ident(&item.sig.ident),
+                &item.sig.ident,
+                item.cfg_attrs(),
+            );
+
+            quote! {
+                #( #cfg_attrs )*
+                Self::#request_item_ident{ .. } => stringify!(#method_ident),
+            }
+        });
+
+        let descriptor = self.gen_descriptor();
+
         quote! {
             #message_attr
-            #vis enum #request_ident {
-                #( #items ),*
+            #vis enum #request_ident #generics #where_clause {
+                #( #items, )*
+                #phantom_item
+            }
+
+            impl #impl_generics #request_ident #ty_generics #where_clause {
+                /// Name of the called method.
+                #vis fn method_name(&self) -> &'static str {
+                    match *self {
+                        #( #method_items )*
+                        #phantom_arm
+                    }
+                }
+
+                /// Whether the call must not run concurrently with other
+                /// sequential calls of the same service.
+                #vis fn is_sequential(&self) -> bool {
+                    match *self {
+                        #( #sequential_items )*
+                        #phantom_arm
+                    }
+                }
+
+                #descriptor
+            }
+        }
+    }
+
+    /// `descriptor()` of the request, see `mrpc::descriptor`.
+    fn gen_descriptor(&self) -> TokenStream2 {
+        let (vis, name, compact) = (
+            &self.vis,
+            self.ident.to_string(),
+            self.service_attrs.is_compact(),
+        );
+        let type_params = self.type_params().into_iter().map(Ident::to_string);
+        let tagging = match &self.service_attrs.message {
+            Some(message) => message.names.gen_tagging(),
+            None => quote! { mrpc::descriptor::Tagging::External },
+        };
+
+        let methods = self.items.iter().map(|item| {
+            let (attrs, sig, cfg_attrs) = (&item.attrs, &item.sig, item.cfg_attrs());
+            let name = sig.ident.to_string();
+            let wire_name = self.wire_name(attrs, &sig.ident);
+            let id = match &attrs.id {
+                Some(id) => quote! { Some(#id) },
+                None => quote! { None },
+            };
+            let (doc, output, sequential) = (
+                doc_string(&item.fn_attrs),
+                type_to_string(&sig.output),
+                attrs.sequential,
+            );
+            let args = sig.inputs.iter().map(|input| {
+                let (name, ty, default) = (
+                    type_to_string(&input.pat),
+                    type_to_string(&input.ty),
+                    has_serde_default(&input.attrs),
+                );
+                quote! {
+                    mrpc::descriptor::ArgDescriptor {
+                        name: #name.to_string(),
+                        ty: #ty.to_string(),
+                        default: #default,
+                    }
+                }
+            });
+
+            quote! {
+                #( #cfg_attrs )*
+                methods.push(mrpc::descriptor::MethodDescriptor {
+                    name: #name.to_string(),
+                    wire_name: #wire_name.to_string(),
+                    id: #id,
+                    doc: #doc.to_string(),
+                    args: vec![#( #args ),*],
+                    output: #output.to_string(),
+                    sequential: #sequential,
+                });
+            }
+        });
+
+        quote! {
+            /// Describes the methods of the service, see
+            /// [`mrpc::descriptor`].
+            #[allow(clippy::vec_init_then_push)]
+            #vis fn descriptor() -> mrpc::descriptor::ServiceDescriptor {
+                let mut methods = Vec::new();
+                #( #methods )*
+
+                mrpc::descriptor::ServiceDescriptor {
+                    name: #name.to_string(),
+                    type_params: vec![#( #type_params.to_string() ),*],
+                    compact: #compact,
+                    tagging: #tagging,
+                    methods,
+                }
+            }
+        }
+    }
+
+    /// The request of the `#[rpc(borrow)]` methods, serialized like the
+    /// owned one.
+    fn gen_request_ref(&self) -> TokenStream2 {
+        if !self.has_borrow() {
+            return quote! {};
+        }
+
+        let (vis, request_ident, request_ref_ident) =
+            (&self.vis, self.request_ident(), self.request_ref_ident());
+
+        let lifetime: Lifetime = parse_quote!('a);
+        let mut generics = self.generics.clone();
+        generics.params.insert(0, parse_quote!(#lifetime));
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let owned_ty_generics = self.generics.split_for_impl().1;
+
+        let (serde_attr, serde_skip) = (
+            self.service_attrs.gen_serialize_attr(),
+            self.gen_serde_skip(),
+        );
+        let type_params = self.type_params();
+
+        let borrowed = self.items.iter().filter(|item| item.attrs.borrow);
+
+        let items = borrowed.clone().map(|item| {
+            let (attrs, sig, variant_attrs) = (&item.attrs, &item.sig, item.variant_attrs());
+            let request_item_ident = Self::request_item_ident(&sig.ident);
+            let args = sig.inputs.iter().map(|input| {
+                let (attrs, pat) = (&input.attrs, &input.pat);
+                match borrowed_ty(&input.ty, Some(&lifetime)) {
+                    Some(ty) => quote! { #( #attrs )* #pat: #ty },
+                    None => quote! { #input },
+                }
+            });
+
+            let attr = self.gen_message_item_attr(attrs);
+
+            quote! {
+                #( #variant_attrs )*
+                #attr
+                #request_item_ident{ #( #args ),* },
+            }
+        });
+
+        let owned_items = borrowed.map(|item| {
+            let (sig, cfg_attrs) = (&item.sig, item.cfg_attrs());
+            let request_item_ident = Self::request_item_ident(&sig.ident);
+            let arg_pats = sig
+                .inputs
+                .iter()
+                .map(|input| &*input.pat)
+                .collect::<Vec<_>>();
+            let owned_args = sig.inputs.iter().map(|input| {
+                let pat = &input.pat;
+                match borrowed_ty(&input.ty, None) {
+                    Some(_) => quote! { #pat: std::borrow::ToOwned::to_owned(#pat) },
+                    None => quote! { #pat },
+                }
+            });
+
+            quote! {
+                #( #cfg_attrs )*
+                Self::#request_item_ident{ #( #arg_pats ),* } => #request_ident::#request_item_ident{
+                    #( #owned_args ),*
+                },
+            }
+        });
+
+        quote! {
+            #serde_attr
+            #vis enum #request_ref_ident #generics #where_clause {
+                #( #items )*
+                #[doc(hidden)]
+                #serde_skip
+                __Phantom(std::marker::PhantomData<fn() -> (&#lifetime (), #( #type_params, )*)>, std::convert::Infallible),
+            }
+
+            impl #impl_generics mrpc::IntoOwned for #request_ref_ident #ty_generics #where_clause {
+                type Owned = #request_ident #owned_ty_generics;
+
+                fn into_owned(self) -> Self::Owned {
+                    match self {
+                        #( #owned_items )*
+                        Self::__Phantom(_, never) => match never {},
+                    }
+                }
             }
         }
     }
@@ -248,26 +739,262 @@ impl Service {
             self.response_ident(),
         );
 
-        let items = self.items.iter().map(|RpcMethod { attrs, sig, .. }| {
-            let (response_item_ident, return_type) =
-                (Self::response_item_ident(&sig.ident), &sig.output);
+        let items = self.items.iter().map(|item| {
+            let (response_item_ident, return_type, variant_attrs) = (
+                Self::response_item_ident(&item.sig.ident),
+                &item.sig.output,
+                item.variant_attrs(),
+            );
 
-            let attr = Self::gen_message_item_attr(attrs);
+            let attr = self.gen_message_item_attr(&item.attrs);
 
             quote! {
+                #( #variant_attrs )*
                 #attr
                 #response_item_ident( #return_type )
             }
         });
 
+        let (generics, where_clause, phantom_item) = (
+            &self.generics,
+            &self.generics.where_clause,
+            self.gen_phantom_item(),
+        );
+
         quote! {
             #message_attr
-            #vis enum #response_ident {
-                #( #items ),*
+            #vis enum #response_ident #generics #where_clause {
+                #( #items, )*
+                #phantom_item
+            }
+        }
+    }
+
+    fn compact_id(item: &RpcMethod) -> Option<LitInt> {
+        item.attrs
+            .id
+            .as_ref()
+            .map(|id| LitInt::new(&format!("{}u32", id.base10_digits()), id.span()))
+    }
+
+    fn bound_type_params(&self, generics: &mut Generics, bound: TokenStream2) {
+        let type_params = self.type_params();
+        let where_clause = generics.make_where_clause();
+        for param in type_params {
+            where_clause.predicates.push(parse_quote!(#param: #bound));
+        }
+    }
+
+    /// `Serialize` of a `message(compact)` enum as a `(id, value)` tuple.
+    fn gen_compact_serialize(
+        &self,
+        ty: &Ident,
+        generics: &Generics,
+        arms: impl Iterator<Item = TokenStream2>,
+        has_phantom: bool,
+    ) -> TokenStream2 {
+        let phantom_arm = has_phantom.then(|| {
+            quote! {
+                Self::__Phantom(_, never) => match *never {},
+            }
+        });
+        let mut generics = generics.clone();
+        self.bound_type_params(&mut generics, quote! { mrpc::serde::Serialize });
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        quote! {
+            impl #impl_generics mrpc::serde::Serialize for #ty #ty_generics #where_clause {
+                #[allow(unreachable_code)]
+                fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+                where
+                    S: mrpc::serde::Serializer,
+                {
+                    use mrpc::serde::ser::SerializeTuple;
+
+                    let mut tuple = serializer.serialize_tuple(2)?;
+                    match self {
+                        #( #arms )*
+                        #phantom_arm
+                    }
+                    tuple.end()
+                }
             }
         }
     }
 
+    /// `Deserialize` of a `message(compact)` enum from a `(id, value)`
+    /// tuple.
+    fn gen_compact_deserialize(
+        &self,
+        ty: &Ident,
+        arms: impl Iterator<Item = TokenStream2>,
+    ) -> TokenStream2 {
+        let mut generics = self.generics.clone();
+        self.bound_type_params(&mut generics, quote! { mrpc::serde::Deserialize<'de> });
+        let ty_generics = self.generics.split_for_impl().1;
+        let where_clause = &generics.where_clause;
+        generics.params.insert(0, parse_quote!('de));
+        let impl_generics = generics.split_for_impl().0;
+
+        let (type_params, phantom_ty) = (self.type_params(), self.gen_phantom_ty());
+
+        quote! {
+            impl #impl_generics mrpc::serde::Deserialize<'de> for #ty #ty_generics #where_clause {
+                fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: mrpc::serde::Deserializer<'de>,
+                {
+                    struct Visitor<#( #type_params ),*>(#phantom_ty);
+
+                    impl #impl_generics mrpc::serde::de::Visitor<'de> for Visitor<#( #type_params ),*> #where_clause {
+                        type Value = #ty #ty_generics;
+
+                        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                            formatter.write_str("a method id and its value")
+                        }
+
+                        fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+                        where
+                            A: mrpc::serde::de::SeqAccess<'de>,
+                        {
+                            use mrpc::serde::de::Error;
+
+                            let id: u32 = seq
+                                .next_element()?
+                                .ok_or_else(|| Error::invalid_length(0, &self))?;
+                            match id {
+                                #( #arms )*
+                                _ => Err(Error::custom(format_args!("unknown method id {}", id))),
+                            }
+                        }
+                    }
+
+                    deserializer.deserialize_tuple(2, Visitor(std::marker::PhantomData))
+                }
+            }
+        }
+    }
+
+    /// Hand written serde impls of `message(compact)`, which put the
+    /// `#[rpc(id = ..)]` of the methods on the wire instead of their names.
+    fn gen_compact(&self) -> TokenStream2 {
+        let (request_ident, request_ref_ident, response_ident) = (
+            self.request_ident(),
+            self.request_ref_ident(),
+            self.response_ident(),
+        );
+
+        let serialize_request_arms = |borrow_only: bool| {
+            self.items
+                .iter()
+                .filter(move |item| !borrow_only || item.attrs.borrow)
+                .map(|item| {
+                    let (id, cfg_attrs) = (Self::compact_id(item), item.cfg_attrs());
+                    let request_item_ident = Self::request_item_ident(&item.sig.ident);
+                    let arg_pats = item
+                        .sig
+                        .inputs
+                        .iter()
+                        .map(|input| &*input.pat)
+                        .collect::<Vec<_>>();
+
+                    quote! {
+                        #( #cfg_attrs )*
+                        Self::#request_item_ident{ #( #arg_pats ),* } => {
+                            tuple.serialize_element(&#id)?;
+                            tuple.serialize_element(&( #( #arg_pats, )* ))?;
+                        }
+                    }
+                })
+        };
+
+        let deserialize_request_arms = self.items.iter().map(|item| {
+            let (id, cfg_attrs) = (Self::compact_id(item), item.cfg_attrs());
+            let request_item_ident = Self::request_item_ident(&item.sig.ident);
+            let (arg_pats, arg_tys) = item
+                .sig
+                .inputs
+                .iter()
+                .map(|input| (&*input.pat, &*input.ty))
+                .unzip::<_, _, Vec<_>, Vec<_>>();
+
+            quote! {
+                #( #cfg_attrs )*
+                #id => {
+                    let ( #( #arg_pats, )* ): ( #( #arg_tys, )* ) = seq
+                        .next_element()?
+                        .ok_or_else(|| Error::invalid_length(1, &self))?;
+                    Ok(#request_ident::#request_item_ident{ #( #arg_pats ),* })
+                }
+            }
+        });
+
+        let serialize_response_arms = self.items.iter().map(|item| {
+            let (id, cfg_attrs) = (Self::compact_id(item), item.cfg_attrs());
+            let response_item_ident = Self::response_item_ident(&item.sig.ident);
+
+            quote! {
+                #( #cfg_attrs )*
+                Self::#response_item_ident(value) => {
+                    tuple.serialize_element(&#id)?;
+                    tuple.serialize_element(value)?;
+                }
+            }
+        });
+
+        let deserialize_response_arms = self.items.iter().map(|item| {
+            let (id, cfg_attrs, output) = (
+                Self::compact_id(item),
+                item.cfg_attrs(),
+                &item.sig.output,
+            );
+            let response_item_ident = Self::response_item_ident(&item.sig.ident);
+
+            quote! {
+                #( #cfg_attrs )*
+                #id => {
+                    let value: #output = seq
+                        .next_element()?
+                        .ok_or_else(|| Error::invalid_length(1, &self))?;
+                    Ok(#response_ident::#response_item_ident(value))
+                }
+            }
+        });
+
+        let mut ref_generics = self.generics.clone();
+        ref_generics.params.insert(0, parse_quote!('a));
+        let has_phantom = !self.generics.params.is_empty();
+
+        let serialize_request_ref = self.has_borrow().then(|| {
+            self.gen_compact_serialize(
+                &request_ref_ident,
+                &ref_generics,
+                serialize_request_arms(true),
+                true,
+            )
+        });
+
+        [
+            self.gen_compact_serialize(
+                &request_ident,
+                &self.generics,
+                serialize_request_arms(false),
+                has_phantom,
+            ),
+            self.gen_compact_deserialize(&request_ident, deserialize_request_arms),
+            serialize_request_ref.unwrap_or_default(),
+            self.gen_compact_serialize(
+                &response_ident,
+                &self.generics,
+                serialize_response_arms,
+                has_phantom,
+            ),
+            self.gen_compact_deserialize(&response_ident, deserialize_response_arms),
+        ]
+        .into_iter()
+        .collect()
+    }
+
     fn gen_client(&self) -> TokenStream2 {
         let (vis, client_ident, request_ident, response_ident, poster_ident) = (
             &self.vis,
@@ -277,63 +1004,185 @@ impl Service {
             self.poster_ident(),
         );
 
-        let rpcs = self.items.iter().map(|RpcMethod { attrs: _, sig, .. }| {
+        let (generics, type_params, phantom_ty) =
+            (&self.generics, self.type_params(), self.gen_phantom_ty());
+        let (_, ty_generics, where_clause) = generics.split_for_impl();
+        let where_predicates = where_clause.map(|where_clause| &where_clause.predicates);
+        let generic_params = generics.params.iter();
+
+        let request_ref_ident = self.request_ref_ident();
+        let service_ident = &self.ident;
+
+        let rpcs = self.items.iter().map(|RpcMethod { attrs, fn_attrs, sig, .. }| {
             let RpcSignature {
-                asyncness: _,
-                fn_token: _,
                 ident,
-                paren_token: _,
                 inputs,
                 output,
+                ..
             } = sig;
 
-            let args = inputs.iter();
             let arg_pats = inputs.iter().map(|input| &*input.pat).collect::<Vec<_>>();
             let request_item_ident = Self::request_item_ident(ident);
             let response_item_ident = Self::response_item_ident(ident);
+
+            let (args, post) = if attrs.borrow {
+                (
+                    inputs
+                        .iter()
+                        .map(|input| {
+                            let pat = &input.pat;
+                            let ty = borrowed_ty(&input.ty, None).unwrap_or_else(|| (*input.ty).clone());
+                            quote! { #pat: #ty }
+                        })
+                        .collect::<Vec<_>>(),
+                    quote! {
+                        self.poster.post_ref(#request_ref_ident::#request_item_ident{
+                            #( #arg_pats ),*
+                        }, tx).await?;
+                    },
+                )
+            } else {
+                (
+                    inputs
+                        .iter()
+                        .map(|PatType { pat, ty, .. }| quote! { #pat: #ty })
+                        .collect(),
+                    quote! {
+                        self.poster.post(#request_ident::#request_item_ident{
+                            #( #arg_pats ),*
+                        }, tx).await?;
+                    },
+                )
+            };
+
             quote! {
+                #( #fn_attrs )*
                 #vis async fn #ident(&self, #( #args ),*) -> mrpc::anyhow::Result<#output> {
-                    let (tx, rx) = mrpc::sync::oneshot::channel::<#response_ident>();
+                    let call = mrpc::metrics::Call::client(stringify!(#service_ident), stringify!(#ident));
+                    let span = mrpc::trace::Span::client(stringify!(#service_ident), stringify!(#ident));
+                    let result = span.instrument(async move {
+                        let (tx, rx) = mrpc::sync::oneshot::channel::<
+                            std::result::Result<#response_ident #ty_generics, mrpc::Error>
+                        >();
 
-                    self.poster.post(#request_ident::#request_item_ident{
-                        #( #arg_pats ),*
-                    }, tx).await?;
+                        #post
 
-                    match rx.await? {
-                        #response_ident::#response_item_ident(o) => {
-                            Ok(o)
-                        }
-                        _ => {
-                            Err(mrpc::anyhow::anyhow!("response not match require {}",
-                                                      stringify!(#response_item_ident)))
+                        match rx.await?? {
+                            #response_ident::#response_item_ident(o) => {
+                                Ok(o)
+                            }
+                            _ => {
+                                Err(mrpc::anyhow::anyhow!("response not match require {}",
+                                                          stringify!(#response_item_ident)))
+                            }
                         }
-                    }
+                    }).await;
+                    span.record_outcome(&result);
+                    call.finish(&result);
+                    result
                 }
             }
         });
 
+        // Only generic clients need a phantom field, the others stay
+        // constructible as `XClient { poster }`.
+        let (phantom_field, phantom_value) = if self.generics.params.is_empty() {
+            (None, None)
+        } else {
+            (
+                Some(quote! { phantom: #phantom_ty, }),
+                Some(quote! { phantom: std::marker::PhantomData, }),
+            )
+        };
+
         quote! {
-            #[derive(Clone)]
-            #vis struct #client_ident<Poster> {
+            #vis struct #client_ident<#( #type_params, )* Poster> {
                 pub poster: Poster,
+                #phantom_field
             }
 
-            impl<Poster> #client_ident<Poster>
-            where Poster: #poster_ident {
+            impl<#( #type_params, )* Poster: Clone> Clone for #client_ident<#( #type_params, )* Poster> {
+                fn clone(&self) -> Self {
+                    Self {
+                        poster: self.poster.clone(),
+                        #phantom_value
+                    }
+                }
+            }
+
+            impl<#( #generic_params, )* Poster> #client_ident<#( #type_params, )* Poster>
+            where Poster: #poster_ident #ty_generics, #where_predicates {
+                #vis fn new(poster: Poster) -> Self {
+                    Self {
+                        poster,
+                        #phantom_value
+                    }
+                }
+
                 #( #rpcs )*
             }
         }
     }
 
     fn gen_poster(&self) -> TokenStream2 {
-        let (vis, poster_ident, request_ident, response_ident) = (
+        let (vis, poster_ident, request_ident, request_ref_ident, response_ident) = (
             &self.vis,
             self.poster_ident(),
             self.request_ident(),
+            self.request_ref_ident(),
             self.response_ident(),
         );
+        let type_params = self.type_params();
+        let (generics, ty_generics, where_clause) = (
+            &self.generics,
+            self.generics.split_for_impl().1,
+            &self.generics.where_clause,
+        );
+
+        // The posters of the servers serialize the borrowed requests, see
+        // `mrpc::PostRef`.
+        let post_ref = self.has_borrow().then(|| {
+            quote! {
+                + for<'r> mrpc::PostRef<#request_ref_ident<'r, #( #type_params ),*>, #response_ident #ty_generics>
+            }
+        });
+
+        quote! {
+            #vis trait #poster_ident #generics: mrpc::Poster<#request_ident #ty_generics, #response_ident #ty_generics> #post_ref + Clone + Sync + Send #where_clause {}
+        }
+    }
+
+    /// `mrpc::openrpc::MethodSchemas` of the request, with `message(schema)`.
+    fn gen_method_schemas(&self) -> TokenStream2 {
+        let request_ident = self.request_ident();
+        let mut generics = self.generics.clone();
+        self.bound_type_params(&mut generics, quote! { mrpc::schemars::JsonSchema });
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        let methods = self.items.iter().map(|item| {
+            let (cfg_attrs, output) = (item.cfg_attrs(), &item.sig.output);
+            let tys = item.sig.inputs.iter().map(|input| &input.ty);
+
+            quote! {
+                #( #cfg_attrs )*
+                methods.push(mrpc::openrpc::MethodSchema {
+                    args: vec![#( gen.subschema_for::<#tys>() ),*],
+                    output: gen.subschema_for::<#output>(),
+                });
+            }
+        });
+
         quote! {
-            #vis trait #poster_ident: mrpc::Poster<#request_ident, #response_ident> + Clone + Sync + Send {}
+            impl #impl_generics mrpc::openrpc::MethodSchemas for #request_ident #ty_generics #where_clause {
+                #[allow(clippy::vec_init_then_push)]
+                fn method_schemas(
+                    gen: &mut mrpc::schemars::gen::SchemaGenerator,
+                ) -> Vec<mrpc::openrpc::MethodSchema> {
+                    let mut methods = Vec::new();
+                    #( #methods )*
+                    methods
+                }
+            }
         }
     }
 }
@@ -342,16 +1191,29 @@ impl ToTokens for Service {
     fn to_tokens(&self, tokens: &mut TokenStream2) {
         tokens.extend([
             self.gen_request(),
+            self.gen_request_ref(),
             self.gen_response(),
             self.gen_service(),
+            self.gen_dispatch(),
             self.gen_client(),
             self.gen_poster(),
-        ])
+        ]);
+
+        if self.service_attrs.is_compact() {
+            tokens.extend(self.gen_compact());
+        }
+
+        if self.service_attrs.is_schema() {
+            tokens.extend(self.gen_method_schemas());
+        }
     }
 }
 
 pub fn parse(attrs: TokenStream, input: TokenStream) -> TokenStream {
     let mut service = parse_macro_input!(input as Service);
     service.service_attrs = parse_macro_input!(attrs as ServiceAttrs);
+    if let Err(err) = service.check() {
+        return err.to_compile_error().into();
+    }
     service.into_token_stream().into()
 }
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use mrpc::sync::mpsc;
+
+#[mrpc::service(message(serde))]
+trait Calculator {
+    /// Adds two numbers.
+    fn add(a: i32, b: i32) -> i32;
+
+    /// Never built.
+    #[cfg(any())]
+    fn missing() -> i32;
+
+    #[deprecated = "use `add`"]
+    #[rpc(sequential)]
+    fn plus(a: i32, b: i32) -> i32;
+
+    #[allow(clippy::too_many_arguments)]
+    fn sum(a: i32, b: i32, c: i32, d: i32, e: i32, f: i32, g: i32, h: i32) -> i32;
+
+    /// Also allowed on the request variant, whose field is named `X`.
+    #[allow(non_snake_case)]
+    fn scale(X: i32) -> i32;
+}
+
+struct CalculatorImpl {}
+
+#[mrpc::async_trait]
+impl Calculator for CalculatorImpl {
+    fn add(self: Arc<Self>, a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    fn plus(self: Arc<Self>, a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    fn sum(self: Arc<Self>, a: i32, b: i32, c: i32, d: i32, e: i32, f: i32, g: i32, h: i32) -> i32 {
+        a + b + c + d + e + f + g + h
+    }
+
+    #[allow(non_snake_case)]
+    fn scale(self: Arc<Self>, X: i32) -> i32 {
+        X * 2
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Calculator(Calculator),
+}
+
+struct ServerImpl {}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_calculator(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Calculator>> {
+        Ok(Arc::new(CalculatorImpl {}))
+    }
+}
+
+#[tokio::test]
+async fn attributes() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+    let calculator = ServerClient::new(tx).calculator();
+
+    assert_eq!(calculator.add(1, 2).await.unwrap(), 3);
+    #[allow(deprecated)]
+    let plus = calculator.plus(1, 2).await.unwrap();
+    assert_eq!(plus, 3);
+    assert_eq!(calculator.sum(1, 1, 1, 1, 1, 1, 1, 1).await.unwrap(), 8);
+    assert_eq!(calculator.scale(2).await.unwrap(), 4);
+}
+
+#[tokio::test]
+async fn client_literal() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+    let poster = ServerClient::new(tx).calculator().poster;
+
+    // The clients of non-generic services have no other field.
+    let calculator = CalculatorClient { poster };
+    assert_eq!(calculator.add(1, 2).await.unwrap(), 3);
+}
@@ -0,0 +1,95 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mrpc::sync::{mpsc, Mutex};
+
+#[mrpc::service(message(serde))]
+trait Blobs {
+    #[rpc(borrow)]
+    async fn put(&self, key: String, blob: Vec<u8>, tag: u32) -> usize;
+    async fn get(&self, key: String) -> Option<Vec<u8>>;
+}
+
+#[derive(Default)]
+struct BlobsImpl {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+#[mrpc::async_trait]
+impl Blobs for BlobsImpl {
+    async fn put(&self, key: String, blob: Vec<u8>, tag: u32) -> usize {
+        let len = blob.len() + tag as usize;
+        self.blobs.lock().await.insert(key, blob);
+        len
+    }
+
+    async fn get(&self, key: String) -> Option<Vec<u8>> {
+        self.blobs.lock().await.get(&key).cloned()
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Blobs(Blobs),
+}
+
+#[mrpc::server]
+enum LocalServer {
+    Blobs(Blobs),
+}
+
+struct ServerImpl {}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_blobs(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Blobs>> {
+        Ok(Arc::new(BlobsImpl::default()))
+    }
+}
+
+#[mrpc::async_trait]
+impl LocalServer for ServerImpl {
+    async fn create_blobs(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Blobs>> {
+        Ok(Arc::new(BlobsImpl::default()))
+    }
+}
+
+#[tokio::test]
+async fn borrowed_arguments() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+    let client = ServerClient::new(tx);
+
+    let blob = vec![1, 2, 3];
+    assert_eq!(client.blobs().put("a", &blob, 1).await.unwrap(), 4);
+    assert_eq!(client.blobs().get("a".into()).await.unwrap(), Some(blob));
+}
+
+#[tokio::test]
+async fn borrowed_arguments_without_serde() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(LocalServer::serve(Arc::new(ServerImpl {}), rx));
+    let client = LocalServerClient::new(tx);
+
+    let blob = vec![1, 2, 3];
+    assert_eq!(client.blobs().put("a", &blob, 0).await.unwrap(), 3);
+    assert_eq!(client.blobs().get("a".into()).await.unwrap(), Some(blob));
+}
+
+#[tokio::test]
+async fn encoded_like_owned() {
+    let (tx, mut rx) = mpsc::channel(1);
+    let blobs = ServerClient::new(tx).blobs();
+    tokio::spawn(async move { blobs.put("a", &[1, 2, 3], 1).await });
+
+    let msg = rx.recv().await.unwrap();
+    assert!(matches!(msg.req, mrpc::Payload::Encoded(_)));
+    let owned = ServerRequest::Blobs(BlobsRequest::Put {
+        key: "a".into(),
+        blob: vec![1, 2, 3],
+        tag: 1,
+    });
+    assert_eq!(
+        serde_json::to_string(&msg.req).unwrap(),
+        serde_json::to_string(&owned).unwrap()
+    );
+}
@@ -0,0 +1,245 @@
+use mrpc::descriptor::{assert_compatible, ArgDescriptor, Descriptor, Route, Tagging};
+use serde_json::json;
+
+mod v1 {
+    #[mrpc::service(message(serde))]
+    pub trait Users {
+        /// Looks a user up.
+        #[rpc(name = "getUser")]
+        async fn get_user(id: u64) -> Option<String>;
+        async fn remove(id: u64, reason: String);
+    }
+
+    #[mrpc::service(message(serde, compact))]
+    pub trait Codes {
+        #[rpc(id = 1)]
+        async fn lookup(name: String) -> u32;
+    }
+}
+
+/// `v1` with other docs and Rust names, but the same wire names.
+mod v1_renamed {
+    #[mrpc::service(message(serde))]
+    pub trait Users {
+        /// Finds a user.
+        #[rpc(name = "getUser")]
+        async fn find_user(id: u64) -> Option<String>;
+        #[rpc(sequential)]
+        async fn remove(id: u64, reason: String);
+    }
+}
+
+mod v2 {
+    #[mrpc::service(message(serde))]
+    pub trait Users {
+        #[rpc(name = "getUser")]
+        async fn find_user(id: u64, #[serde(default)] deleted: bool) -> Option<String>;
+        async fn remove(id: u64, force: bool);
+        async fn count() -> usize;
+    }
+
+    #[mrpc::service(message(serde, compact))]
+    pub trait Codes {
+        #[rpc(id = 1)]
+        async fn lookup(name: String, index: u32) -> u32;
+    }
+}
+
+#[mrpc::service(message(serde))]
+trait Store<K, V> {
+    async fn get(key: K) -> Option<V>;
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Users(v1::Users),
+    Store(Store<u64, Vec<(u8, String)>>),
+}
+
+mod renamed {
+    #[mrpc::service(message(serde(
+        rename_all = "SCREAMING-KEBAB-CASE",
+        tag = "t",
+        content = "c"
+    )))]
+    pub trait Names {
+        async fn get_user_name(id: u64) -> String;
+        #[rpc(name = "count")]
+        async fn count_users() -> usize;
+        #[rpc(message(serde(rename = "drop")))]
+        async fn remove_user(id: u64);
+    }
+
+    #[mrpc::server(message(serde(rename_all = "camelCase", tag = "service")))]
+    pub enum Server {
+        UserNames(Names),
+        #[serde(rename = "other")]
+        OtherNames(Names),
+    }
+}
+
+#[test]
+fn service_descriptor() {
+    let descriptor = v1::UsersRequest::descriptor();
+    assert_eq!(descriptor.name, "Users");
+    assert!(!descriptor.compact);
+
+    let method = &descriptor.methods[0];
+    assert_eq!(
+        (
+            method.name.as_str(),
+            method.wire_name.as_str(),
+            method.doc.as_str()
+        ),
+        ("get_user", "getUser", "Looks a user up.")
+    );
+    assert_eq!(method.output, "Option<String>");
+    assert_eq!(
+        method.args,
+        vec![ArgDescriptor {
+            name: "id".into(),
+            ty: "u64".into(),
+            default: false,
+        }]
+    );
+    assert_eq!(descriptor.methods[1].wire_name, "Remove");
+
+    let descriptor = v2::CodesRequest::descriptor();
+    assert!(descriptor.compact);
+    assert_eq!(descriptor.methods[0].id, Some(1));
+
+    let descriptor = ServerRequest::descriptor();
+    let store = &descriptor.services[1];
+    assert_eq!(store.type_args, vec!["u64", "Vec<(u8, String)>"]);
+    assert_eq!(store.service.type_params, vec!["K", "V"]);
+    assert_eq!(store.service.methods[0].output, "Option<V>");
+}
+
+#[test]
+fn changes() {
+    let changes = v2::UsersRequest::descriptor()
+        .changes_since(&v1::UsersRequest::descriptor())
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            "compatible: Users.get_user: renamed to `find_user` with the same wire name",
+            "compatible: Users.get_user: argument `deleted` was added",
+            "compatible: Users.remove: argument `reason` was removed",
+            "breaking: Users.remove: required argument `force` was added",
+            "compatible: Users.count: method was added",
+        ]
+    );
+
+    let changes = v1::UsersRequest::descriptor().changes_since(&v2::UsersRequest::descriptor());
+    assert!(changes
+        .iter()
+        .any(|change| change.is_breaking() && change.message == "method was removed"));
+
+    let changes = v2::CodesRequest::descriptor().changes_since(&v1::CodesRequest::descriptor());
+    assert_eq!(
+        changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec!["breaking: Codes.lookup: takes `(String, u32)` instead of `(String)`"]
+    );
+}
+
+#[test]
+fn fingerprint() {
+    let v1 = v1::UsersRequest::descriptor().fingerprint();
+    assert_eq!(v1_renamed::UsersRequest::descriptor().fingerprint(), v1);
+    assert_ne!(v2::UsersRequest::descriptor().fingerprint(), v1);
+}
+
+#[test]
+fn snapshot() {
+    assert_compatible("tests/snapshots/server.json", &ServerRequest::descriptor());
+}
+
+fn v1_snapshot(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("mrpc-{}-{}.json", name, std::process::id()));
+    let json = serde_json::to_string(&v1::UsersRequest::descriptor()).unwrap();
+    std::fs::write(&path, json).unwrap();
+    path
+}
+
+#[test]
+#[should_panic(expected = "required argument `force` was added")]
+fn incompatible_snapshot() {
+    assert_compatible(v1_snapshot("incompatible"), &v2::UsersRequest::descriptor());
+}
+
+#[test]
+#[should_panic(expected = "compatible changes since")]
+fn outdated_snapshot() {
+    let path = v1_snapshot("outdated");
+    assert_compatible(&path, &v1::UsersRequest::descriptor());
+    assert_compatible(&path, &v1_renamed::UsersRequest::descriptor());
+}
+
+#[test]
+#[should_panic(expected = "missing snapshot")]
+fn missing_snapshot() {
+    let path = std::env::temp_dir().join(format!("mrpc-missing-{}.json", std::process::id()));
+    assert_compatible(path, &v1::UsersRequest::descriptor());
+}
+
+#[test]
+fn serde_names() {
+    let descriptor = renamed::ServerRequest::descriptor();
+    assert_eq!(
+        descriptor.tagging,
+        Tagging::Internal {
+            tag: "service".into()
+        }
+    );
+    let wire_names = descriptor
+        .services
+        .iter()
+        .map(|entry| entry.wire_name())
+        .collect::<Vec<_>>();
+    assert_eq!(wire_names, ["userNames", "other"]);
+
+    let service = &descriptor.services[0].service;
+    assert_eq!(
+        service.tagging,
+        Tagging::Adjacent {
+            tag: "t".into(),
+            content: "c".into()
+        }
+    );
+    let wire_names = service
+        .methods
+        .iter()
+        .map(|method| method.wire_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(wire_names, ["GET-USER-NAME", "count", "drop"]);
+
+    // The descriptor matches what serde puts on the wire.
+    let req = renamed::ServerRequest::UserNames(renamed::NamesRequest::GetUserName { id: 1 });
+    assert_eq!(
+        serde_json::to_value(&req).unwrap(),
+        json!({ "service": "userNames", "t": "GET-USER-NAME", "c": { "id": 1 } })
+    );
+
+    let route = Route::new(&descriptor, &descriptor.services[0], &service.methods[0]).unwrap();
+    let routed: renamed::ServerRequest = route.request(json!([1])).unwrap();
+    assert_eq!(
+        serde_json::to_value(&routed).unwrap(),
+        serde_json::to_value(&req).unwrap()
+    );
+    let resp = renamed::ServerResponse::UserNames(renamed::NamesResponse::GetUserName("a".into()));
+    assert_eq!(route.output(resp).unwrap(), json!("a"));
+
+    let mut old = descriptor.clone();
+    old.tagging = Tagging::External;
+    assert_eq!(
+        descriptor
+            .changes_since(&old)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec!["breaking: Server: the services are internally tagged by `service` instead of externally tagged"]
+    );
+}
@@ -0,0 +1,94 @@
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+
+use mrpc::sync::{mpsc, Mutex};
+
+#[mrpc::service(message(serde))]
+trait Store<K, V>
+where
+    K: Eq + Hash,
+{
+    async fn get(&self, key: K) -> Option<V>;
+    async fn insert(&self, key: K, value: V) -> Option<V>;
+}
+
+struct StoreImpl<K, V> {
+    map: Mutex<HashMap<K, V>>,
+}
+
+impl<K, V> Default for StoreImpl<K, V> {
+    fn default() -> Self {
+        Self {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[mrpc::async_trait]
+impl<K, V> Store<K, V> for StoreImpl<K, V>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    async fn get(&self, key: K) -> Option<V> {
+        self.map.lock().await.get(&key).cloned()
+    }
+
+    async fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map.lock().await.insert(key, value)
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Users(Store<u64, String>),
+    Tags(Store<String, Vec<String>>),
+}
+
+struct ServerImpl {}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_users(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Store<u64, String>>> {
+        Ok(Arc::new(StoreImpl::default()))
+    }
+
+    async fn create_tags(
+        self: Arc<Self>,
+    ) -> mrpc::anyhow::Result<Arc<dyn Store<String, Vec<String>>>> {
+        Ok(Arc::new(StoreImpl::default()))
+    }
+}
+
+#[tokio::test]
+async fn generic_services() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+    let client = ServerClient::new(tx);
+
+    assert_eq!(
+        client.users().insert(1, "alice".into()).await.unwrap(),
+        None
+    );
+    assert_eq!(
+        client.users().get(1).await.unwrap(),
+        Some("alice".to_string())
+    );
+
+    let tags = vec!["admin".to_string()];
+    client
+        .tags()
+        .insert("alice".into(), tags.clone())
+        .await
+        .unwrap();
+    assert_eq!(client.tags().get("alice".into()).await.unwrap(), Some(tags));
+    assert_eq!(client.tags().get("bob".into()).await.unwrap(), None);
+}
+
+#[test]
+fn serde_roundtrip() {
+    let data = serde_json::to_vec(&ServerRequest::Users(StoreRequest::Get { key: 1 })).unwrap();
+    match serde_json::from_slice::<ServerRequest>(&data).unwrap() {
+        ServerRequest::Users(StoreRequest::Get { key }) => assert_eq!(key, 1),
+        _ => panic!("unexpected request"),
+    }
+}
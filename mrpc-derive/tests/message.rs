@@ -0,0 +1,94 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mrpc::sync::{mpsc, Mutex};
+
+#[mrpc::service(message(
+    derive(Clone, Debug, PartialEq),
+    serde(rename_all = "snake_case", tag = "method", content = "params")
+))]
+trait Kv {
+    async fn get(key: String, #[serde(default)] version: u64) -> Option<String>;
+    #[rpc(borrow)]
+    async fn set(key: String, value: String);
+}
+
+#[derive(Default)]
+struct KvImpl {
+    map: Mutex<HashMap<String, String>>,
+}
+
+#[mrpc::async_trait]
+impl Kv for KvImpl {
+    async fn get(self: Arc<Self>, key: String, version: u64) -> Option<String> {
+        let value = self.map.lock().await.get(&key).cloned()?;
+        Some(format!("{}@{}", value, version))
+    }
+
+    async fn set(self: Arc<Self>, key: String, value: String) {
+        self.map.lock().await.insert(key, value);
+    }
+}
+
+#[mrpc::server(message(
+    debug,
+    derive(Clone, PartialEq),
+    serde(tag = "service", content = "request")
+))]
+enum Server {
+    Kv(Kv),
+}
+
+struct ServerImpl {}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_kv(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Kv>> {
+        Ok(Arc::new(KvImpl::default()))
+    }
+}
+
+#[test]
+fn container_attributes() {
+    let req = ServerRequest::Kv(KvRequest::Get {
+        key: "a".into(),
+        version: 0,
+    });
+    assert_eq!(req.clone(), req);
+    assert_eq!(
+        serde_json::to_string(&req).unwrap(),
+        r#"{"service":"Kv","request":{"method":"get","params":{"key":"a","version":0}}}"#
+    );
+
+    let decoded: ServerRequest =
+        serde_json::from_str(r#"{"service":"Kv","request":{"method":"get","params":{"key":"a"}}}"#)
+            .unwrap();
+    assert_eq!(decoded, req);
+}
+
+#[tokio::test]
+async fn borrowed_request_attributes() {
+    let (tx, mut rx) = mpsc::channel(1);
+    let kv = ServerClient::new(tx).kv();
+    tokio::spawn(async move { kv.set("a", "b").await });
+
+    let msg = rx.recv().await.unwrap();
+    assert!(matches!(msg.req, mrpc::Payload::Encoded(_)));
+    let owned = ServerRequest::Kv(KvRequest::Set {
+        key: "a".into(),
+        value: "b".into(),
+    });
+    assert_eq!(
+        serde_json::to_string(&msg.req).unwrap(),
+        serde_json::to_string(&owned).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn roundtrip() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+    let kv = ServerClient::new(tx).kv();
+
+    kv.set("a", "b").await.unwrap();
+    assert_eq!(kv.get("a".into(), 1).await.unwrap(), Some("b@1".into()));
+}
@@ -0,0 +1,96 @@
+use mrpc::{
+    schemars::JsonSchema,
+    serde::{Deserialize, Serialize},
+};
+use serde_json::json;
+
+/// A user of the store.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "mrpc::serde")]
+#[schemars(crate = "mrpc::schemars")]
+pub struct User {
+    pub name: String,
+    pub age: Option<u32>,
+}
+
+#[mrpc::service(message(serde, schema))]
+trait Users {
+    /// Looks a user up.
+    ///
+    /// Returns `None` when there is none.
+    #[rpc(name = "getUser")]
+    async fn get_user(id: u64) -> Option<User>;
+    async fn add(user: User, #[serde(default)] notify: bool);
+}
+
+#[mrpc::service(message(serde, compact, schema))]
+trait Store<V> {
+    #[rpc(id = 1)]
+    async fn get(key: String) -> Option<V>;
+}
+
+#[mrpc::server(message(serde, schema), reflection)]
+enum Server {
+    Users(Users),
+    Store(Store<Vec<u8>>),
+}
+
+#[test]
+fn openrpc() {
+    let doc = serde_json::to_value(ServerRequest::openrpc()).unwrap();
+
+    assert_eq!(doc["openrpc"], "1.2.6");
+    assert_eq!(
+        doc["info"],
+        json!({ "title": "Server", "version": env!("CARGO_PKG_VERSION") })
+    );
+
+    let names = doc["methods"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|method| method["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            "Users.get_user",
+            "Users.add",
+            "Store.get",
+            "Reflection.describe",
+            "Reflection.describe_service",
+        ]
+    );
+
+    let get_user = &doc["methods"][0];
+    assert_eq!(
+        get_user["description"],
+        "Looks a user up.\n\nReturns `None` when there is none."
+    );
+    assert_eq!(
+        get_user["params"],
+        json!([{
+            "name": "id",
+            "required": true,
+            "schema": { "type": "integer", "format": "uint64", "minimum": 0.0 },
+        }])
+    );
+    assert_eq!(
+        get_user["result"]["schema"]["anyOf"][0],
+        json!({ "$ref": "#/components/schemas/User" })
+    );
+
+    let add = &doc["methods"][1];
+    assert!(add.get("description").is_none());
+    assert_eq!(add["params"][1]["name"], "notify");
+    assert_eq!(add["params"][1]["required"], false);
+    assert_eq!(add["result"]["schema"]["type"], "null");
+
+    let get = &doc["methods"][2];
+    assert_eq!(get["result"]["schema"]["type"], json!(["array", "null"]));
+
+    let schemas = &doc["components"]["schemas"];
+    assert_eq!(schemas["User"]["description"], "A user of the store.");
+    assert_eq!(schemas["User"]["required"], json!(["name"]));
+    assert!(schemas.get("ServerDescriptor").is_some());
+}
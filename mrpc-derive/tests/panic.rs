@@ -0,0 +1,90 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use mrpc::{server::ServeOptions, sync::mpsc, RemoteErrorKind};
+
+#[mrpc::service]
+trait Fragile {
+    fn id() -> usize;
+    fn boom(message: String);
+}
+
+struct FragileImpl {
+    id: usize,
+}
+
+#[mrpc::async_trait]
+impl Fragile for FragileImpl {
+    fn id(self: Arc<Self>) -> usize {
+        self.id
+    }
+
+    fn boom(self: Arc<Self>, message: String) {
+        panic!("{}", message);
+    }
+}
+
+#[mrpc::server]
+enum Server {
+    Fragile(Fragile),
+}
+
+struct ServerImpl {
+    created: AtomicUsize,
+    recreate_on_panic: bool,
+}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_fragile(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Fragile>> {
+        Ok(Arc::new(FragileImpl {
+            id: self.created.fetch_add(1, Ordering::SeqCst),
+        }))
+    }
+
+    fn serve_options(&self) -> ServeOptions {
+        ServeOptions {
+            recreate_on_panic: self.recreate_on_panic,
+            ..Default::default()
+        }
+    }
+}
+
+fn client(recreate_on_panic: bool) -> ServerClient {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(
+        Arc::new(ServerImpl {
+            created: AtomicUsize::new(0),
+            recreate_on_panic,
+        }),
+        rx,
+    ));
+    ServerClient::new(tx)
+}
+
+#[tokio::test]
+async fn panic_is_returned() {
+    let client = client(false);
+
+    let e = client.fragile().boom("oops".into()).await.unwrap_err();
+    assert_eq!(
+        e.downcast_ref::<mrpc::Error>(),
+        Some(&mrpc::Error::Remote {
+            kind: RemoteErrorKind::Panic,
+            message: "oops".into(),
+        })
+    );
+
+    assert_eq!(client.fragile().id().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn recreate_on_panic() {
+    let client = client(true);
+
+    assert_eq!(client.fragile().id().await.unwrap(), 0);
+    assert!(client.fragile().boom("oops".into()).await.is_err());
+    assert_eq!(client.fragile().id().await.unwrap(), 1);
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use mrpc::sync::{mpsc, Mutex};
+
+#[mrpc::service]
+trait Greeter {
+    fn greet(&self, name: String) -> String;
+    async fn greet_later(&self, name: String) -> String;
+}
+
+struct GreeterImpl {
+    greeting: String,
+}
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    fn greet(&self, name: String) -> String {
+        format!("{}, {}", self.greeting, name)
+    }
+
+    async fn greet_later(&self, name: String) -> String {
+        tokio::task::yield_now().await;
+        self.greet(name)
+    }
+}
+
+#[mrpc::service]
+trait Counter {
+    fn add(&mut self, n: u64) -> u64;
+    fn get(&self) -> u64;
+}
+
+#[derive(Default)]
+struct CounterImpl {
+    value: u64,
+}
+
+#[mrpc::async_trait]
+impl Counter for CounterImpl {
+    fn add(&mut self, n: u64) -> u64 {
+        self.value += n;
+        self.value
+    }
+
+    fn get(&self) -> u64 {
+        self.value
+    }
+}
+
+#[mrpc::server]
+enum Server {
+    Greeter(Greeter),
+    Counter(Counter),
+}
+
+struct ServerImpl {}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl {
+            greeting: "Hello".into(),
+        }))
+    }
+
+    async fn create_counter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<Mutex<dyn Counter>>> {
+        Ok(Arc::new(Mutex::new(CounterImpl::default())))
+    }
+}
+
+#[tokio::test]
+async fn receivers() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+    let client = ServerClient::new(tx);
+
+    assert_eq!(
+        client.greeter().greet("mrpc".into()).await.unwrap(),
+        "Hello, mrpc"
+    );
+    assert_eq!(
+        client.greeter().greet_later("mrpc".into()).await.unwrap(),
+        "Hello, mrpc"
+    );
+
+    let adds = (1..=10).map(|n| {
+        let client = client.clone();
+        tokio::spawn(async move { client.counter().add(n).await.unwrap() })
+    });
+    for add in adds {
+        add.await.unwrap();
+    }
+    assert_eq!(client.counter().get().await.unwrap(), 55);
+}
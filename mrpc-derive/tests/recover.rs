@@ -0,0 +1,165 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mrpc::{
+    server::{Control, Retry, ServeOptions},
+    sync::mpsc,
+};
+
+#[mrpc::service]
+trait Counter {
+    fn id() -> usize;
+}
+
+struct CounterImpl {
+    id: usize,
+}
+
+#[mrpc::async_trait]
+impl Counter for CounterImpl {
+    fn id(self: Arc<Self>) -> usize {
+        self.id
+    }
+}
+
+#[mrpc::server]
+enum Server {
+    Counter(Counter),
+}
+
+#[mrpc::server]
+enum EagerServer {
+    #[eager]
+    Counter(Counter),
+}
+
+#[mrpc::server]
+enum CallServer {
+    #[scope(call)]
+    Counter(Counter),
+}
+
+/// Fails to create its first instance.
+#[derive(Default)]
+struct ServerImpl {
+    attempts: AtomicUsize,
+    control: Control,
+}
+
+impl ServerImpl {
+    fn create(&self) -> mrpc::anyhow::Result<Arc<dyn Counter>> {
+        let id = self.attempts.fetch_add(1, Ordering::SeqCst);
+        if id == 0 {
+            mrpc::anyhow::bail!("database is down");
+        }
+        Ok(Arc::new(CounterImpl { id }))
+    }
+
+    fn options(&self) -> ServeOptions {
+        ServeOptions {
+            retry: Retry {
+                backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_millis(100),
+            },
+            control: self.control.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_counter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Counter>> {
+        self.create()
+    }
+
+    fn serve_options(&self) -> ServeOptions {
+        self.options()
+    }
+}
+
+#[mrpc::async_trait]
+impl CallServer for ServerImpl {
+    async fn create_counter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Counter>> {
+        self.create()
+    }
+
+    fn serve_options(&self) -> ServeOptions {
+        self.options()
+    }
+}
+
+#[mrpc::async_trait]
+impl EagerServer for ServerImpl {
+    async fn create_counter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Counter>> {
+        self.create()
+    }
+}
+
+fn is_unavailable(e: &mrpc::anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<mrpc::Error>(),
+        Some(mrpc::Error::ServiceUnavailable(name)) if name == "Counter"
+    )
+}
+
+#[tokio::test]
+async fn retry_and_invalidate() {
+    let server = Arc::new(ServerImpl::default());
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(server.clone(), rx));
+    let client = ServerClient::new(tx);
+
+    assert!(is_unavailable(&client.counter().id().await.unwrap_err()));
+    // Still in backoff, not created again.
+    assert!(is_unavailable(&client.counter().id().await.unwrap_err()));
+    assert_eq!(server.attempts.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(client.counter().id().await.unwrap(), 1);
+    assert_eq!(client.counter().id().await.unwrap(), 1);
+
+    server.control.invalidate("Counter");
+    assert_eq!(client.counter().id().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn retry_per_call() {
+    let server = Arc::new(ServerImpl::default());
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(CallServer::serve(server.clone(), rx));
+    let client = CallServerClient::new(tx);
+
+    assert!(is_unavailable(&client.counter().id().await.unwrap_err()));
+    // Each call has its own instance, but shares the backoff.
+    assert!(is_unavailable(&client.counter().id().await.unwrap_err()));
+    assert_eq!(server.attempts.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(client.counter().id().await.unwrap(), 1);
+    assert_eq!(client.counter().id().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn eager() {
+    let (_tx, rx) = mpsc::channel(32);
+    let result = EagerServer::serve(Arc::new(ServerImpl::default()), rx).await;
+    assert!(is_unavailable(&result.unwrap_err()));
+
+    let server = Arc::new(ServerImpl {
+        attempts: AtomicUsize::new(1),
+        ..Default::default()
+    });
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(EagerServer::serve(server.clone(), rx));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.attempts.load(Ordering::SeqCst), 2);
+
+    let client = EagerServerClient::new(tx);
+    assert_eq!(client.counter().id().await.unwrap(), 1);
+}
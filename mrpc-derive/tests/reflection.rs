@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use mrpc::{
+    descriptor::ServerDescriptor,
+    reflection,
+    sync::{mpsc, oneshot},
+    trace::Span,
+    Connection, Message, Payload,
+};
+use serde_json::Value;
+
+#[mrpc::service(message(serde))]
+trait Greeter {
+    /// Greets `name`.
+    async fn greet(name: String) -> String;
+}
+
+struct GreeterImpl;
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    async fn greet(self: Arc<Self>, name: String) -> String {
+        format!("hello {}", name)
+    }
+}
+
+#[mrpc::server(message(serde), reflection)]
+enum Server {
+    Greeter(Greeter),
+}
+
+#[mrpc::server(message(serde(rename_all = "snake_case")), reflection)]
+enum RenamedServer {
+    Greeter(Greeter),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+}
+
+#[mrpc::async_trait]
+impl RenamedServer for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+}
+
+#[tokio::test]
+async fn reflection() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    let client = ServerClient::new(tx);
+
+    let descriptor = client.reflection().describe().await.unwrap();
+    assert_eq!(descriptor, ServerRequest::descriptor());
+    let names = descriptor
+        .services
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Greeter", "Reflection"]);
+
+    let greeter = client
+        .reflection()
+        .describe_service("Greeter".into())
+        .await
+        .unwrap()
+        .unwrap();
+    let method = &greeter.service.methods[0];
+    assert_eq!(method.name, "greet");
+    assert_eq!(method.doc, "Greets `name`.");
+    assert_eq!(method.args[0].ty, "String");
+    assert_eq!(method.output, "String");
+
+    assert!(client
+        .reflection()
+        .describe_service("Missing".into())
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(client.greeter().greet("a".into()).await.unwrap(), "hello a");
+}
+
+#[test]
+fn reflection_on_the_wire() {
+    let request = ServerRequest::Reflection(mrpc::reflection::ReflectionRequest::Describe {});
+    assert_eq!(
+        serde_json::to_string(&request).unwrap(),
+        r#"{"Reflection":{"Describe":{}}}"#
+    );
+}
+
+#[tokio::test]
+async fn describe_route() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(RenamedServer::serve(Arc::new(ServerImpl), rx));
+
+    // The other services follow `rename_all`, the reflection keeps its name.
+    let names = RenamedServerRequest::descriptor()
+        .services
+        .into_iter()
+        .map(|entry| entry.wire_name().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["greeter", "Reflection"]);
+
+    let route = reflection::describe_route();
+    let req: RenamedServerRequest = route.request(Value::Null).unwrap();
+    let (resp, rx) = oneshot::channel();
+    let msg = Message {
+        req: Payload::Value(req),
+        resp,
+        conn: Connection::new(None),
+        span: Span::none(),
+        metadata: Default::default(),
+    };
+    assert!(tx.send(msg).await.is_ok());
+
+    let output = route.output(rx.await.unwrap().unwrap()).unwrap();
+    let descriptor: ServerDescriptor = serde_json::from_value(output).unwrap();
+    assert_eq!(descriptor, RenamedServerRequest::descriptor());
+}
@@ -0,0 +1,93 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use mrpc::sync::mpsc;
+
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+#[mrpc::service]
+trait Session {
+    fn id() -> usize;
+}
+
+struct SessionImpl {
+    id: usize,
+}
+
+impl SessionImpl {
+    fn new() -> Self {
+        Self {
+            id: CREATED.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+}
+
+impl Drop for SessionImpl {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[mrpc::async_trait]
+impl Session for SessionImpl {
+    fn id(self: Arc<Self>) -> usize {
+        self.id
+    }
+}
+
+#[mrpc::server]
+enum Server {
+    Singleton(Session),
+    #[scope(connection)]
+    PerConnection(Session),
+    #[scope(call)]
+    PerCall(Session),
+}
+
+struct ServerImpl {}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_singleton(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Session>> {
+        Ok(Arc::new(SessionImpl::new()))
+    }
+
+    async fn create_per_connection(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Session>> {
+        Ok(Arc::new(SessionImpl::new()))
+    }
+
+    async fn create_per_call(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Session>> {
+        Ok(Arc::new(SessionImpl::new()))
+    }
+}
+
+#[tokio::test]
+async fn scopes() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+
+    let a = ServerClient::new(tx.clone());
+    let b = ServerClient::new(tx);
+
+    let singleton = a.singleton().id().await.unwrap();
+    assert_eq!(b.singleton().id().await.unwrap(), singleton);
+
+    let per_connection = a.per_connection().id().await.unwrap();
+    assert_eq!(
+        a.clone().per_connection().id().await.unwrap(),
+        per_connection
+    );
+    assert_ne!(b.per_connection().id().await.unwrap(), per_connection);
+
+    let per_call = a.per_call().id().await.unwrap();
+    assert_ne!(a.per_call().id().await.unwrap(), per_call);
+
+    // Two calls, then the instance of `a`.
+    let dropped = DROPPED.load(Ordering::SeqCst);
+    drop(a);
+    tokio::task::yield_now().await;
+    assert_eq!(DROPPED.load(Ordering::SeqCst), dropped + 1);
+}
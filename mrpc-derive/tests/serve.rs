@@ -0,0 +1,161 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mrpc::sync::{mpsc, Mutex};
+
+#[mrpc::service]
+trait Worker {
+    async fn sleep(ms: u64) -> u64;
+    async fn push(v: u64, ms: u64) -> Vec<u64>;
+    #[rpc(sequential)]
+    async fn push_sequential(v: u64, ms: u64) -> Vec<u64>;
+}
+
+#[derive(Default)]
+struct WorkerImpl {
+    pushed: Mutex<Vec<u64>>,
+}
+
+#[mrpc::async_trait]
+impl Worker for WorkerImpl {
+    async fn sleep(self: Arc<Self>, ms: u64) -> u64 {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        ms
+    }
+
+    async fn push(self: Arc<Self>, v: u64, ms: u64) -> Vec<u64> {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        let mut pushed = self.pushed.lock().await;
+        pushed.push(v);
+        pushed.clone()
+    }
+
+    async fn push_sequential(self: Arc<Self>, v: u64, ms: u64) -> Vec<u64> {
+        self.push(v, ms).await
+    }
+}
+
+#[mrpc::server(concurrency(limit = 1, queue = 1))]
+enum LimitedServer {
+    Worker(Worker),
+}
+
+#[mrpc::server]
+enum Server {
+    #[sequential]
+    Sequential(Worker),
+    Concurrent(Worker),
+}
+
+#[mrpc::server]
+enum FlakyServer {
+    #[sequential]
+    Worker(Worker),
+}
+
+struct ServerImpl {}
+
+struct FlakyServerImpl {
+    panicked: AtomicBool,
+}
+
+#[mrpc::async_trait]
+impl LimitedServer for ServerImpl {
+    async fn create_worker(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Worker>> {
+        Ok(Arc::new(WorkerImpl::default()))
+    }
+}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_sequential(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Worker>> {
+        Ok(Arc::new(WorkerImpl::default()))
+    }
+
+    async fn create_concurrent(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Worker>> {
+        Ok(Arc::new(WorkerImpl::default()))
+    }
+}
+
+#[mrpc::async_trait]
+impl FlakyServer for FlakyServerImpl {
+    async fn create_worker(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Worker>> {
+        if !self.panicked.swap(true, Ordering::SeqCst) {
+            panic!("flaky");
+        }
+        Ok(Arc::new(WorkerImpl::default()))
+    }
+}
+
+#[tokio::test]
+async fn overloaded() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(LimitedServer::serve(Arc::new(ServerImpl {}), rx));
+
+    let cli = LimitedServerClient::new(tx);
+    let worker = cli.worker();
+    let (a, b, c) = tokio::join!(worker.sleep(100), worker.sleep(100), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        worker.sleep(100).await
+    });
+
+    assert_eq!(a.unwrap(), 100);
+    assert_eq!(b.unwrap(), 100);
+    assert_eq!(
+        c.unwrap_err().downcast_ref::<mrpc::Error>(),
+        Some(&mrpc::Error::Overloaded("Worker".into()))
+    );
+}
+
+#[tokio::test]
+async fn sequential() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl {}), rx));
+
+    let cli = ServerClient::new(tx);
+
+    let concurrent = cli.concurrent();
+    let (_, _, last) = tokio::join!(
+        concurrent.push(1, 60),
+        concurrent.push(2, 30),
+        concurrent.push(3, 0)
+    );
+    assert_eq!(last.unwrap(), vec![3]);
+
+    let (_, _, last) = tokio::join!(
+        concurrent.push_sequential(4, 60),
+        concurrent.push_sequential(5, 30),
+        concurrent.push_sequential(6, 0)
+    );
+    assert_eq!(last.unwrap(), vec![3, 2, 1, 4, 5, 6]);
+
+    let sequential = cli.sequential();
+    let (_, _, last) = tokio::join!(
+        sequential.push(1, 60),
+        sequential.push(2, 30),
+        sequential.push(3, 0)
+    );
+    assert_eq!(last.unwrap(), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn sequential_after_panic() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(FlakyServer::serve(
+        Arc::new(FlakyServerImpl {
+            panicked: AtomicBool::new(false),
+        }),
+        rx,
+    ));
+
+    let worker = FlakyServerClient::new(tx).worker();
+    assert!(worker.sleep(0).await.is_err());
+
+    let (_, _, last) = tokio::join!(worker.push(1, 60), worker.push(2, 30), worker.push(3, 0));
+    assert_eq!(last.unwrap(), vec![1, 2, 3]);
+}
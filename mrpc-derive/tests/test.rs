@@ -2,4 +2,11 @@
 fn test() {
     let t = trybuild::TestCases::new();
     t.compile_fail("tests/ui/null_server.rs");
+    t.compile_fail("tests/ui/associated_type.rs");
+    t.compile_fail("tests/ui/duplicate_id.rs");
+    t.compile_fail("tests/ui/duplicate_name.rs");
+    t.compile_fail("tests/ui/duplicate_wire_name.rs");
+    t.compile_fail("tests/ui/missing_id.rs");
+    t.compile_fail("tests/ui/schema_without_serde.rs");
+    t.compile_fail("tests/ui/tagged_reflection.rs");
 }
@@ -0,0 +1,7 @@
+#[mrpc::service]
+trait Store {
+    type Key;
+    fn get(key: u64) -> u64;
+}
+
+fn main() {}
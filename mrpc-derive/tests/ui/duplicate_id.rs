@@ -0,0 +1,9 @@
+#[mrpc::service(message(serde, compact))]
+trait Store {
+    #[rpc(id = 1)]
+    fn get(key: u64) -> u64;
+    #[rpc(id = 1)]
+    fn set(key: u64, value: u64);
+}
+
+fn main() {}
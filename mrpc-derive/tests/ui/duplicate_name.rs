@@ -0,0 +1,8 @@
+#[mrpc::service]
+trait Store {
+    #[rpc(name = "Set")]
+    fn get(key: u64) -> u64;
+    fn set(key: u64, value: u64);
+}
+
+fn main() {}
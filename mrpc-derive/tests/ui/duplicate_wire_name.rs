@@ -0,0 +1,7 @@
+#[mrpc::service(message(serde(rename_all = "lowercase")))]
+trait Store {
+    fn get_a(key: u64) -> u64;
+    fn geta(key: u64) -> u64;
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[mrpc::service(message(debug, schema))]
+trait Store {
+    fn get(key: u64) -> u64;
+}
+
+fn main() {}
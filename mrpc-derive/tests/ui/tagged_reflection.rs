@@ -0,0 +1,11 @@
+#[mrpc::service(message(serde))]
+trait Store {
+    fn get(key: u64) -> u64;
+}
+
+#[mrpc::server(message(serde(tag = "service")), reflection)]
+enum Server {
+    Store(Store),
+}
+
+fn main() {}
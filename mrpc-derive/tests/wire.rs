@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use mrpc::sync::mpsc;
+
+#[mrpc::service(message(serde))]
+trait Users {
+    #[rpc(name = "getUser")]
+    async fn get_user(id: u64) -> String;
+    async fn user_count() -> usize;
+}
+
+#[mrpc::service(message(serde, debug, compact))]
+trait Codes {
+    #[rpc(id = 7, borrow)]
+    async fn lookup(name: String, index: u32) -> Option<String>;
+    #[rpc(id = 1)]
+    async fn clear();
+}
+
+#[mrpc::service(message(serde, compact))]
+trait Echo<T> {
+    #[rpc(id = 0)]
+    async fn echo(value: T) -> T;
+}
+
+struct UsersImpl;
+
+#[mrpc::async_trait]
+impl Users for UsersImpl {
+    async fn get_user(self: Arc<Self>, id: u64) -> String {
+        format!("user{}", id)
+    }
+
+    async fn user_count(self: Arc<Self>) -> usize {
+        1
+    }
+}
+
+struct CodesImpl;
+
+#[mrpc::async_trait]
+impl Codes for CodesImpl {
+    async fn lookup(self: Arc<Self>, name: String, index: u32) -> Option<String> {
+        (index == 0).then(|| name.to_uppercase())
+    }
+
+    async fn clear(self: Arc<Self>) {}
+}
+
+struct EchoImpl;
+
+#[mrpc::async_trait]
+impl Echo<Vec<u8>> for EchoImpl {
+    async fn echo(self: Arc<Self>, value: Vec<u8>) -> Vec<u8> {
+        value
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Users(Users),
+    Codes(Codes),
+    Echo(Echo<Vec<u8>>),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_users(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Users>> {
+        Ok(Arc::new(UsersImpl))
+    }
+
+    async fn create_codes(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Codes>> {
+        Ok(Arc::new(CodesImpl))
+    }
+
+    async fn create_echo(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Echo<Vec<u8>>>> {
+        Ok(Arc::new(EchoImpl))
+    }
+}
+
+#[test]
+fn renamed_methods() {
+    let json = serde_json::to_string(&UsersRequest::GetUser { id: 1 }).unwrap();
+    assert_eq!(json, r#"{"getUser":{"id":1}}"#);
+    let json = serde_json::to_string(&UsersResponse::GetUser("a".into())).unwrap();
+    assert_eq!(json, r#"{"getUser":"a"}"#);
+    let json = serde_json::to_string(&UsersRequest::UserCount {}).unwrap();
+    assert_eq!(json, r#"{"UserCount":{}}"#);
+}
+
+#[test]
+fn compact_methods() {
+    let request = CodesRequest::Lookup {
+        name: "a".into(),
+        index: 1,
+    };
+    let json = serde_json::to_string(&request).unwrap();
+    assert_eq!(json, r#"[7,["a",1]]"#);
+    let json = serde_json::to_string(&CodesRequestRef::Lookup {
+        name: "a",
+        index: 1,
+    })
+    .unwrap();
+    assert_eq!(json, r#"[7,["a",1]]"#);
+    match serde_json::from_str(&json).unwrap() {
+        CodesRequest::Lookup { name, index } => assert_eq!((name.as_str(), index), ("a", 1)),
+        _ => panic!("unexpected request"),
+    }
+
+    let json = serde_json::to_string(&CodesRequest::Clear {}).unwrap();
+    assert_eq!(json, "[1,null]");
+    assert!(matches!(
+        serde_json::from_str(&json).unwrap(),
+        CodesRequest::Clear {}
+    ));
+
+    let json = serde_json::to_string(&CodesResponse::Lookup(Some("A".into()))).unwrap();
+    assert_eq!(json, r#"[7,"A"]"#);
+    match serde_json::from_str(&json).unwrap() {
+        CodesResponse::Lookup(value) => assert_eq!(value.as_deref(), Some("A")),
+        _ => panic!("unexpected response"),
+    }
+
+    let json = serde_json::to_string(&EchoRequest::Echo { value: vec![1u8] }).unwrap();
+    assert_eq!(json, "[0,[[1]]]");
+
+    let err = serde_json::from_str::<CodesRequest>("[3,null]").unwrap_err();
+    assert!(err.to_string().contains("unknown method id 3"));
+}
+
+#[tokio::test]
+async fn compact_calls() {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    let client = ServerClient::new(tx);
+
+    assert_eq!(client.users().get_user(2).await.unwrap(), "user2");
+    assert_eq!(client.users().user_count().await.unwrap(), 1);
+    assert_eq!(
+        client.codes().lookup("a", 0).await.unwrap().as_deref(),
+        Some("A")
+    );
+    client.codes().clear().await.unwrap();
+    assert_eq!(client.echo().echo(vec![1, 2]).await.unwrap(), vec![1, 2]);
+}
@@ -51,7 +51,7 @@ async fn main() {
     });
 
     {
-        let cli = ServerClient { sender: tx };
+        let cli = ServerClient::new(tx);
 
         println!("{:?}", cli.service().api1(1, 2).await);
         println!("{:?}", cli.service().api2(1, 2.to_string()).await);
@@ -60,7 +60,7 @@ async fn main() {
     #[cfg(feature = "tcp")]
     {
         let tx = mrpc::net::tcp::writer("127.0.0.1:8081").await.unwrap();
-        let cli = ServerClient { sender: tx };
+        let cli = ServerClient::new(tx);
 
         println!("{:?}", cli.service().api1(1, 2).await);
         println!("{:?}", cli.service().api2(1, 2.to_string()).await);
@@ -71,7 +71,7 @@ async fn main() {
         let tx = mrpc::net::websocket::writer("ws://127.0.0.1:8080")
             .await
             .unwrap();
-        let cli = ServerClient { sender: tx };
+        let cli = ServerClient::new(tx);
 
         println!("{:?}", cli.service().api1(1, 2).await);
         println!("{:?}", cli.service().api2(1, 2.to_string()).await);
@@ -62,7 +62,7 @@ async fn run() {
         Arc::new(ServerImpl {}).serve(rx).await.unwrap();
     });
 
-    let cli = ServerClient { sender: tx };
+    let cli = ServerClient::new(tx);
 
     console_log!("{:?}", cli.service().api1(1, 2).await);
     console_log!("{:?}", cli.service().api2(1, 2.to_string()).await);
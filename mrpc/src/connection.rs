@@ -0,0 +1,62 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+};
+
+use crate::sync::Mutex;
+
+type Slot<T> = Arc<Mutex<T>>;
+
+/// The session a request belongs to: an accepted transport connection, or
+/// an in-process client.
+///
+/// It is dropped, along with the `#[scope(connection)]` services created
+/// for it, once the session ended and its last call completed.
+pub struct Connection {
+    id: u64,
+    peer: Option<SocketAddr>,
+    slots: StdMutex<HashMap<&'static str, Box<dyn Any + Send + Sync>>>,
+}
+
+impl Connection {
+    pub fn new(peer: Option<SocketAddr>) -> Arc<Self> {
+        static ID_GENERATOR: AtomicU64 = AtomicU64::new(0);
+
+        Arc::new(Self {
+            id: ID_GENERATOR.fetch_add(1, Ordering::Relaxed),
+            peer,
+            slots: StdMutex::new(HashMap::new()),
+        })
+    }
+
+    /// Unique among the connections of this process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The remote address, `None` for in-process clients.
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// Storage for a value living as long as the connection.
+    #[doc(hidden)]
+    pub fn slot<T>(&self, key: &'static str) -> Slot<T>
+    where
+        T: Default + Send + 'static,
+    {
+        self.slots
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Box::new(Slot::<T>::default()))
+            .downcast_ref::<Slot<T>>()
+            .expect("connection slot of another type")
+            .clone()
+    }
+}
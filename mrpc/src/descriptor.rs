@@ -0,0 +1,648 @@
+//! Machine readable descriptions of the services, emitted by the macros
+//! as `XRequest::descriptor()` and `ServerRequest::descriptor()`.
+//!
+//! A descriptor committed as a snapshot can be compared with the current
+//! one to find the changes that break deployed peers, see
+//! [`assert_compatible`]. A [`Route`] calls a method of a server with JSON
+//! values, for the peers which only have its descriptor.
+
+use std::{fmt, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// A `#[mrpc::service]` trait.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ServiceDescriptor {
+    pub name: String,
+    /// Type parameters, which the argument types may refer to.
+    #[serde(default)]
+    pub type_params: Vec<String>,
+    /// Whether the methods are encoded by id, see `message(compact)`.
+    #[serde(default)]
+    pub compact: bool,
+    /// How the variants of the methods are tagged.
+    #[serde(default)]
+    pub tagging: Tagging,
+    pub methods: Vec<MethodDescriptor>,
+}
+
+/// How the variants of the messages are tagged, after the enum
+/// representations of serde chosen with `message(serde(..))`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Tagging {
+    /// `{"Variant": value}`, the default.
+    #[default]
+    External,
+    /// `{tag: "Variant", ..value}`, `serde(tag = ..)`.
+    Internal { tag: String },
+    /// `{tag: "Variant", content: value}`, `serde(tag = .., content = ..)`.
+    Adjacent { tag: String, content: String },
+    /// `value`, `serde(untagged)`.
+    Untagged,
+}
+
+/// A method of a service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct MethodDescriptor {
+    pub name: String,
+    /// Name of the request and response variants on the wire.
+    pub wire_name: String,
+    #[serde(default)]
+    pub id: Option<u32>,
+    #[serde(default)]
+    pub doc: String,
+    pub args: Vec<ArgDescriptor>,
+    pub output: String,
+    #[serde(default)]
+    pub sequential: bool,
+}
+
+/// An argument of a method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ArgDescriptor {
+    pub name: String,
+    pub ty: String,
+    /// Whether the argument has `#[serde(default)]`, so that requests
+    /// without it are accepted.
+    #[serde(default)]
+    pub default: bool,
+}
+
+/// A `#[mrpc::server]` enum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ServerDescriptor {
+    pub name: String,
+    /// How the variants of the services are tagged.
+    #[serde(default)]
+    pub tagging: Tagging,
+    pub services: Vec<ServiceEntry>,
+}
+
+/// A service of a server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ServiceEntry {
+    /// Name of the variant.
+    pub name: String,
+    /// Name of the variant on the wire, when serde renames it.
+    #[serde(default)]
+    pub wire_name: Option<String>,
+    /// Type arguments of the service, matching its `type_params`.
+    #[serde(default)]
+    pub type_args: Vec<String>,
+    pub service: ServiceDescriptor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Peers built from the old descriptor keep working.
+    Compatible,
+    /// Peers built from the old descriptor fail on some calls.
+    Breaking,
+}
+
+/// A difference between two descriptors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// `Service.method` the change is about.
+    pub path: String,
+    pub message: String,
+}
+
+impl Change {
+    fn compatible(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            kind: ChangeKind::Compatible,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    fn breaking(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            kind: ChangeKind::Breaking,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.kind == ChangeKind::Breaking
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ChangeKind::Compatible => "compatible",
+            ChangeKind::Breaking => "breaking",
+        };
+        write!(f, "{}: {}: {}", kind, self.path, self.message)
+    }
+}
+
+/// A descriptor which can be compared with an older version of itself.
+pub trait Descriptor: Serialize + DeserializeOwned {
+    /// The changes from `old` to `self`, judged by whether clients built
+    /// from `old` can still call a server built from `self`.
+    fn changes_since(&self, old: &Self) -> Vec<Change>;
+
+    /// A hash of what the descriptor puts on the wire, stable across
+    /// builds, which peers compare in the opening handshake. The docs and
+    /// the Rust names are left out, so changing them keeps peers talking.
+    fn fingerprint(&self) -> String;
+}
+
+/// FNV-1a of `value`, since the std hashers may change between releases.
+fn fingerprint(value: &Value) -> String {
+    let json = serde_json::to_vec(value).expect("serialize descriptor");
+    let hash = json.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+impl fmt::Display for Tagging {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tagging::External => write!(f, "externally tagged"),
+            Tagging::Internal { tag } => write!(f, "internally tagged by `{}`", tag),
+            Tagging::Adjacent { tag, content } => {
+                write!(f, "adjacently tagged by `{}` and `{}`", tag, content)
+            }
+            Tagging::Untagged => write!(f, "untagged"),
+        }
+    }
+}
+
+impl Tagging {
+    /// `value` as the variant `name`.
+    fn wrap(&self, name: &str, value: Value) -> Value {
+        match (self, value) {
+            (Tagging::Internal { tag }, Value::Object(mut fields)) => {
+                fields.insert(tag.clone(), name.into());
+                Value::Object(fields)
+            }
+            (Tagging::Adjacent { tag, content }, value) => {
+                json!({ tag.as_str(): name, content.as_str(): value })
+            }
+            (_, value) => json!({ name: value }),
+        }
+    }
+
+    /// The value of the variant `name` in `value`, if it is that variant.
+    fn unwrap(&self, name: &str, value: &Value) -> Option<Value> {
+        match self {
+            Tagging::Internal { tag } if value.get(tag)? == name => {
+                let mut fields = value.as_object()?.clone();
+                fields.remove(tag);
+                Some(Value::Object(fields))
+            }
+            Tagging::Adjacent { tag, content } if value.get(tag)? == name => {
+                // Serde leaves out the content of unit variants.
+                Some(value.get(content).cloned().unwrap_or(Value::Null))
+            }
+            Tagging::External => value.get(name).cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl ServiceEntry {
+    /// Name of the variant on the wire.
+    pub fn wire_name(&self) -> &str {
+        self.wire_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+impl MethodDescriptor {
+    /// The fields which change the messages of the method.
+    fn wire(&self) -> Value {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| json!([arg.name, arg.ty, arg.default]))
+            .collect::<Vec<_>>();
+        json!([self.wire_name, self.id, args, self.output])
+    }
+
+    /// What identifies the method on the wire.
+    fn key(&self, compact: bool) -> String {
+        match (compact, self.id) {
+            (true, Some(id)) => id.to_string(),
+            _ => self.wire_name.clone(),
+        }
+    }
+
+    fn changes_since(&self, old: &Self, compact: bool, path: &str, changes: &mut Vec<Change>) {
+        if self.name != old.name {
+            changes.push(Change::compatible(
+                path,
+                format!("renamed to `{}` with the same wire name", self.name),
+            ));
+        }
+
+        if self.output != old.output {
+            changes.push(Change::breaking(
+                path,
+                format!("returns `{}` instead of `{}`", self.output, old.output),
+            ));
+        }
+
+        if compact {
+            let tys = |args: &[ArgDescriptor]| {
+                args.iter()
+                    .map(|arg| arg.ty.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let (new_tys, old_tys) = (tys(&self.args), tys(&old.args));
+            if new_tys != old_tys {
+                changes.push(Change::breaking(
+                    path,
+                    format!("takes `({})` instead of `({})`", new_tys, old_tys),
+                ));
+            }
+        } else {
+            for old_arg in &old.args {
+                match self.args.iter().find(|arg| arg.name == old_arg.name) {
+                    Some(arg) if arg.ty != old_arg.ty => changes.push(Change::breaking(
+                        path,
+                        format!(
+                            "argument `{}` is `{}` instead of `{}`",
+                            arg.name, arg.ty, old_arg.ty
+                        ),
+                    )),
+                    Some(_) => {}
+                    None => changes.push(Change::compatible(
+                        path,
+                        format!("argument `{}` was removed", old_arg.name),
+                    )),
+                }
+            }
+
+            for arg in &self.args {
+                if old.args.iter().any(|old_arg| old_arg.name == arg.name) {
+                    continue;
+                }
+                changes.push(if arg.default {
+                    Change::compatible(path, format!("argument `{}` was added", arg.name))
+                } else {
+                    Change::breaking(path, format!("required argument `{}` was added", arg.name))
+                });
+            }
+        }
+
+        if self.sequential != old.sequential {
+            changes.push(Change::compatible(
+                path,
+                if self.sequential {
+                    "became sequential"
+                } else {
+                    "is no longer sequential"
+                },
+            ));
+        }
+    }
+}
+
+impl ServiceDescriptor {
+    fn wire(&self) -> Value {
+        let methods = self
+            .methods
+            .iter()
+            .map(MethodDescriptor::wire)
+            .collect::<Vec<_>>();
+        json!([self.compact, self.tagging, methods])
+    }
+}
+
+impl Descriptor for ServiceDescriptor {
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.wire())
+    }
+
+    fn changes_since(&self, old: &Self) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        if self.compact != old.compact {
+            changes.push(Change::breaking(
+                &self.name,
+                "the methods are encoded by id in one version and by name in the other",
+            ));
+            return changes;
+        }
+
+        if self.tagging != old.tagging {
+            changes.push(Change::breaking(
+                &self.name,
+                format!(
+                    "the methods are {} instead of {}",
+                    self.tagging, old.tagging
+                ),
+            ));
+            return changes;
+        }
+
+        for old_method in &old.methods {
+            let key = old_method.key(old.compact);
+            let path = format!("{}.{}", self.name, old_method.name);
+            match self
+                .methods
+                .iter()
+                .find(|method| method.key(self.compact) == key)
+            {
+                Some(method) => method.changes_since(old_method, self.compact, &path, &mut changes),
+                None => changes.push(Change::breaking(path, "method was removed")),
+            }
+        }
+
+        for method in &self.methods {
+            let key = method.key(self.compact);
+            if !old
+                .methods
+                .iter()
+                .any(|old_method| old_method.key(old.compact) == key)
+            {
+                changes.push(Change::compatible(
+                    format!("{}.{}", self.name, method.name),
+                    "method was added",
+                ));
+            }
+        }
+
+        changes
+    }
+}
+
+impl Descriptor for ServerDescriptor {
+    fn fingerprint(&self) -> String {
+        let services = self
+            .services
+            .iter()
+            .map(|entry| json!([entry.wire_name(), entry.type_args, entry.service.wire()]))
+            .collect::<Vec<_>>();
+        fingerprint(&json!([self.tagging, services]))
+    }
+
+    fn changes_since(&self, old: &Self) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        if self.tagging != old.tagging {
+            changes.push(Change::breaking(
+                &self.name,
+                format!(
+                    "the services are {} instead of {}",
+                    self.tagging, old.tagging
+                ),
+            ));
+            return changes;
+        }
+
+        for old_entry in &old.services {
+            match self
+                .services
+                .iter()
+                .find(|entry| entry.name == old_entry.name)
+            {
+                Some(entry) => {
+                    if entry.wire_name() != old_entry.wire_name() {
+                        changes.push(Change::breaking(
+                            &entry.name,
+                            format!(
+                                "is `{}` on the wire instead of `{}`",
+                                entry.wire_name(),
+                                old_entry.wire_name()
+                            ),
+                        ));
+                    }
+                    if entry.type_args != old_entry.type_args {
+                        changes.push(Change::breaking(
+                            &entry.name,
+                            format!(
+                                "type arguments are `<{}>` instead of `<{}>`",
+                                entry.type_args.join(", "),
+                                old_entry.type_args.join(", ")
+                            ),
+                        ));
+                    }
+                    changes.extend(
+                        entry
+                            .service
+                            .changes_since(&old_entry.service)
+                            .into_iter()
+                            .map(|change| Change {
+                                path: format!("{}::{}", entry.name, change.path),
+                                ..change
+                            }),
+                    );
+                }
+                None => changes.push(Change::breaking(&old_entry.name, "service was removed")),
+            }
+        }
+
+        for entry in &self.services {
+            if !old
+                .services
+                .iter()
+                .any(|old_entry| old_entry.name == entry.name)
+            {
+                changes.push(Change::compatible(&entry.name, "service was added"));
+            }
+        }
+
+        changes
+    }
+}
+
+/// How to call a method of a server with JSON values: the arguments as the
+/// request of the server, and its response as the output of the method,
+/// as the generated enums serialize them.
+#[derive(Debug, Clone)]
+pub struct Route {
+    server: Tagging,
+    service: String,
+    tagging: Tagging,
+    compact: bool,
+    method: String,
+    wire_name: String,
+    id: Option<u32>,
+    args: Vec<String>,
+}
+
+impl Route {
+    /// The route of `method` of the service `entry` of `server`. Fails
+    /// when the messages are untagged, since their variants can't be told
+    /// apart by name.
+    pub fn new(
+        server: &ServerDescriptor,
+        entry: &ServiceEntry,
+        method: &MethodDescriptor,
+    ) -> Result<Self, String> {
+        let untagged = |what: String| {
+            Err(format!(
+                "{} can't be called by name, they are untagged",
+                what
+            ))
+        };
+        if server.tagging == Tagging::Untagged {
+            return untagged(format!("the services of `{}`", server.name));
+        }
+        if entry.service.tagging == Tagging::Untagged {
+            return untagged(format!("the methods of `{}`", entry.name));
+        }
+        if entry.service.compact && method.id.is_none() {
+            return Err(format!("`{}.{}` has no id", entry.name, method.name));
+        }
+
+        Ok(Self {
+            server: server.tagging.clone(),
+            service: entry.wire_name().to_string(),
+            tagging: entry.service.tagging.clone(),
+            compact: entry.service.compact,
+            method: method.name.clone(),
+            wire_name: method.wire_name.clone(),
+            id: method.id,
+            args: method.args.iter().map(|arg| arg.name.clone()).collect(),
+        })
+    }
+
+    /// The request of the server for `args`, the arguments by name or in
+    /// order. Fails with why the arguments are invalid.
+    pub fn request<Request>(&self, args: Value) -> Result<Request, String>
+    where
+        Request: DeserializeOwned,
+    {
+        let args = match args {
+            Value::Null => Map::new(),
+            Value::Object(args) => args,
+            Value::Array(values) if values.len() == self.args.len() => {
+                self.args.iter().cloned().zip(values).collect()
+            }
+            Value::Array(values) => {
+                return Err(format!(
+                    "`{}` takes {} arguments, got {}",
+                    self.method,
+                    self.args.len(),
+                    values.len()
+                ))
+            }
+            value => {
+                return Err(format!(
+                    "expected an object or an array of arguments, got {}",
+                    value
+                ))
+            }
+        };
+
+        if let Some(name) = args.keys().find(|name| !self.args.contains(name)) {
+            return Err(format!("`{}` has no argument `{}`", self.method, name));
+        }
+
+        let value = match (self.compact, self.id) {
+            (true, Some(id)) => {
+                let values = self
+                    .args
+                    .iter()
+                    .map(|name| {
+                        args.get(name)
+                            .cloned()
+                            .ok_or_else(|| format!("missing argument `{}`", name))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let values = if values.is_empty() {
+                    Value::Null
+                } else {
+                    Value::Array(values)
+                };
+                json!([id, values])
+            }
+            _ => self.tagging.wrap(&self.wire_name, Value::Object(args)),
+        };
+
+        serde_json::from_value(self.server.wrap(&self.service, value)).map_err(|e| e.to_string())
+    }
+
+    /// The output of the method in `value`, a response of the server.
+    pub fn output<Response>(&self, value: Response) -> Result<Value, String>
+    where
+        Response: Serialize,
+    {
+        let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        let output = self.server.unwrap(&self.service, &value).and_then(|value| {
+            if self.compact {
+                match value.as_array()?.as_slice() {
+                    [id, output] if id.as_u64() == self.id.map(u64::from) => Some(output.clone()),
+                    _ => None,
+                }
+            } else {
+                self.tagging.unwrap(&self.wire_name, &value)
+            }
+        });
+        output.ok_or_else(|| format!("unexpected response {}", value))
+    }
+}
+
+/// Compares `current` with the snapshot at `path` and panics if they
+/// differ, listing the changes and whether they break peers built from the
+/// snapshot. For use in tests.
+///
+/// The snapshot is written instead, missing or not, when the
+/// `MRPC_UPDATE_SNAPSHOTS` environment variable is set.
+pub fn assert_compatible<D: Descriptor>(path: impl AsRef<Path>, current: &D) {
+    let path = path.as_ref();
+
+    if std::env::var_os("MRPC_UPDATE_SNAPSHOTS").is_some() {
+        let json = serde_json::to_string_pretty(current).expect("serialize descriptor");
+        std::fs::write(path, json + "\n")
+            .unwrap_or_else(|e| panic!("write {}: {}", path.display(), e));
+        return;
+    }
+
+    if !path.exists() {
+        panic!(
+            "missing snapshot {}, set MRPC_UPDATE_SNAPSHOTS to write it",
+            path.display()
+        );
+    }
+
+    let old = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str::<D>(&json)?))
+        .unwrap_or_else(|e| panic!("read {}: {}", path.display(), e));
+
+    let changes = current.changes_since(&old);
+    let (breaking, compatible): (Vec<_>, Vec<_>) =
+        changes.iter().partition(|change| change.is_breaking());
+    let list = |changes: Vec<&Change>| {
+        changes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    if !breaking.is_empty() {
+        panic!(
+            "incompatible changes since {}:\n{}",
+            path.display(),
+            list(breaking)
+        );
+    }
+
+    if !compatible.is_empty() {
+        panic!(
+            "compatible changes since {}, set MRPC_UPDATE_SNAPSHOTS to update it:\n{}",
+            path.display(),
+            list(compatible)
+        );
+    }
+}
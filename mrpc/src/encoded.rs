@@ -0,0 +1,105 @@
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+
+use crate::Error;
+
+/// A request serialized by the client from borrowed arguments.
+///
+/// The transports write it as is, an in-process server decodes it back.
+#[derive(Debug, Clone)]
+pub struct Encoded(Box<RawValue>);
+
+impl Encoded {
+    pub fn new<T>(value: &T) -> Result<Self, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        serde_json::value::to_raw_value(value)
+            .map(Self)
+            .map_err(|e| Error::Protocol(e.to_string()))
+    }
+
+    pub fn decode<T>(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_str(self.0.get()).map_err(|e| Error::Protocol(e.to_string()))
+    }
+}
+
+impl PartialEq for Encoded {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.get() == other.0.get()
+    }
+}
+
+impl Eq for Encoded {}
+
+impl std::hash::Hash for Encoded {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.get().hash(state)
+    }
+}
+
+impl Serialize for Encoded {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+/// The request of a [`Message`](crate::Message) to a `message(serde)`
+/// server: built by the client, or serialized from borrowed arguments.
+#[derive(Debug, Clone)]
+pub enum Payload<Request> {
+    Value(Request),
+    Encoded(Encoded),
+}
+
+impl<Request> Payload<Request> {
+    /// The request, decoded if the client serialized it.
+    pub fn decode(self) -> Result<Request, Error>
+    where
+        Request: DeserializeOwned,
+    {
+        match self {
+            Payload::Value(req) => Ok(req),
+            Payload::Encoded(encoded) => encoded.decode(),
+        }
+    }
+}
+
+impl<Request> From<Request> for Payload<Request> {
+    fn from(req: Request) -> Self {
+        Payload::Value(req)
+    }
+}
+
+impl<Request> Serialize for Payload<Request>
+where
+    Request: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Payload::Value(req) => req.serialize(serializer),
+            Payload::Encoded(encoded) => encoded.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, Request> Deserialize<'de> for Payload<Request>
+where
+    Request: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Request::deserialize(deserializer).map(Payload::Value)
+    }
+}
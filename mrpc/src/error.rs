@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+/// Errors produced by the framework itself, as opposed to the values
+/// returned by service methods.
+///
+/// They travel on the wire in place of a response, so a client can tell a
+/// rejected call apart from a lost connection.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+pub enum Error {
+    /// The peer sent a frame that could not be decoded.
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    /// The server refused the request or connection because of its
+    /// [`Limits`](crate::net::Limits).
+    #[error("limit exceeded: {0}")]
+    LimitExceeded(Limit),
+    /// The wait queue of the named service was full.
+    #[error("service {0} is overloaded")]
+    Overloaded(String),
+    /// The named service could not be created, or can't run sequential
+    /// calls anymore. The cause is only logged by the server.
+    #[error("service {0} is unavailable")]
+    ServiceUnavailable(String),
+    /// The peers have nothing in common to speak, found in the opening
+    /// [`Hello`](crate::net::Hello) exchange.
+    #[error("incompatible peer: {0}")]
+    Incompatible(String),
+    /// The server failed while handling the call.
+    #[error("{kind}: {message}")]
+    Remote {
+        kind: RemoteErrorKind,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteErrorKind {
+    /// The service method panicked. The message is the panic payload.
+    Panic,
+    /// A layer of the server, added with the `tower` feature, failed the
+    /// call. The message is its error.
+    Layer,
+}
+
+impl std::fmt::Display for RemoteErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteErrorKind::Panic => f.write_str("service panicked"),
+            RemoteErrorKind::Layer => f.write_str("layer failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Limit {
+    FrameSize,
+    ConcurrentRequests,
+    PendingResponses,
+    Connections,
+    ConnectionsPerIp,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Limit::FrameSize => "frame too large",
+            Limit::ConcurrentRequests => "too many concurrent requests",
+            Limit::PendingResponses => "too many pending responses",
+            Limit::Connections => "too many connections",
+            Limit::ConnectionsPerIp => "too many connections from this address",
+        };
+        f.write_str(s)
+    }
+}
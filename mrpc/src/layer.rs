@@ -0,0 +1,173 @@
+//! Interceptors for clients and servers, as `tower` layers, with the
+//! `tower` feature.
+//!
+//! [`intercept`] puts the service of a layer in front of the channel of
+//! [`Message`]s between a transport and its user, so it sees every call as
+//! a [`Request`] and its response:
+//!
+//! - on a client, in front of the sender of the transport, before giving it
+//!   to the generated client, so the calls of all the services go through;
+//! - on a server, in front of the sender of the generated `serve`, before
+//!   giving it to the transport.
+//!
+//! The layers may change the requests, the responses and the metadata, and
+//! fail the calls. The [`Metadata`] of a client request is sent to the
+//! server, where the layers find it in the request. An [`Error`] of a layer
+//! reaches the caller as is, other errors as [`RemoteErrorKind::Layer`].
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future;
+
+pub use tower_layer::{Identity, Layer, Stack};
+pub use tower_service::Service;
+
+use crate::{
+    spawn,
+    sync::{mpsc, oneshot},
+    trace::Span,
+    Connection, Error, Message, Metadata, RemoteErrorKind,
+};
+
+/// The errors of the services of `tower`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A call through the layers, the [`Message`] without its response channel.
+pub struct Request<T> {
+    pub req: T,
+    pub conn: Arc<Connection>,
+    pub span: Span,
+    pub metadata: Metadata,
+}
+
+/// The innermost service, forwarding the requests to the channel it was
+/// created with.
+pub struct Forward<Req, Resp> {
+    tx: mpsc::Sender<Message<Req, Resp>>,
+}
+
+impl<Req, Resp> Forward<Req, Resp> {
+    pub fn new(tx: mpsc::Sender<Message<Req, Resp>>) -> Self {
+        Self { tx }
+    }
+}
+
+impl<Req, Resp> Clone for Forward<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<Req, Resp> Service<Request<Req>> for Forward<Req, Resp>
+where
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    type Response = Resp;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Resp, Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        let tx = self.tx.clone();
+        Box::pin(async move {
+            let Request {
+                req,
+                conn,
+                span,
+                metadata,
+            } = request;
+            let (resp, rx) = oneshot::channel();
+            let closed = || Error::Protocol("connection closed".into());
+            tx.send(Message {
+                req,
+                resp,
+                conn,
+                span,
+                metadata,
+            })
+            .await
+            .map_err(|_| closed())?;
+            rx.await.map_err(|_| closed())?
+        })
+    }
+}
+
+/// Runs the [`Message`]s sent to the returned sender through the service of
+/// `layer`, in front of `tx`.
+pub fn intercept<L, Req, Resp>(
+    layer: L,
+    tx: mpsc::Sender<Message<Req, Resp>>,
+) -> mpsc::Sender<Message<Req, Resp>>
+where
+    L: Layer<Forward<Req, Resp>>,
+    L::Service: Service<Request<Req>, Response = Resp> + Send + 'static,
+    <L::Service as Service<Request<Req>>>::Error: Into<BoxError>,
+    <L::Service as Service<Request<Req>>>::Future: Send + 'static,
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    let (rpctx, rx) = mpsc::channel(32);
+    spawn(run_loop(layer.layer(Forward::new(tx)), rx));
+    rpctx
+}
+
+async fn run_loop<S, Req, Resp>(mut service: S, mut rx: mpsc::Receiver<Message<Req, Resp>>)
+where
+    S: Service<Request<Req>, Response = Resp>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    while let Some(msg) = rx.recv().await {
+        let Message {
+            req,
+            resp,
+            conn,
+            span,
+            metadata,
+        } = msg;
+
+        // The calls wait for the service, which may be limiting them.
+        if let Err(e) = future::poll_fn(|cx| service.poll_ready(cx)).await {
+            if resp.send(Err(into_error(e.into()))).is_err() {
+                log::warn!("Failed to send response");
+            }
+            continue;
+        }
+
+        let call = service.call(Request {
+            req,
+            conn,
+            span,
+            metadata,
+        });
+        spawn(async move {
+            let result = call.await.map_err(|e| into_error(e.into()));
+            if resp.send(result).is_err() {
+                log::warn!("Failed to send response");
+            }
+        });
+    }
+}
+
+fn into_error(e: BoxError) -> Error {
+    match e.downcast::<Error>() {
+        Ok(e) => *e,
+        Err(e) => Error::Remote {
+            kind: RemoteErrorKind::Layer,
+            message: e.to_string(),
+        },
+    }
+}
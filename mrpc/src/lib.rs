@@ -1,11 +1,33 @@
+// The code generated by the macros refers to `mrpc::`.
+extern crate self as mrpc;
+
+mod connection;
+pub mod descriptor;
+mod encoded;
+mod error;
+#[cfg(feature = "tower")]
+pub mod layer;
+pub mod metrics;
 pub mod net;
+#[cfg(feature = "schemars")]
+pub mod openrpc;
+pub mod reflection;
+pub mod server;
+pub mod trace;
+
+pub use connection::Connection;
+pub use encoded::{Encoded, Payload};
+pub use error::{Error, Limit, RemoteErrorKind};
 
 pub use mrpc_derive::*;
 
 pub use anyhow;
 pub use async_trait::async_trait;
 pub use log;
+#[cfg(feature = "schemars")]
+pub use schemars;
 pub use serde;
+pub use tokio;
 
 pub mod sync {
     pub use tokio::sync::{mpsc, oneshot, Mutex};
@@ -17,14 +39,45 @@ pub use wasm_bindgen_futures::{spawn_local as spawn, spawn_local};
 #[cfg(not(target_arch = "wasm32"))]
 pub use tokio::{spawn, task::spawn_local};
 
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
 use sync::*;
 
+/// Strings sent along a request, such as credentials, for the layers of the
+/// peers rather than for the services.
+pub type Metadata = HashMap<String, String>;
+
 pub struct Message<Request, Response> {
     pub req: Request,
-    pub resp: oneshot::Sender<Response>,
+    pub resp: oneshot::Sender<Result<Response, Error>>,
+    pub conn: Arc<Connection>,
+    /// The span of the call, see [`trace`].
+    pub span: trace::Span,
+    pub metadata: Metadata,
 }
 
 #[async_trait]
 pub trait Poster<Request, Response> {
-    async fn post(&self, req: Request, resp: oneshot::Sender<Response>) -> anyhow::Result<()>;
+    async fn post(
+        &self,
+        req: Request,
+        resp: oneshot::Sender<Result<Response, Error>>,
+    ) -> anyhow::Result<()>;
+}
+
+/// Posts the borrowed requests of the `#[rpc(borrow)]` methods of a
+/// service. The posters of `message(serde)` servers serialize them, the
+/// others make them owned, before the returned future runs.
+pub trait PostRef<Request, Response> {
+    fn post_ref(
+        &self,
+        req: Request,
+        resp: oneshot::Sender<Result<Response, Error>>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+}
+
+/// Implemented by `#[mrpc::service]` for the borrowed request of a service.
+pub trait IntoOwned {
+    type Owned;
+
+    fn into_owned(self) -> Self::Owned;
 }
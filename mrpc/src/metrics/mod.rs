@@ -0,0 +1,181 @@
+//! Metrics of the calls and the transports, recorded with the `metrics`
+//! feature through the [`metrics`](https://docs.rs/metrics) facade, to be
+//! exported by the recorder the application installs, or by the
+//! `prometheus` module with the feature of the same name.
+//!
+//! The calls of the generated clients and the handlers of the generated
+//! `serve` record, labelled with `side` (`client` or `server`), `service`
+//! and `method`:
+//!
+//! - `mrpc_requests_total`, the finished calls;
+//! - `mrpc_errors_total`, the failed calls, also labelled with the `kind` of
+//!   [`Error`];
+//! - `mrpc_request_duration_seconds`, a histogram of the latency of the
+//!   calls;
+//! - `mrpc_requests_in_flight`, a gauge of the pending calls.
+//!
+//! The transports record `mrpc_transport_bytes_total`, labelled with
+//! `transport` and `direction` (`in` or `out`), the size of the frames.
+//!
+//! Without the feature, or on wasm, [`Call`] and [`Transport`] are empty and
+//! their methods do nothing.
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+use crate::Error;
+
+/// The errors counted by their kind.
+pub trait ErrorKind {
+    fn error_kind(&self) -> &'static str;
+}
+
+impl ErrorKind for Error {
+    fn error_kind(&self) -> &'static str {
+        match self {
+            Error::Protocol(_) => "protocol",
+            Error::LimitExceeded(_) => "limit_exceeded",
+            Error::Overloaded(_) => "overloaded",
+            Error::ServiceUnavailable(_) => "service_unavailable",
+            Error::Incompatible(_) => "incompatible",
+            Error::Remote { .. } => "remote",
+        }
+    }
+}
+
+/// The errors of the clients that are not an [`Error`] of the server come
+/// from the connection.
+impl ErrorKind for anyhow::Error {
+    fn error_kind(&self) -> &'static str {
+        self.downcast_ref::<Error>()
+            .map_or("transport", ErrorKind::error_kind)
+    }
+}
+
+impl<E> ErrorKind for &E
+where
+    E: ErrorKind + ?Sized,
+{
+    fn error_kind(&self) -> &'static str {
+        (**self).error_kind()
+    }
+}
+
+/// A pending call, in flight until it is dropped.
+pub struct Call {
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    labels: [(&'static str, &'static str); 3],
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    start: std::time::Instant,
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    in_flight: ::metrics::Gauge,
+}
+
+/// The bytes of the frames of a transport.
+#[derive(Clone)]
+pub struct Transport {
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    received: ::metrics::Counter,
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    sent: ::metrics::Counter,
+}
+
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+impl Call {
+    /// A call by a client.
+    pub fn client(service: &'static str, method: &'static str) -> Self {
+        Self::new("client", service, method)
+    }
+
+    /// A call handled by a server.
+    pub fn server(service: &'static str, method: &'static str) -> Self {
+        Self::new("server", service, method)
+    }
+
+    fn new(side: &'static str, service: &'static str, method: &'static str) -> Self {
+        let labels = [("side", side), ("service", service), ("method", method)];
+        let in_flight = ::metrics::gauge!("mrpc_requests_in_flight", &labels);
+        in_flight.increment(1.0);
+        Self {
+            labels,
+            start: std::time::Instant::now(),
+            in_flight,
+        }
+    }
+
+    /// Records the call, its latency and its error.
+    pub fn finish<T, E>(self, result: &Result<T, E>)
+    where
+        E: ErrorKind,
+    {
+        ::metrics::counter!("mrpc_requests_total", &self.labels).increment(1);
+        ::metrics::histogram!("mrpc_request_duration_seconds", &self.labels)
+            .record(self.start.elapsed().as_secs_f64());
+        if let Err(e) = result {
+            let [side, service, method] = self.labels;
+            let labels = [side, service, method, ("kind", e.error_kind())];
+            ::metrics::counter!("mrpc_errors_total", &labels).increment(1);
+        }
+    }
+}
+
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+impl Drop for Call {
+    fn drop(&mut self) {
+        self.in_flight.decrement(1.0);
+    }
+}
+
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+impl Transport {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            received: ::metrics::counter!(
+                "mrpc_transport_bytes_total",
+                "transport" => name,
+                "direction" => "in"
+            ),
+            sent: ::metrics::counter!(
+                "mrpc_transport_bytes_total",
+                "transport" => name,
+                "direction" => "out"
+            ),
+        }
+    }
+
+    pub fn received(&self, len: usize) {
+        self.received.increment(len as u64);
+    }
+
+    pub fn sent(&self, len: usize) {
+        self.sent.increment(len as u64);
+    }
+}
+
+#[cfg(not(all(feature = "metrics", not(target_arch = "wasm32"))))]
+impl Call {
+    pub fn client(_service: &'static str, _method: &'static str) -> Self {
+        Self {}
+    }
+
+    pub fn server(_service: &'static str, _method: &'static str) -> Self {
+        Self {}
+    }
+
+    pub fn finish<T, E>(self, _result: &Result<T, E>)
+    where
+        E: ErrorKind,
+    {
+    }
+}
+
+#[cfg(not(all(feature = "metrics", not(target_arch = "wasm32"))))]
+impl Transport {
+    pub fn new(_name: &'static str) -> Self {
+        Self {}
+    }
+
+    pub fn received(&self, _len: usize) {}
+
+    pub fn sent(&self, _len: usize) {}
+}
@@ -0,0 +1,81 @@
+//! The metrics in the Prometheus text format, served over HTTP by
+//! [`serve`] with the handle of the recorder [`install`]ed for the process.
+
+use std::convert::Infallible;
+
+use hyper::{
+    header::{self, HeaderValue},
+    server::conn::Http,
+    service::service_fn,
+    Body, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+pub use metrics_exporter_prometheus::PrometheusHandle;
+
+/// The buckets of `mrpc_request_duration_seconds`, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the recorder of the process, whose handle renders the metrics.
+pub fn install() -> anyhow::Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("mrpc_request_duration_seconds".into()),
+            LATENCY_BUCKETS,
+        )?
+        .install_recorder()?;
+    Ok(handle)
+}
+
+/// Serves the metrics of `handle` to `GET`s of any path on `addr`.
+pub async fn serve<Addr>(addr: Addr, handle: PrometheusHandle) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+{
+    serve_with_listener(TcpListener::bind(addr).await?, handle).await
+}
+
+/// Serves like [`serve`] the connections of `listener`, bound by the caller.
+pub async fn serve_with_listener(
+    listener: TcpListener,
+    handle: PrometheusHandle,
+) -> anyhow::Result<()> {
+    loop {
+        let (s, peer) = listener.accept().await?;
+
+        let handle = handle.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| {
+                let resp = render(&handle, &req);
+                async move { Ok::<_, Infallible>(resp) }
+            });
+            if let Err(e) = Http::new()
+                .http1_only(true)
+                .serve_connection(s, service)
+                .await
+            {
+                log::warn!("Failed to serve metrics to {}: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+fn render(handle: &PrometheusHandle, req: &HttpRequest<Body>) -> HttpResponse<Body> {
+    if req.method() != Method::GET {
+        let mut resp = HttpResponse::new(Body::empty());
+        *resp.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        resp.headers_mut()
+            .insert(header::ALLOW, HeaderValue::from_static("GET"));
+        return resp;
+    }
+
+    let mut resp = HttpResponse::new(Body::from(handle.render()));
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    resp
+}
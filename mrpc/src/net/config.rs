@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::Limit;
+
+use super::{Hello, JsonRpc};
+
+/// Settings shared by the listeners in this module.
+///
+/// Passing clones of the same config to several listeners makes them share
+/// the connection limits and the stats.
+#[derive(Clone, Default)]
+pub struct ServerConfig {
+    /// Close a connection once it has produced this many protocol errors.
+    /// `None` keeps the connection open regardless.
+    pub max_protocol_errors: Option<usize>,
+    pub limits: Limits,
+    /// Counters updated by every connection accepted with this config.
+    pub stats: Arc<ServerStats>,
+    /// Expect a [`Hello`] from each client before its requests, and refuse
+    /// the clients it has nothing in common with. `None` serves clients
+    /// which don't send one.
+    pub handshake: Option<Hello>,
+    /// Speak JSON-RPC 2.0 instead of the mrpc protocol. The tcp and unix
+    /// socket transports then delimit the frames by newlines, and the
+    /// handshake is skipped.
+    pub jsonrpc: Option<Arc<JsonRpc>>,
+}
+
+/// Settings of the `writer` of the transports in this module, and of the
+/// `connect` of the browser ones.
+#[derive(Clone, Default)]
+pub struct ClientConfig {
+    /// Send a [`Hello`] when connecting, failing if the server refuses it.
+    pub handshake: Option<Hello>,
+}
+
+/// Resource limits applied by the listeners. The optional ones are
+/// unlimited when `None`.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// Largest frame accepted from a peer, in bytes.
+    pub max_frame_size: usize,
+    /// Requests of a single connection being handled at the same time,
+    /// counting those whose response is not queued yet.
+    pub max_concurrent_requests: Option<usize>,
+    /// Responses queued for a peer that does not read them. A peer going
+    /// over it is disconnected.
+    pub max_pending_responses: usize,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    /// How long a client has to send its [`Hello`] when
+    /// [`ServerConfig::handshake`] is set, before it is disconnected.
+    pub handshake_timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_frame_size: 8 * 1024 * 1024,
+            max_concurrent_requests: None,
+            max_pending_responses: 32,
+            max_connections: None,
+            max_connections_per_ip: None,
+            handshake_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ServerStats {
+    malformed_frames: AtomicU64,
+    oversized_frames: AtomicU64,
+    closed_connections: AtomicU64,
+    rejected_connections: AtomicU64,
+    active_connections: AtomicUsize,
+    connections_per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl ServerStats {
+    /// Frames that could not be decoded into a request.
+    pub fn malformed_frames(&self) -> u64 {
+        self.malformed_frames.load(Ordering::Relaxed)
+    }
+
+    /// Frames rejected because they exceeded [`Limits::max_frame_size`].
+    pub fn oversized_frames(&self) -> u64 {
+        self.oversized_frames.load(Ordering::Relaxed)
+    }
+
+    /// Connections closed because of protocol errors.
+    pub fn closed_connections(&self) -> u64 {
+        self.closed_connections.load(Ordering::Relaxed)
+    }
+
+    /// Connections refused because of the connection limits.
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    /// Registers a connection from `ip`, failing if it would go over the
+    /// connection limits. The returned guard unregisters it on drop.
+    ///
+    /// Connections without address, from unix sockets, only count towards
+    /// [`Limits::max_connections`].
+    pub(crate) fn open_connection(
+        self: &Arc<Self>,
+        ip: Option<IpAddr>,
+        limits: &Limits,
+    ) -> Result<ConnectionGuard, Limit> {
+        let mut per_ip = self.connections_per_ip.lock().unwrap();
+
+        let count = ip.and_then(|ip| per_ip.get(&ip).copied()).unwrap_or(0);
+        let limit = if matches!(limits.max_connections, Some(max) if self.active_connections() >= max)
+        {
+            Some(Limit::Connections)
+        } else if matches!(limits.max_connections_per_ip, Some(max) if count >= max) {
+            Some(Limit::ConnectionsPerIp)
+        } else {
+            None
+        };
+
+        if let Some(limit) = limit {
+            self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            return Err(limit);
+        }
+
+        if let Some(ip) = ip {
+            per_ip.insert(ip, count + 1);
+        }
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+
+        Ok(ConnectionGuard {
+            stats: self.clone(),
+            ip,
+        })
+    }
+}
+
+pub(crate) struct ConnectionGuard {
+    stats: Arc<ServerStats>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut per_ip = self.stats.connections_per_ip.lock().unwrap();
+        if let Some(ip) = self.ip {
+            if let Some(count) = per_ip.get_mut(&ip) {
+                *count -= 1;
+                if *count == 0 {
+                    per_ip.remove(&ip);
+                }
+            }
+        }
+        self.stats
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) enum ProtocolEvent {
+    Malformed,
+    Oversized,
+}
+
+/// Per-connection bookkeeping of protocol errors.
+pub(crate) struct ProtocolGuard {
+    max_protocol_errors: Option<usize>,
+    stats: Arc<ServerStats>,
+    errors: usize,
+}
+
+impl ProtocolGuard {
+    pub(crate) fn new(config: &ServerConfig) -> Self {
+        Self {
+            max_protocol_errors: config.max_protocol_errors,
+            stats: config.stats.clone(),
+            errors: 0,
+        }
+    }
+
+    /// Records an error and returns whether the connection must be closed.
+    ///
+    /// An oversized frame always closes the connection since the stream
+    /// cannot be resynchronized after it.
+    pub(crate) fn on_error(&mut self, event: ProtocolEvent) -> bool {
+        let stats = &self.stats;
+        let fatal = match event {
+            ProtocolEvent::Malformed => {
+                stats.malformed_frames.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            ProtocolEvent::Oversized => {
+                stats.oversized_frames.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+        };
+
+        self.errors += 1;
+        let close = fatal || matches!(self.max_protocol_errors, Some(max) if self.errors >= max);
+        if close {
+            stats.closed_connections.fetch_add(1, Ordering::Relaxed);
+        }
+        close
+    }
+}
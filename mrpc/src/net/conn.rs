@@ -0,0 +1,380 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures::{
+    future::{self, Either},
+    Sink, SinkExt, Stream, StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot, Notify};
+
+use crate::{metrics::Transport, trace::Span, Connection, Error, Limit, Message, Metadata};
+
+use super::{
+    decode_request,
+    handshake::{self, Handshake},
+    jsonrpc::{self, Call, Incoming, JsonRpc},
+    Limits, ProtocolEvent, ProtocolGuard, RpcRequest, RpcResponse, ServerConfig,
+};
+
+/// A frame read by a transport.
+pub(crate) enum Frame {
+    Data(Vec<u8>),
+    /// The transport refused a frame above [`Limits::max_frame_size`](super::Limits).
+    Oversized,
+}
+
+/// Queues a frame for the writer. Fails when the peer does not read its
+/// responses fast enough.
+fn queue(resp_tx: &mpsc::Sender<Vec<u8>>, data: Vec<u8>) -> anyhow::Result<()> {
+    if resp_tx.try_send(data).is_err() {
+        return Err(Error::LimitExceeded(Limit::PendingResponses).into());
+    }
+    Ok(())
+}
+
+/// Queues the response of a handler, waking `overflow` to close the
+/// connection when the peer does not read its responses fast enough.
+fn queue_response(resp_tx: &mpsc::Sender<Vec<u8>>, overflow: &Notify, data: Vec<u8>) {
+    if let Err(e) = queue(resp_tx, data) {
+        log::warn!("{}", e);
+        overflow.notify_one();
+    }
+}
+
+/// A frame reporting an error that isn't about a particular request.
+fn error_frame<Response>(config: &ServerConfig, e: Error) -> anyhow::Result<Vec<u8>>
+where
+    Response: Serialize,
+{
+    Ok(match config.jsonrpc {
+        Some(_) => jsonrpc::error_frame(e),
+        None => serde_json::to_vec(&RpcResponse::<Response> {
+            id: None,
+            value: Err(e),
+        })?,
+    })
+}
+
+/// Hands a request to the server and waits for its response. `None` when
+/// the server dropped it.
+pub(crate) async fn dispatch<Request, Response>(
+    rpctx: &mpsc::Sender<Message<Request, Response>>,
+    conn: Arc<Connection>,
+    req: Request,
+    span: Span,
+    metadata: Metadata,
+) -> Option<Result<Response, Error>> {
+    let (tx, rx) = oneshot::channel();
+
+    if let Err(e) = rpctx
+        .send(Message {
+            req,
+            resp: tx,
+            conn,
+            span,
+            metadata,
+        })
+        .await
+    {
+        log::warn!("Failed to send request: {}", e);
+        return None;
+    }
+
+    match rx.await {
+        Ok(v) => Some(v),
+        Err(e) => {
+            log::warn!("Failed to wait response: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn write_loop<W>(mut w: W, mut resp_rx: mpsc::Receiver<Vec<u8>>)
+where
+    W: Sink<Vec<u8>, Error = anyhow::Error> + Unpin,
+{
+    while let Some(data) = resp_rx.recv().await {
+        if let Err(e) = w.send(data).await {
+            log::warn!("Failed to send response: {:?}", e);
+            break;
+        }
+    }
+}
+
+/// Serves the requests of one accepted connection, whatever the transport.
+pub(crate) async fn serve<R, W, Request, Response>(
+    r: R,
+    w: W,
+    peer: Option<SocketAddr>,
+    transport: Transport,
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    R: Stream<Item = anyhow::Result<Frame>> + Unpin,
+    W: Sink<Vec<u8>, Error = anyhow::Error> + Unpin + Send + 'static,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    let received = transport.clone();
+    let mut r = r.inspect(move |frame| {
+        if let Ok(Frame::Data(data)) = frame {
+            received.received(data.len());
+        }
+    });
+    let mut w = w.with(move |data: Vec<u8>| {
+        transport.sent(data.len());
+        future::ok::<_, anyhow::Error>(data)
+    });
+
+    let name = peer.map_or_else(|| "unix socket".to_string(), |peer| peer.to_string());
+    let _guard = match config
+        .stats
+        .open_connection(peer.map(|peer| peer.ip()), &config.limits)
+    {
+        Ok(conn) => conn,
+        Err(limit) => {
+            let data = error_frame::<Response>(&config, Error::LimitExceeded(limit))?;
+            w.send(data).await?;
+            anyhow::bail!("Refused connection from {}: {}", name, limit);
+        }
+    };
+
+    // JSON-RPC clients don't know about the handshake.
+    if let (Some(hello), None) = (&config.handshake, &config.jsonrpc) {
+        let timeout = config.limits.handshake_timeout;
+        let first = match tokio::time::timeout(timeout, r.next()).await {
+            Ok(first) => first,
+            Err(_) => anyhow::bail!(
+                "Closed connection from {}: no handshake in {:?}",
+                name,
+                timeout
+            ),
+        };
+        let (data, refused) = match first {
+            Some(Ok(Frame::Data(data))) => handshake::accept(hello, &data),
+            Some(Ok(Frame::Oversized)) => {
+                let e = Error::LimitExceeded(Limit::FrameSize);
+                (Handshake::Refuse(e.clone()).encode(), Some(e))
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Ok(()),
+        };
+        w.send(data).await?;
+        if let Some(e) = refused {
+            anyhow::bail!("Refused connection from {}: {}", name, e);
+        }
+    }
+
+    let (resp_tx, resp_rx) = mpsc::channel(config.limits.max_pending_responses.max(1));
+    let writer = tokio::spawn(write_loop(w, resp_rx));
+    let overflow = Arc::new(Notify::new());
+
+    let mut session = Session {
+        rpctx,
+        conn: Connection::new(peer),
+        limits: config.limits.clone(),
+        guard: ProtocolGuard::new(&config),
+        in_flight: Arc::new(AtomicUsize::new(0)),
+        resp_tx,
+        overflow: overflow.clone(),
+    };
+
+    let result = loop {
+        let next = match future::select(r.next(), std::pin::pin!(overflow.notified())).await {
+            Either::Left((next, _)) => next,
+            Either::Right(_) => {
+                // The writer may be stuck on the peer, which isn't reading.
+                writer.abort();
+                break Err(Error::LimitExceeded(Limit::PendingResponses).into());
+            }
+        };
+        let data = match next {
+            Some(Ok(Frame::Data(data))) => data,
+            Some(Ok(Frame::Oversized)) => {
+                session.guard.on_error(ProtocolEvent::Oversized);
+                let e = Error::LimitExceeded(Limit::FrameSize);
+                if let Ok(data) = error_frame::<Response>(&config, e) {
+                    let _ = queue(&session.resp_tx, data);
+                }
+                break Err(anyhow::anyhow!("Closed connection: {}", Limit::FrameSize));
+            }
+            Some(Err(e)) => break Err(e),
+            None => break Ok(()),
+        };
+
+        let handled = match &config.jsonrpc {
+            Some(jsonrpc) => session.on_jsonrpc(jsonrpc, &data),
+            None => session.on_request(&data),
+        };
+        if let Err(e) = handled {
+            break Err(e);
+        }
+    };
+
+    drop(session);
+    let _ = writer.await;
+
+    result
+}
+
+/// The state shared by the requests of a connection.
+struct Session<Request, Response> {
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    conn: Arc<Connection>,
+    limits: Limits,
+    guard: ProtocolGuard,
+    in_flight: Arc<AtomicUsize>,
+    resp_tx: mpsc::Sender<Vec<u8>>,
+    overflow: Arc<Notify>,
+}
+
+impl<Request, Response> Session<Request, Response>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    /// Counts a request in flight, unless the connection already handles
+    /// as many as it may.
+    fn admit(&self) -> bool {
+        if matches!(self.limits.max_concurrent_requests, Some(max) if self.in_flight.load(Ordering::Acquire) >= max)
+        {
+            return false;
+        }
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    /// Records a frame that could not be decoded, failing when the
+    /// connection must be closed for it.
+    fn on_malformed(&mut self) -> anyhow::Result<()> {
+        if self.guard.on_error(ProtocolEvent::Malformed) {
+            anyhow::bail!("Closed connection: too many protocol errors");
+        }
+        Ok(())
+    }
+
+    /// Handles a frame of the mrpc protocol, a single request.
+    fn on_request(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let RpcRequest {
+            id,
+            value,
+            trace,
+            metadata,
+        } = match decode_request::<Request>(data) {
+            Ok(v) => v,
+            Err((id, e)) => {
+                log::warn!("{}", e);
+                let data = serde_json::to_vec(&RpcResponse::<Response> { id, value: Err(e) })?;
+                queue(&self.resp_tx, data)?;
+                return self.on_malformed();
+            }
+        };
+
+        if !self.admit() {
+            let e = Error::LimitExceeded(Limit::ConcurrentRequests);
+            let data = serde_json::to_vec(&RpcResponse::<Response> {
+                id: Some(id),
+                value: Err(e),
+            })?;
+            return queue(&self.resp_tx, data);
+        }
+
+        let (rpctx, resp_tx, overflow, in_flight, conn) = (
+            self.rpctx.clone(),
+            self.resp_tx.clone(),
+            self.overflow.clone(),
+            self.in_flight.clone(),
+            self.conn.clone(),
+        );
+        tokio::spawn(async move {
+            let span = Span::server(conn.peer());
+            span.record_request_id(id);
+            if let Some(trace) = &trace {
+                span.set_remote_parent(trace);
+            }
+            let value = dispatch(&rpctx, conn, value, span, metadata).await;
+
+            if let Some(value) = value {
+                match serde_json::to_vec(&RpcResponse {
+                    id: Some(id),
+                    value,
+                }) {
+                    Ok(data) => queue_response(&resp_tx, &overflow, data),
+                    Err(e) => log::warn!("{:?}", e),
+                }
+            }
+            // The request is in flight until its response is queued.
+            in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+        Ok(())
+    }
+
+    /// Handles a frame of JSON-RPC 2.0, a call or a batch of them. A batch
+    /// is answered at once when all of its calls completed, and its calls
+    /// beyond the concurrency limit fail.
+    fn on_jsonrpc(&mut self, jsonrpc: &JsonRpc, data: &[u8]) -> anyhow::Result<()> {
+        let Incoming { calls, batch } = jsonrpc.decode::<Request>(data);
+        let malformed = calls.iter().any(|call| call.value.is_err());
+        let calls = calls
+            .into_iter()
+            .map(|call| {
+                let admitted = call.value.is_ok() && self.admit();
+                (call, admitted)
+            })
+            .collect::<Vec<_>>();
+
+        let count = calls.iter().filter(|(_, admitted)| *admitted).count();
+        let (rpctx, resp_tx, overflow, in_flight, conn) = (
+            self.rpctx.clone(),
+            self.resp_tx.clone(),
+            self.overflow.clone(),
+            self.in_flight.clone(),
+            self.conn.clone(),
+        );
+        tokio::spawn(async move {
+            let responses =
+                future::join_all(calls.into_iter().map(|(Call { id, value }, admitted)| {
+                    let (rpctx, conn) = (&rpctx, conn.clone());
+                    async move {
+                        let response = match value {
+                            Ok(_) if !admitted => {
+                                let e = Error::LimitExceeded(Limit::ConcurrentRequests);
+                                jsonrpc::result_response(id?, Err(e.into()))
+                            }
+                            Ok((route, req)) => {
+                                let span = Span::server(conn.peer());
+                                if let Some(id) = &id {
+                                    span.record_request_id(id);
+                                }
+                                let value = dispatch(rpctx, conn, req, span, Metadata::new()).await;
+                                jsonrpc::response(id?, &route, value)
+                            }
+                            Err(e) => {
+                                log::warn!("{}", e.message());
+                                jsonrpc::result_response(id?, Err(e))
+                            }
+                        };
+                        Some(response)
+                    }
+                }))
+                .await;
+
+            if let Some(data) = jsonrpc::encode(responses.into_iter().flatten().collect(), batch) {
+                queue_response(&resp_tx, &overflow, data);
+            }
+            in_flight.fetch_sub(count, Ordering::AcqRel);
+        });
+
+        if malformed {
+            self.on_malformed()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+//! Length delimited frames over a byte stream, shared by the tcp and unix
+//! socket transports. JSON-RPC frames are delimited by newlines instead.
+
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use futures::{future, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+};
+use tokio_util::codec::{
+    Framed, LengthDelimitedCodec, LengthDelimitedCodecError, LinesCodec, LinesCodecError,
+};
+
+use crate::{metrics::Transport, Error, Message};
+
+use super::{
+    conn::{self, Frame},
+    handshake::{self, Handshake},
+    ClientConfig, RpcRequest, RpcResponse, ServerConfig,
+};
+
+/// Runs a client on a stream connected to `peer`.
+pub(crate) async fn connect<S, Request, Response>(
+    s: S,
+    peer: Option<SocketAddr>,
+    transport: Transport,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
+    Request: Serialize + Send + Unpin + 'static,
+{
+    let (tx, rx) = mpsc::channel(32);
+
+    let mut s = Framed::new(s, LengthDelimitedCodec::new());
+
+    if let Some(hello) = &config.handshake {
+        s.send(Bytes::from(Handshake::Hello(hello.clone()).encode()))
+            .await?;
+        let frame = match s.next().await {
+            Some(frame) => frame?,
+            None => anyhow::bail!("connection closed during the handshake"),
+        };
+        handshake::check_reply(hello, &frame)?;
+    }
+
+    tokio::spawn(run_loop(s, peer, transport, rx));
+
+    Ok(tx)
+}
+
+async fn run_loop<S, Request, Response>(
+    mut s: Framed<S, LengthDelimitedCodec>,
+    peer: Option<SocketAddr>,
+    transport: Transport,
+    mut rx: mpsc::Receiver<Message<Request, Response>>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
+    Request: Serialize + Send + Unpin + 'static,
+{
+    let mut id_generator: i64 = 0;
+    while let Some(msg) = rx.recv().await {
+        let Message::<Request, Response> {
+            req,
+            resp,
+            span,
+            metadata,
+            ..
+        } = msg;
+        span.record_request_id(id_generator);
+        span.record_peer(peer);
+
+        let data = match serde_json::to_vec(&RpcRequest {
+            id: id_generator,
+            value: req,
+            trace: span.trace_context(),
+            metadata,
+        }) {
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("{:?}", e);
+                continue;
+            }
+        };
+        id_generator += 1;
+
+        transport.sent(data.len());
+        if let Err(e) = s.send(Bytes::from(data)).await {
+            log::warn!("{:?}", e);
+            continue;
+        }
+
+        let value = match s.next().await {
+            Some(Ok(frame)) => {
+                transport.received(frame.len());
+                match serde_json::from_slice::<RpcResponse<Response>>(&frame) {
+                    Ok(RpcResponse { id: _, value }) => value,
+                    Err(e) => Err(Error::Protocol(e.to_string())),
+                }
+            }
+            Some(Err(e)) => Err(Error::Protocol(e.to_string())),
+            None => Err(Error::Protocol("connection closed".into())),
+        };
+
+        if resp.send(value).is_err() {
+            log::warn!("Failed to send response");
+        }
+    }
+}
+
+/// Serves an accepted stream.
+pub(crate) async fn serve<S, Request, Response>(
+    s: S,
+    peer: Option<SocketAddr>,
+    transport: Transport,
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    if config.jsonrpc.is_some() {
+        return serve_lines(s, peer, transport, rpctx, config).await;
+    }
+
+    let codec = LengthDelimitedCodec::builder()
+        .max_frame_length(config.limits.max_frame_size)
+        .new_codec();
+    let (w, r) = Framed::new(s, codec).split();
+
+    let r = r.map(|frame| match frame {
+        Ok(frame) => Ok(Frame::Data(frame.to_vec())),
+        Err(e)
+            if e.get_ref()
+                .is_some_and(|e| e.is::<LengthDelimitedCodecError>()) =>
+        {
+            Ok(Frame::Oversized)
+        }
+        Err(e) => Err(e.into()),
+    });
+    let w = w
+        .with(|data: Vec<u8>| future::ok::<_, std::io::Error>(Bytes::from(data)))
+        .sink_map_err(anyhow::Error::from);
+
+    conn::serve(r, Box::pin(w), peer, transport, rpctx, config).await
+}
+
+async fn serve_lines<S, Request, Response>(
+    s: S,
+    peer: Option<SocketAddr>,
+    transport: Transport,
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    let codec = LinesCodec::new_with_max_length(config.limits.max_frame_size);
+    let (w, r) = Framed::new(s, codec).split();
+
+    let r = r.map(|line| match line {
+        Ok(line) => Ok(Frame::Data(line.into_bytes())),
+        Err(LinesCodecError::MaxLineLengthExceeded) => Ok(Frame::Oversized),
+        Err(LinesCodecError::Io(e)) => Err(e.into()),
+    });
+    // serde_json escapes the newlines in strings.
+    let w = w
+        .sink_map_err(anyhow::Error::from)
+        .with(|data: Vec<u8>| future::ready(String::from_utf8(data).map_err(anyhow::Error::from)));
+
+    conn::serve(r, Box::pin(w), peer, transport, rpctx, config).await
+}
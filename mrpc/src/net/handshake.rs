@@ -0,0 +1,133 @@
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+
+use crate::{descriptor::Descriptor, Error};
+
+use super::RpcResponse;
+
+/// Version of the framing and envelopes, bumped on incompatible changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What a peer speaks, exchanged when a connection opens if
+/// [`ServerConfig::handshake`](super::ServerConfig) and
+/// [`ClientConfig::handshake`](super::ClientConfig) are set. The messages
+/// are always JSON, uncompressed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub protocol: u32,
+    /// [`Descriptor::fingerprint`] of the services. Peers which both send
+    /// one refuse to talk when they differ.
+    pub descriptor: Option<String>,
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            descriptor: None,
+        }
+    }
+}
+
+/// What the server agreed to from the [`Hello`] of the client.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Agreement {
+    pub protocol: u32,
+}
+
+impl Hello {
+    /// The default hello, checking that the peer has the same `descriptor`.
+    pub fn with_descriptor(descriptor: &impl Descriptor) -> Self {
+        Self {
+            descriptor: Some(descriptor.fingerprint()),
+            ..Default::default()
+        }
+    }
+
+    /// Checks that `peer` speaks the same protocol and services.
+    pub fn negotiate(&self, peer: &Hello) -> Result<Agreement, Error> {
+        if peer.protocol != self.protocol {
+            return Err(Error::Incompatible(format!(
+                "protocol version {} is not supported, expected {}",
+                peer.protocol, self.protocol
+            )));
+        }
+
+        if let (Some(ours), Some(theirs)) = (&self.descriptor, &peer.descriptor) {
+            if ours != theirs {
+                return Err(Error::Incompatible(format!(
+                    "service descriptors differ: {} instead of {}",
+                    theirs, ours
+                )));
+            }
+        }
+
+        Ok(Agreement {
+            protocol: self.protocol,
+        })
+    }
+
+    fn offers(&self, agreement: &Agreement) -> bool {
+        agreement.protocol == self.protocol
+    }
+}
+
+/// Frames of the handshake, sent before any request.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Handshake {
+    Hello(Hello),
+    Accept(Agreement),
+    Refuse(Error),
+}
+
+impl Handshake {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("serialize handshake")
+    }
+}
+
+/// Answers the first frame of a client. Returns the frame to send back
+/// and, when the connection is refused, why.
+pub(crate) fn accept(local: &Hello, data: &[u8]) -> (Vec<u8>, Option<Error>) {
+    match serde_json::from_slice::<Handshake>(data) {
+        Ok(Handshake::Hello(peer)) => match local.negotiate(&peer) {
+            Ok(agreement) => (Handshake::Accept(agreement).encode(), None),
+            Err(e) => (Handshake::Refuse(e.clone()).encode(), Some(e)),
+        },
+        // Likely a client without handshake, which only reads responses.
+        _ => {
+            let e = Error::Incompatible("the server expects a handshake".into());
+            let data = serde_json::to_vec(&RpcResponse::<()> {
+                id: None,
+                value: Err(e.clone()),
+            })
+            .expect("serialize response");
+            (data, Some(e))
+        }
+    }
+}
+
+/// Checks the answer of the server to `local`.
+pub(crate) fn check_reply(local: &Hello, data: &[u8]) -> Result<Agreement, Error> {
+    match serde_json::from_slice::<Handshake>(data) {
+        Ok(Handshake::Accept(agreement)) if local.offers(&agreement) => Ok(agreement),
+        Ok(Handshake::Accept(agreement)) => Err(Error::Incompatible(format!(
+            "server chose {:?}, which was not offered",
+            agreement
+        ))),
+        Ok(Handshake::Refuse(e)) => Err(e),
+        Ok(Handshake::Hello(_)) => Err(Error::Protocol("unexpected hello".into())),
+        // A server refusing the connection before the handshake, or one
+        // without handshake answering the hello as a malformed request.
+        Err(e) => match serde_json::from_slice::<RpcResponse<IgnoredAny>>(data) {
+            Ok(RpcResponse {
+                value: Err(Error::Protocol(_)),
+                ..
+            }) => Err(Error::Incompatible(
+                "the server does not expect a handshake".into(),
+            )),
+            Ok(RpcResponse { value: Err(e), .. }) => Err(e),
+            _ => Err(Error::Protocol(e.to_string())),
+        },
+    }
+}
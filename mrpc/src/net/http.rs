@@ -0,0 +1,324 @@
+//! An HTTP/1.1 gateway for the servers, for peers that only speak HTTP.
+//!
+//! A method is called by `POST /<service>/<method>` with its arguments by
+//! name in a JSON object, as in [`JsonRpc`](super::JsonRpc), and answered
+//! with its output as JSON. The method is the name or the wire name of the
+//! method in the service. Errors of the framework are answered with a
+//! status code and `{"error": <Error>, "message": <description>}`.
+//!
+//! The `traceparent` and `tracestate` headers of a request are its
+//! [`TraceContext`].
+//!
+//! The handshake and the JSON-RPC codec of the [`ServerConfig`] don't apply
+//! to HTTP and are ignored.
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use hyper::{
+    body::HttpBody,
+    header::{self, HeaderMap, HeaderValue},
+    server::conn::Http,
+    service::service_fn,
+    Body, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc,
+};
+
+use crate::{
+    descriptor::ServerDescriptor,
+    metrics::Transport,
+    trace::{Span, TraceContext},
+    Connection, Error, Limit, Message, Metadata,
+};
+
+use super::{
+    conn::dispatch,
+    route::{Route, Routes},
+    ProtocolEvent, ProtocolGuard, ServerConfig,
+};
+
+pub async fn reader<Addr, Request, Response>(
+    addr: Addr,
+    descriptor: &ServerDescriptor,
+    tx: mpsc::Sender<Message<Request, Response>>,
+) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_config(addr, descriptor, tx, ServerConfig::default()).await
+}
+
+/// Serves the server described by `descriptor` on `addr`, usually the
+/// `ServerRequest::descriptor()` of the server behind `tx`. Fails when its
+/// methods can't be called by name, as [`JsonRpc::new`](super::JsonRpc::new).
+pub async fn reader_with_config<Addr, Request, Response>(
+    addr: Addr,
+    descriptor: &ServerDescriptor,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    reader_with_listener(listener, descriptor, tx, config).await
+}
+
+/// Serves like [`reader_with_config`] the connections of `listener`, bound
+/// by the caller, e.g. to port 0 to read back the address.
+pub async fn reader_with_listener<Request, Response>(
+    listener: TcpListener,
+    descriptor: &ServerDescriptor,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    let routes = Arc::new(Routes::new(descriptor)?);
+
+    loop {
+        let (s, peer) = listener.accept().await?;
+
+        let tx = tx.clone();
+        let routes = routes.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(s, peer, tx, routes, config).await {
+                log::warn!("{:?}", e);
+            }
+        });
+    }
+}
+
+async fn serve<Request, Response>(
+    s: TcpStream,
+    peer: SocketAddr,
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    routes: Arc<Routes>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    // A refused connection still gets an answer to its first request.
+    let (_guard, refused) = match config
+        .stats
+        .open_connection(Some(peer.ip()), &config.limits)
+    {
+        Ok(guard) => (Some(guard), None),
+        Err(limit) => (None, Some(limit)),
+    };
+
+    let gateway = Arc::new(Gateway {
+        rpctx,
+        routes,
+        conn: Connection::new(Some(peer)),
+        max_body_size: config.limits.max_frame_size,
+        guard: Mutex::new(ProtocolGuard::new(&config)),
+        refused,
+        transport: Transport::new("http"),
+    });
+
+    let service = service_fn(move |req| {
+        let gateway = gateway.clone();
+        async move {
+            let resp = gateway.handle(req).await;
+            if let Some(len) = resp.body().size_hint().exact() {
+                gateway.transport.sent(len as usize);
+            }
+            Ok::<_, Infallible>(resp)
+        }
+    });
+    Http::new()
+        .http1_only(true)
+        .serve_connection(s, service)
+        .await?;
+
+    if let Some(limit) = refused {
+        anyhow::bail!("Refused connection from {}: {}", peer, limit);
+    }
+    Ok(())
+}
+
+/// The state shared by the requests of a connection.
+struct Gateway<Request, Response> {
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    routes: Arc<Routes>,
+    conn: Arc<Connection>,
+    max_body_size: usize,
+    guard: Mutex<ProtocolGuard>,
+    refused: Option<Limit>,
+    transport: Transport,
+}
+
+impl<Request, Response> Gateway<Request, Response>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    async fn handle(&self, req: HttpRequest<Body>) -> HttpResponse<Body> {
+        if let Some(limit) = self.refused {
+            return close(error_response(Error::LimitExceeded(limit)));
+        }
+
+        let route = match self.route(req.uri().path()) {
+            Some(route) => route.clone(),
+            None => {
+                let e = Error::Protocol(format!("no method at `{}`", req.uri().path()));
+                let mut resp = error_response(e);
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+                return resp;
+            }
+        };
+        if req.method() != Method::POST {
+            let e = Error::Protocol(format!("expected POST, got {}", req.method()));
+            let mut resp = error_response(e);
+            *resp.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            resp.headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static("POST"));
+            return resp;
+        }
+
+        let trace = trace_context(req.headers());
+        let body = match read_body(req.into_body(), self.max_body_size).await {
+            Ok(body) => {
+                self.transport.received(body.len());
+                body
+            }
+            Err(e) => {
+                self.on_error(ProtocolEvent::Oversized);
+                return close(error_response(e));
+            }
+        };
+
+        let args = if body.iter().all(u8::is_ascii_whitespace) {
+            Ok(Value::Null)
+        } else {
+            serde_json::from_slice(&body).map_err(|e| e.to_string())
+        };
+        let req = match args.and_then(|args| route.request::<Request>(args)) {
+            Ok(req) => req,
+            Err(e) => {
+                let resp = error_response(Error::Protocol(e));
+                return if self.on_error(ProtocolEvent::Malformed) {
+                    close(resp)
+                } else {
+                    resp
+                };
+            }
+        };
+
+        let span = Span::server(self.conn.peer());
+        if let Some(trace) = &trace {
+            span.set_remote_parent(trace);
+        }
+        match dispatch(&self.rpctx, self.conn.clone(), req, span, Metadata::new()).await {
+            Some(Ok(resp)) => output_response(&route, resp),
+            Some(Err(e)) => error_response(e),
+            None => internal_error("the request was dropped"),
+        }
+    }
+
+    fn route(&self, path: &str) -> Option<&Arc<Route>> {
+        let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+        self.routes.get(service, method)
+    }
+
+    /// Records a request that could not be decoded and returns whether the
+    /// connection must be closed for it.
+    fn on_error(&self, event: ProtocolEvent) -> bool {
+        self.guard.lock().unwrap().on_error(event)
+    }
+}
+
+fn trace_context(headers: &HeaderMap) -> Option<TraceContext> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    Some(TraceContext {
+        traceparent: header("traceparent")?.to_string(),
+        tracestate: header("tracestate").unwrap_or_default().to_string(),
+    })
+}
+
+/// Reads a body of at most `max_size` bytes.
+async fn read_body(mut body: Body, max_size: usize) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| Error::Protocol(e.to_string()))?;
+        if data.len() + chunk.len() > max_size {
+            return Err(Error::LimitExceeded(Limit::FrameSize));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// The status code answering an error of the framework.
+fn status(e: &Error) -> StatusCode {
+    match e {
+        Error::Protocol(_) | Error::Incompatible(_) => StatusCode::BAD_REQUEST,
+        Error::LimitExceeded(Limit::FrameSize) => StatusCode::PAYLOAD_TOO_LARGE,
+        Error::LimitExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+        Error::Overloaded(_) | Error::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        Error::Remote { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn json_response(value: Value) -> HttpResponse<Body> {
+    let mut resp = HttpResponse::new(Body::from(value.to_string()));
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    resp
+}
+
+fn output_response<Response>(route: &Route, resp: Response) -> HttpResponse<Body>
+where
+    Response: Serialize,
+{
+    match route.output(resp) {
+        Ok(output) => json_response(output),
+        Err(e) => {
+            log::warn!("Failed to encode response: {}", e);
+            internal_error("the response could not be encoded")
+        }
+    }
+}
+
+fn error_response(e: Error) -> HttpResponse<Body> {
+    let mut resp = json_response(json!({
+        "error": e,
+        "message": e.to_string(),
+    }));
+    *resp.status_mut() = status(&e);
+    resp
+}
+
+/// An error of the server outside of the framework.
+fn internal_error(message: &str) -> HttpResponse<Body> {
+    let mut resp = json_response(json!({ "message": message }));
+    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    resp
+}
+
+/// Asks the peer to close the connection after `resp`.
+fn close(mut resp: HttpResponse<Body>) -> HttpResponse<Body> {
+    resp.headers_mut()
+        .insert(header::CONNECTION, HeaderValue::from_static("close"));
+    resp
+}
@@ -0,0 +1,226 @@
+//! JSON-RPC 2.0 in place of the mrpc envelopes, so that any JSON-RPC client
+//! library can call a server. See [`ServerConfig::jsonrpc`](super::ServerConfig).
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{descriptor::ServerDescriptor, Error};
+
+use super::route::{Route, Routes};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+// Framework errors, in the range the specification leaves to servers.
+const LIMIT_EXCEEDED: i64 = -32000;
+const OVERLOADED: i64 = -32001;
+const SERVICE_UNAVAILABLE: i64 = -32002;
+const INCOMPATIBLE: i64 = -32003;
+
+/// The methods of a server by their JSON-RPC name, `Service.method`: the
+/// variant of the service in the server, then the name or the wire name
+/// of the method. `params` are the arguments by name or in order.
+///
+/// Errors of the framework are reported with the standard codes, or
+/// -32000 (limit exceeded), -32001 (overloaded), -32002 (service
+/// unavailable) and -32003 (incompatible), with the [`Error`] as `data`.
+/// A panicking method is an internal error, -32603.
+#[derive(Debug, Clone)]
+pub struct JsonRpc {
+    routes: Routes,
+}
+
+impl JsonRpc {
+    /// Fails when the messages of the server are untagged, since their
+    /// variants can't be called by name.
+    pub fn new(descriptor: &ServerDescriptor) -> anyhow::Result<Self> {
+        Ok(Self {
+            routes: Routes::new(descriptor)?,
+        })
+    }
+
+    /// Decodes a frame holding a call or a batch of them.
+    pub(crate) fn decode<Request>(&self, data: &[u8]) -> Incoming<Request>
+    where
+        for<'de> Request: Deserialize<'de>,
+    {
+        let invalid = |code, message: &str| Incoming {
+            calls: vec![Call {
+                id: Some(Value::Null),
+                value: Err(ErrorObject::new(code, message)),
+            }],
+            batch: false,
+        };
+
+        match serde_json::from_slice::<Value>(data) {
+            Ok(Value::Array(values)) if values.is_empty() => {
+                invalid(INVALID_REQUEST, "Invalid Request: empty batch")
+            }
+            Ok(Value::Array(values)) => Incoming {
+                calls: values.into_iter().map(|value| self.call(value)).collect(),
+                batch: true,
+            },
+            Ok(value) => Incoming {
+                calls: vec![self.call(value)],
+                batch: false,
+            },
+            Err(e) => invalid(PARSE_ERROR, &format!("Parse error: {}", e)),
+        }
+    }
+
+    fn call<Request>(&self, value: Value) -> Call<Request>
+    where
+        for<'de> Request: Deserialize<'de>,
+    {
+        let invalid = |id: Option<&Value>, message: &str| Call {
+            id: Some(id.filter(|id| valid_id(id)).cloned().unwrap_or(Value::Null)),
+            value: Err(ErrorObject::new(
+                INVALID_REQUEST,
+                &format!("Invalid Request: {}", message),
+            )),
+        };
+
+        let mut object = match value {
+            Value::Object(object) => object,
+            _ => return invalid(None, "expected an object"),
+        };
+        let id = object.remove("id");
+
+        if object.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            return invalid(id.as_ref(), "expected `\"jsonrpc\": \"2.0\"`");
+        }
+        if matches!(&id, Some(id) if !valid_id(id)) {
+            return invalid(None, "`id` must be a string, a number or null");
+        }
+        let method = match object.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => return invalid(id.as_ref(), "expected a `method` string"),
+        };
+
+        let route = method
+            .split_once('.')
+            .and_then(|(service, method)| self.routes.get(service, method));
+        let value = match route {
+            Some(route) => route
+                .request(object.remove("params").unwrap_or(Value::Null))
+                .map(|req| (route.clone(), req))
+                .map_err(|e| ErrorObject::new(INVALID_PARAMS, &format!("Invalid params: {}", e))),
+            None => Err(ErrorObject::new(
+                METHOD_NOT_FOUND,
+                &format!("Method not found: {}", method),
+            )),
+        };
+        Call { id, value }
+    }
+}
+
+fn valid_id(id: &Value) -> bool {
+    matches!(id, Value::Null | Value::Number(_) | Value::String(_))
+}
+
+/// The calls of a frame.
+pub(crate) struct Incoming<Request> {
+    pub(crate) calls: Vec<Call<Request>>,
+    /// Whether the frame was an array, answered by an array.
+    pub(crate) batch: bool,
+}
+
+pub(crate) struct Call<Request> {
+    /// `None` for a notification, which is not answered.
+    pub(crate) id: Option<Value>,
+    pub(crate) value: Result<(Arc<Route>, Request), ErrorObject>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ErrorObject {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl ErrorObject {
+    fn new(code: i64, message: &str) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<Error> for ErrorObject {
+    fn from(e: Error) -> Self {
+        let code = match &e {
+            Error::Protocol(_) => INVALID_REQUEST,
+            Error::LimitExceeded(_) => LIMIT_EXCEEDED,
+            Error::Overloaded(_) => OVERLOADED,
+            Error::ServiceUnavailable(_) => SERVICE_UNAVAILABLE,
+            Error::Incompatible(_) => INCOMPATIBLE,
+            Error::Remote { .. } => INTERNAL_ERROR,
+        };
+        Self {
+            code,
+            message: e.to_string(),
+            data: serde_json::to_value(&e).ok(),
+        }
+    }
+}
+
+/// The JSON-RPC response to the call `id` of `route`. `value` is `None`
+/// when the server dropped the call.
+pub(crate) fn response<Response>(
+    id: Value,
+    route: &Route,
+    value: Option<Result<Response, Error>>,
+) -> Value
+where
+    Response: Serialize,
+{
+    let result = match value {
+        Some(Ok(value)) => route
+            .output(value)
+            .map_err(|e| ErrorObject::new(INTERNAL_ERROR, &format!("Internal error: {}", e))),
+        Some(Err(e)) => Err(e.into()),
+        None => Err(ErrorObject::new(
+            INTERNAL_ERROR,
+            "Internal error: the call was dropped",
+        )),
+    };
+    result_response(id, result)
+}
+
+pub(crate) fn result_response(id: Value, result: Result<Value, ErrorObject>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": id }),
+    }
+}
+
+/// The frame answering the calls of a frame, `None` when they were all
+/// notifications.
+pub(crate) fn encode(mut responses: Vec<Value>, batch: bool) -> Option<Vec<u8>> {
+    let value = if batch {
+        if responses.is_empty() {
+            return None;
+        }
+        Value::Array(responses)
+    } else {
+        responses.pop()?
+    };
+    Some(serde_json::to_vec(&value).expect("serialize response"))
+}
+
+/// A frame reporting an error that isn't about a particular call.
+pub(crate) fn error_frame(e: Error) -> Vec<u8> {
+    serde_json::to_vec(&result_response(Value::Null, Err(e.into()))).expect("serialize response")
+}
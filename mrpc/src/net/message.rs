@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{trace::TraceContext, Error, Metadata};
+
+/// `trace` is the context of the call when it is traced, see
+/// [`trace`](crate::trace).
+#[derive(Serialize, Deserialize)]
+pub struct RpcRequest<Value> {
+    pub id: i64,
+    pub value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceContext>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+/// `id` is `None` when the server could not recover it from a malformed
+/// request.
+#[derive(Serialize, Deserialize)]
+pub struct RpcResponse<Value> {
+    pub id: Option<i64>,
+    pub value: Result<Value, Error>,
+}
+
+#[derive(Deserialize)]
+struct RpcId {
+    id: i64,
+}
+
+/// Decodes a request frame. On failure the request id is returned as well
+/// if the envelope itself was intact.
+pub(crate) fn decode_request<Request>(
+    data: &[u8],
+) -> Result<RpcRequest<Request>, (Option<i64>, Error)>
+where
+    for<'de> Request: Deserialize<'de>,
+{
+    serde_json::from_slice::<RpcRequest<Request>>(data).map_err(|e| {
+        let id = serde_json::from_slice::<RpcId>(data).ok().map(|v| v.id);
+        (id, Error::Protocol(e.to_string()))
+    })
+}
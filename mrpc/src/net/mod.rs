@@ -1,5 +1,88 @@
+// The helpers shared by the transports are unused when none is enabled,
+// and the framing ones when only the HTTP gateway is.
+#[cfg_attr(
+    not(any(
+        feature = "tcp",
+        feature = "websocket",
+        feature = "unix",
+        feature = "http",
+        feature = "sse"
+    )),
+    allow(dead_code)
+)]
+mod config;
+#[cfg(any(
+    feature = "tcp",
+    feature = "websocket",
+    feature = "unix",
+    feature = "http",
+    feature = "sse"
+))]
+#[cfg_attr(
+    not(any(
+        feature = "tcp",
+        feature = "websocket",
+        feature = "unix",
+        feature = "sse"
+    )),
+    allow(dead_code)
+)]
+mod conn;
+#[cfg(any(feature = "tcp", all(unix, feature = "unix")))]
+mod framed;
+#[cfg_attr(
+    not(any(feature = "tcp", feature = "websocket", feature = "unix")),
+    allow(dead_code)
+)]
+mod handshake;
+#[cfg_attr(
+    not(any(
+        feature = "tcp",
+        feature = "websocket",
+        feature = "unix",
+        feature = "sse"
+    )),
+    allow(dead_code)
+)]
+mod jsonrpc;
+#[cfg_attr(
+    not(any(
+        feature = "tcp",
+        feature = "websocket",
+        feature = "unix",
+        feature = "sse"
+    )),
+    allow(dead_code)
+)]
+mod message;
+#[cfg_attr(
+    not(any(
+        feature = "tcp",
+        feature = "websocket",
+        feature = "unix",
+        feature = "http",
+        feature = "sse"
+    )),
+    allow(dead_code)
+)]
+mod route;
+
+pub use config::*;
+pub use handshake::{Agreement, Hello, PROTOCOL_VERSION};
+pub use jsonrpc::JsonRpc;
+pub use message::*;
+
 #[cfg(feature = "tcp")]
 pub mod tcp;
 
+#[cfg(all(unix, feature = "unix"))]
+pub mod unix;
+
 #[cfg(any(feature = "websocket", feature = "websocket_web"))]
 pub mod websocket;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+#[cfg(any(feature = "sse", feature = "sse_web"))]
+pub mod sse;
@@ -0,0 +1,38 @@
+//! The methods of a server by name, for the transports whose peers call
+//! them with JSON arguments rather than serialized requests.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+
+pub(crate) use crate::descriptor::Route;
+use crate::descriptor::ServerDescriptor;
+
+/// The routes of a server, by the variant of the service in the server,
+/// then the name or the wire name of the method.
+#[derive(Debug, Clone)]
+pub(crate) struct Routes {
+    methods: HashMap<(String, String), Arc<Route>>,
+}
+
+impl Routes {
+    /// Fails when the methods of `descriptor` can't be called by name, see
+    /// [`Route::new`].
+    pub(crate) fn new(descriptor: &ServerDescriptor) -> anyhow::Result<Self> {
+        let mut methods = HashMap::new();
+        for entry in &descriptor.services {
+            for method in &entry.service.methods {
+                let route = Route::new(descriptor, entry, method).map_err(|e| anyhow!(e))?;
+                let route = Arc::new(route);
+                // The wire names are unique, so they win over the names.
+                methods.insert((entry.name.clone(), method.name.clone()), route.clone());
+                methods.insert((entry.name.clone(), method.wire_name.clone()), route);
+            }
+        }
+        Ok(Self { methods })
+    }
+
+    pub(crate) fn get(&self, service: &str, method: &str) -> Option<&Arc<Route>> {
+        self.methods.get(&(service.to_string(), method.to_string()))
+    }
+}
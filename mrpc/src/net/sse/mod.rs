@@ -0,0 +1,24 @@
+//! A fallback for the browsers behind proxies that break WebSockets: the
+//! requests are `POST`ed and the responses come back as Server-Sent Events.
+//!
+//! `GET` on the address opens a session, a `text/event-stream` whose first
+//! event, named `session`, carries the id of the session. Each frame the
+//! server sends in the session follows as a `message` event. The requests
+//! are `POST`ed to the address with `?session=<id>`, one frame per body, and
+//! answered `202 Accepted` once queued. The frames are those of the
+//! WebSocket transport, so a session is served like a connection of the
+//! other transports, starting with the handshake when
+//! [`ServerConfig::handshake`](super::ServerConfig) is set, and ends with
+//! its event stream.
+
+#[cfg(all(feature = "sse_web", target_arch = "wasm32"))]
+mod sse_web;
+
+#[cfg(all(feature = "sse_web", target_arch = "wasm32"))]
+pub use sse_web::*;
+
+#[cfg(all(feature = "sse", not(target_arch = "wasm32")))]
+mod server;
+
+#[cfg(all(feature = "sse", not(target_arch = "wasm32")))]
+pub use server::*;
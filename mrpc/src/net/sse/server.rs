@@ -0,0 +1,301 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    convert::Infallible,
+    hash::BuildHasher,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use futures::{
+    future::{self, Either},
+    sink, stream, StreamExt,
+};
+use hyper::{
+    body::HttpBody,
+    header::{self, HeaderValue},
+    server::conn::Http,
+    service::service_fn,
+    Body, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use crate::{
+    metrics::Transport,
+    net::{
+        conn::{self, Frame},
+        ServerConfig,
+    },
+    Message,
+};
+
+/// How many requests of a session may wait for the server before the
+/// `POST`s wait as well.
+const QUEUE_SIZE: usize = 32;
+
+/// How often an idle event stream gets a comment, so that proxies don't
+/// time it out.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+pub async fn reader<Addr, Request, Response>(
+    addr: Addr,
+    tx: mpsc::Sender<Message<Request, Response>>,
+) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_config(addr, tx, ServerConfig::default()).await
+}
+
+pub async fn reader_with_config<Addr, Request, Response>(
+    addr: Addr,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_listener(TcpListener::bind(addr).await?, tx, config).await
+}
+
+/// Serves like [`reader_with_config`] the connections of `listener`, bound
+/// by the caller, e.g. to port 0 to read back the address.
+pub async fn reader_with_listener<Request, Response>(
+    listener: TcpListener,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    let sessions = Arc::new(Sessions {
+        rpctx: tx,
+        config,
+        senders: Mutex::new(HashMap::new()),
+        keys: RandomState::new(),
+        next: AtomicU64::new(0),
+    });
+
+    loop {
+        let (s, peer) = listener.accept().await?;
+
+        let sessions = sessions.clone();
+        tokio::spawn(async move {
+            if let Err(e) = on_accept(s, peer, sessions).await {
+                log::warn!("{:?}", e);
+            }
+        });
+    }
+}
+
+async fn on_accept<Request, Response>(
+    s: TcpStream,
+    peer: SocketAddr,
+    sessions: Arc<Sessions<Request, Response>>,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    let service = service_fn(move |req| {
+        let sessions = sessions.clone();
+        async move { Ok::<_, Infallible>(sessions.handle(req, peer).await) }
+    });
+    Http::new()
+        .http1_only(true)
+        .serve_connection(s, service)
+        .await?;
+    Ok(())
+}
+
+/// The open sessions, by id. The ids are unguessable so that a peer can't
+/// send requests in the session of another.
+struct Sessions<Request, Response> {
+    rpctx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+    senders: Mutex<HashMap<u64, mpsc::Sender<Frame>>>,
+    keys: RandomState,
+    next: AtomicU64,
+}
+
+impl<Request, Response> Sessions<Request, Response>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    async fn handle(
+        self: Arc<Self>,
+        req: HttpRequest<Body>,
+        peer: SocketAddr,
+    ) -> HttpResponse<Body> {
+        let mut resp = match *req.method() {
+            Method::GET => self.open(peer),
+            Method::POST => self.post(req).await,
+            _ => {
+                let mut resp = status(StatusCode::METHOD_NOT_ALLOWED);
+                resp.headers_mut()
+                    .insert(header::ALLOW, HeaderValue::from_static("GET, POST"));
+                resp
+            }
+        };
+        // The pages using the transport are usually served from elsewhere.
+        resp.headers_mut().insert(
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            HeaderValue::from_static("*"),
+        );
+        resp
+    }
+
+    /// Opens a session served like a connection of the other transports,
+    /// until the peer drops its event stream.
+    fn open(self: Arc<Self>, peer: SocketAddr) -> HttpResponse<Body> {
+        let id = self
+            .keys
+            .hash_one(self.next.fetch_add(1, Ordering::Relaxed));
+        let (frame_tx, frame_rx) = mpsc::channel(QUEUE_SIZE);
+        let (event_tx, event_rx) = mpsc::channel::<Vec<u8>>(1);
+        self.senders.lock().unwrap().insert(id, frame_tx);
+
+        // The frames end with the event stream, which hyper drops when the
+        // peer goes away.
+        let closed = event_tx.clone();
+        let r = stream::unfold((frame_rx, closed), |(mut rx, closed)| async move {
+            let frame = match future::select(Box::pin(rx.recv()), Box::pin(closed.closed())).await {
+                Either::Left((frame, _)) => frame,
+                Either::Right(_) => None,
+            };
+            Some((Ok::<_, anyhow::Error>(frame?), (rx, closed)))
+        });
+        let w = sink::unfold(event_tx, |tx, data: Vec<u8>| async move {
+            tx.send(data)
+                .await
+                .map_err(|_| anyhow::anyhow!("Event stream closed"))?;
+            Ok::<_, anyhow::Error>(tx)
+        });
+
+        let sessions = self.clone();
+        tokio::spawn(async move {
+            let (rpctx, config) = (sessions.rpctx.clone(), sessions.config.clone());
+            if let Err(e) = conn::serve(
+                Box::pin(r),
+                Box::pin(w),
+                Some(peer),
+                Transport::new("sse"),
+                rpctx,
+                config,
+            )
+            .await
+            {
+                log::warn!("{:?}", e);
+            }
+            sessions.senders.lock().unwrap().remove(&id);
+        });
+
+        let keep_alive = time::interval_at(Instant::now() + KEEP_ALIVE, KEEP_ALIVE);
+        let events = stream::unfold(
+            (event_rx, keep_alive),
+            |(mut rx, mut keep_alive)| async move {
+                let event =
+                    match future::select(Box::pin(rx.recv()), Box::pin(keep_alive.tick())).await {
+                        Either::Left((data, _)) => event(None, &data?),
+                        Either::Right(_) => b": keep-alive\n\n".to_vec(),
+                    };
+                Some((event, (rx, keep_alive)))
+            },
+        );
+        let events = stream::once(future::ready(event(
+            Some("session"),
+            id.to_string().as_bytes(),
+        )))
+        .chain(events)
+        .map(Ok::<_, Infallible>);
+
+        let mut resp = HttpResponse::new(Body::wrap_stream(events));
+        let headers = resp.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        resp
+    }
+
+    /// Queues the request in the body for the session of the query.
+    async fn post(&self, req: HttpRequest<Body>) -> HttpResponse<Body> {
+        let id = req.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("session="))
+                .and_then(|id| id.parse::<u64>().ok())
+        });
+        let frame_tx = match id.and_then(|id| self.senders.lock().unwrap().get(&id).cloned()) {
+            Some(frame_tx) => frame_tx,
+            None => return status(StatusCode::NOT_FOUND),
+        };
+
+        let max_size = self.config.limits.max_frame_size;
+        let (frame, code) = match read_body(req.into_body(), max_size).await {
+            Ok(Some(data)) => (Frame::Data(data), StatusCode::ACCEPTED),
+            // The session answers and ends like the other transports.
+            Ok(None) => (Frame::Oversized, StatusCode::PAYLOAD_TOO_LARGE),
+            Err(e) => {
+                log::warn!("Failed to read request: {}", e);
+                return status(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        match frame_tx.send(frame).await {
+            Ok(()) => status(code),
+            Err(_) => status(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+/// Reads a body, or `None` when it is larger than `max_size` bytes.
+async fn read_body(mut body: Body, max_size: usize) -> hyper::Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if data.len() + chunk.len() > max_size {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(data))
+}
+
+/// An event of the stream, with one `data` line per line of `data`.
+fn event(name: Option<&str>, data: &[u8]) -> Vec<u8> {
+    let mut event = Vec::with_capacity(data.len() + 16);
+    if let Some(name) = name {
+        event.extend_from_slice(b"event: ");
+        event.extend_from_slice(name.as_bytes());
+        event.push(b'\n');
+    }
+    for line in data.split(|b| *b == b'\n') {
+        event.extend_from_slice(b"data: ");
+        event.extend_from_slice(line);
+        event.push(b'\n');
+    }
+    event.push(b'\n');
+    event
+}
+
+fn status(code: StatusCode) -> HttpResponse<Body> {
+    let mut resp = HttpResponse::new(Body::empty());
+    *resp.status_mut() = code;
+    resp
+}
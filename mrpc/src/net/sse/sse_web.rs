@@ -0,0 +1,259 @@
+use futures::{channel::mpsc as events, StreamExt};
+use send_wrapper::SendWrapper;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Event, EventSource, MessageEvent, RequestInit};
+
+use crate::{
+    net::{
+        handshake::{self, Handshake},
+        ClientConfig, RpcRequest, RpcResponse,
+    },
+    spawn_local,
+    sync::{mpsc, oneshot, Mutex},
+    Error, Message,
+};
+
+#[derive(Debug)]
+enum SseEvent {
+    Session(String),
+    Message(String),
+    Error,
+}
+
+type IdMap<Response> = Arc<Mutex<HashMap<i64, oneshot::Sender<Result<Response, Error>>>>>;
+
+/// Opens a session on the [`reader`](super) at `addr`, an `http(s)` URL.
+pub async fn connect<Request, Response, Addr>(
+    addr: Addr,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    Addr: ToString,
+    for<'de> Response: Deserialize<'de> + Send + 'static,
+    Request: Serialize + Send + 'static,
+{
+    connect_with_config(addr, ClientConfig::default()).await
+}
+
+/// Opens a session like [`connect`], whose first frame is the hello of
+/// `config` when it has one.
+pub async fn connect_with_config<Request, Response, Addr>(
+    addr: Addr,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    Addr: ToString,
+    for<'de> Response: Deserialize<'de> + Send + 'static,
+    Request: Serialize + Send + 'static,
+{
+    let addr = addr.to_string();
+    let (tx, rpc_request_source) = mpsc::channel(32);
+
+    let source = match EventSource::new(&addr) {
+        Ok(source) => SendWrapper::new(source),
+        Err(e) => {
+            anyhow::bail!("{:?}", e);
+        }
+    };
+
+    let (ev_tx, mut evs) = events::unbounded();
+
+    let on_session = {
+        let ev_tx = ev_tx.clone();
+        Closure::wrap(Box::new(move |e: MessageEvent| {
+            if let Some(id) = e.data().as_string() {
+                let _ = ev_tx.unbounded_send(SseEvent::Session(id));
+            }
+        }) as Box<dyn FnMut(MessageEvent)>)
+    };
+    if let Err(e) =
+        source.add_event_listener_with_callback("session", on_session.as_ref().unchecked_ref())
+    {
+        anyhow::bail!("{:?}", e);
+    }
+    on_session.forget();
+
+    let on_message = {
+        let ev_tx = ev_tx.clone();
+        Closure::wrap(Box::new(move |e: MessageEvent| match e.data().as_string() {
+            Some(data) => {
+                let _ = ev_tx.unbounded_send(SseEvent::Message(data));
+            }
+            None => log::warn!("Unsupported message data format: {:?}", e.data()),
+        }) as Box<dyn FnMut(MessageEvent)>)
+    };
+    source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+
+    let on_error = Closure::wrap(Box::new(move |_: Event| {
+        let _ = ev_tx.unbounded_send(SseEvent::Error);
+    }) as Box<dyn FnMut(Event)>);
+    source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    on_error.forget();
+
+    let session = match evs.next().await {
+        Some(SseEvent::Session(id)) => id,
+        Some(SseEvent::Message(_)) => {
+            source.close();
+            anyhow::bail!("Failed to open event stream: message event should not have happened");
+        }
+        Some(SseEvent::Error) => {
+            source.close();
+            anyhow::bail!("Failed to open event stream {}", addr);
+        }
+        None => anyhow::bail!("Failed to recv event"),
+    };
+
+    let separator = if addr.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}session={}", addr, separator, session);
+
+    if let Some(hello) = &config.handshake {
+        let data = String::from_utf8(Handshake::Hello(hello.clone()).encode())?;
+        let reply = match post(&url, &data).await {
+            Ok(()) => match evs.next().await {
+                Some(SseEvent::Message(data)) => {
+                    handshake::check_reply(hello, data.as_bytes()).map_err(Into::into)
+                }
+                _ => Err(anyhow::anyhow!("event stream closed during the handshake")),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = reply {
+            source.close();
+            return Err(e);
+        }
+    }
+
+    let id_map = Arc::new(Mutex::new(HashMap::new()));
+    spawn_local(accept_event_loop(evs, source, id_map.clone()));
+    spawn_local(accept_rpc_request_loop(rpc_request_source, url, id_map));
+    Ok(tx)
+}
+
+async fn accept_event_loop<Response>(
+    mut evs: events::UnboundedReceiver<SseEvent>,
+    source: SendWrapper<EventSource>,
+    id_map: IdMap<Response>,
+) where
+    for<'de> Response: Deserialize<'de> + Send + 'static,
+{
+    while let Some(ev) = evs.next().await {
+        match ev {
+            SseEvent::Session(_) => {
+                log::warn!("session event should not have happened");
+            }
+            SseEvent::Message(data) => {
+                let (id, value) = match serde_json::from_str::<RpcResponse<Response>>(&data) {
+                    Ok(RpcResponse {
+                        id: Some(id),
+                        value,
+                    }) => (id, value),
+                    Ok(RpcResponse { id: None, value }) => {
+                        log::warn!("Response without id: {:?}", value.err());
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("{:?}", e);
+                        continue;
+                    }
+                };
+
+                match id_map.lock().await.remove(&id) {
+                    Some(rpc_response_tx) => {
+                        if rpc_response_tx.send(value).is_err() {
+                            log::warn!("Failed to send rpc response");
+                        }
+                    }
+                    None => {
+                        log::warn!("message {} is removed", id);
+                    }
+                }
+            }
+            SseEvent::Error => {
+                // A reconnected stream would open another session.
+                log::warn!("Event stream failed");
+                source.close();
+                break;
+            }
+        }
+    }
+    id_map.lock().await.clear();
+}
+
+async fn accept_rpc_request_loop<Request, Response>(
+    mut rpc_request_source: mpsc::Receiver<Message<Request, Response>>,
+    url: String,
+    id_map: IdMap<Response>,
+) where
+    for<'de> Response: Deserialize<'de> + Send + 'static,
+    Request: Serialize + Send + 'static,
+{
+    let mut id_generator: i64 = 0;
+    while let Some(message) = rpc_request_source.recv().await {
+        let Message::<Request, Response> {
+            req,
+            resp,
+            span,
+            metadata,
+            ..
+        } = message;
+
+        let id = id_generator;
+        span.record_request_id(id);
+        let data = match serde_json::to_string(&RpcRequest {
+            id,
+            value: req,
+            trace: span.trace_context(),
+            metadata,
+        }) {
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("{:?}", e);
+                continue;
+            }
+        };
+        id_generator += 1;
+
+        id_map.lock().await.insert(id, resp);
+
+        // The requests are posted concurrently, their responses come back
+        // in the event stream in any order.
+        let url = url.clone();
+        let id_map = id_map.clone();
+        spawn_local(async move {
+            if let Err(e) = post(&url, &data).await {
+                log::warn!("{:?}", e);
+                id_map.lock().await.remove(&id);
+            }
+        });
+    }
+}
+
+async fn post(url: &str, data: &str) -> anyhow::Result<()> {
+    let window = match web_sys::window() {
+        Some(window) => window,
+        None => anyhow::bail!("Failed to post request: no window"),
+    };
+
+    // A text body keeps the request simple, without a CORS preflight.
+    let mut init = RequestInit::new();
+    init.method("POST").body(Some(&JsValue::from_str(data)));
+
+    let resp = match JsFuture::from(window.fetch_with_str_and_init(url, &init)).await {
+        Ok(resp) => resp,
+        Err(e) => anyhow::bail!("Failed to post request: {:?}", e),
+    };
+    let resp: web_sys::Response = match resp.dyn_into() {
+        Ok(resp) => resp,
+        Err(e) => anyhow::bail!("Failed to post request: {:?}", e),
+    };
+    if !resp.ok() {
+        anyhow::bail!("Failed to post request: status {}", resp.status());
+    }
+    Ok(())
+}
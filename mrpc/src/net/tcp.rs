@@ -1,13 +1,12 @@
-use futures::{SinkExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::{
     net::{TcpListener, TcpStream, ToSocketAddrs},
-    sync::{mpsc, oneshot},
+    sync::mpsc,
 };
-use tokio_serde::{formats::Json, Framed};
-use tokio_util::codec::LengthDelimitedCodec;
 
-use crate::Message;
+use crate::{metrics::Transport, Message};
+
+use super::{framed, ClientConfig, ServerConfig};
 
 pub async fn writer<Request, Response, Addr>(
     addr: Addr,
@@ -17,45 +16,21 @@ where
     for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
     Request: Serialize + Send + Unpin + 'static,
 {
-    let (tx, rx) = mpsc::channel(32);
-
-    let s = TcpStream::connect(addr).await?;
-    let s = tokio_util::codec::Framed::new(s, LengthDelimitedCodec::new());
-    let s = Framed::new(s, Json::<Response, Request>::default());
-
-    tokio::spawn(run_loop(s, rx));
-
-    Ok(tx)
+    writer_with_config(addr, ClientConfig::default()).await
 }
 
-async fn run_loop<Request, Response>(
-    mut s: Framed<
-        tokio_util::codec::Framed<TcpStream, LengthDelimitedCodec>,
-        Response,
-        Request,
-        Json<Response, Request>,
-    >,
-    mut rx: mpsc::Receiver<Message<Request, Response>>,
-) where
+pub async fn writer_with_config<Request, Response, Addr>(
+    addr: Addr,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    Addr: ToSocketAddrs,
     for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
     Request: Serialize + Send + Unpin + 'static,
 {
-    while let Some(msg) = rx.recv().await {
-        let Message::<Request, Response> { req, resp } = msg;
-
-        if let Err(e) = s.send(req).await {
-            log::warn!("{:?}", e);
-            continue;
-        }
-
-        if let Ok(response) = s.try_next().await {
-            if let Some(response) = response {
-                if let Err(_) = resp.send(response) {
-                    log::warn!("Failed to send response");
-                }
-            }
-        }
-    }
+    let s = TcpStream::connect(addr).await?;
+    let peer = s.peer_addr().ok();
+    framed::connect(s, peer, Transport::new("tcp"), config).await
 }
 
 pub async fn reader<Addr, Request, Response>(
@@ -64,40 +39,44 @@ pub async fn reader<Addr, Request, Response>(
 ) -> anyhow::Result<()>
 where
     Addr: ToSocketAddrs,
-    for<'de> Request: Deserialize<'de> + Unpin + Send + 'static,
-    Response: Serialize + Unpin + Send + 'static,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
 {
-    let listener = TcpListener::bind(addr).await?;
-
-    loop {
-        let (s, _) = listener.accept().await?;
+    reader_with_config(addr, tx, ServerConfig::default()).await
+}
 
-        let s = tokio_util::codec::Framed::new(s, LengthDelimitedCodec::new());
+pub async fn reader_with_config<Addr, Request, Response>(
+    addr: Addr,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_listener(TcpListener::bind(addr).await?, tx, config).await
+}
 
-        let mut s = Framed::new(s, Json::<Request, Response>::default());
+/// Serves like [`reader_with_config`] the connections of `listener`, bound
+/// by the caller, e.g. to port 0 to read back the address.
+pub async fn reader_with_listener<Request, Response>(
+    listener: TcpListener,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    loop {
+        let (s, peer) = listener.accept().await?;
 
-        let msgtx = tx.clone();
+        let tx = tx.clone();
+        let config = config.clone();
         tokio::spawn(async move {
-            while let Some(req) = s.try_next().await.unwrap() {
-                let (tx, rx) = oneshot::channel();
-
-                if let Err(e) = msgtx
-                    .send(Message::<Request, Response> { req, resp: tx })
-                    .await
-                {
-                    log::warn!("{}", e);
-                }
-
-                match rx.await {
-                    Ok(resp) => {
-                        if let Err(e) = s.send(resp).await {
-                            log::warn!("{:?}", e);
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("{:?}", e);
-                    }
-                }
+            if let Err(e) = framed::serve(s, Some(peer), Transport::new("tcp"), tx, config).await {
+                log::warn!("{:?}", e);
             }
         });
     }
@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+
+use crate::{metrics::Transport, Message};
+
+use super::{framed, ClientConfig, ServerConfig};
+
+pub async fn writer<Request, Response, P>(
+    path: P,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    P: AsRef<Path>,
+    for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
+    Request: Serialize + Send + Unpin + 'static,
+{
+    writer_with_config(path, ClientConfig::default()).await
+}
+
+pub async fn writer_with_config<Request, Response, P>(
+    path: P,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    P: AsRef<Path>,
+    for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
+    Request: Serialize + Send + Unpin + 'static,
+{
+    let s = UnixStream::connect(path).await?;
+    framed::connect(s, None, Transport::new("unix"), config).await
+}
+
+pub async fn reader<P, Request, Response>(
+    path: P,
+    tx: mpsc::Sender<Message<Request, Response>>,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_config(path, tx, ServerConfig::default()).await
+}
+
+/// Serves the socket at `path`, which must not exist yet. The peers have
+/// no address, so [`Limits::max_connections_per_ip`](super::Limits) does
+/// not apply to them.
+pub async fn reader_with_config<P, Request, Response>(
+    path: P,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_listener(UnixListener::bind(path)?, tx, config).await
+}
+
+/// Serves like [`reader_with_config`] the connections of `listener`, bound
+/// by the caller, e.g. to accept them as soon as it returns.
+pub async fn reader_with_listener<Request, Response>(
+    listener: UnixListener,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    loop {
+        let (s, _) = listener.accept().await?;
+
+        let tx = tx.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = framed::serve(s, None, Transport::new("unix"), tx, config).await {
+                log::warn!("{:?}", e);
+            }
+        });
+    }
+}
@@ -1,5 +1,3 @@
-mod message;
-
 #[cfg(all(feature = "websocket_web", target_arch = "wasm32"))]
 mod ws_web;
 
@@ -11,6 +9,3 @@ mod ws;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub use ws::*;
-
-
-
@@ -1,20 +1,42 @@
+use std::net::SocketAddr;
+
 use futures::{future, SinkExt, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::{
     net::{TcpListener, TcpStream, ToSocketAddrs},
-    sync::{mpsc, oneshot},
+    sync::mpsc,
 };
 use tokio_tungstenite::{
-    connect_async, tungstenite::Message as WsMessage, MaybeTlsStream, WebSocketStream,
+    connect_async,
+    tungstenite::{protocol::WebSocketConfig, Error as WsError, Message as WsMessage},
+    MaybeTlsStream, WebSocketStream,
 };
 
-use crate::Message;
-
-use super::message::WsRpcMessage;
+use crate::{
+    metrics::Transport,
+    net::{
+        conn::{self, Frame},
+        handshake::{self, Handshake},
+        ClientConfig, RpcRequest, RpcResponse, ServerConfig,
+    },
+    Error, Message,
+};
 
 pub async fn writer<Request, Response, R>(
     r: R,
 ) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    R: ToString,
+    for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
+    Request: Serialize + Send + Unpin + 'static,
+{
+    writer_with_config(r, ClientConfig::default()).await
+}
+
+pub async fn writer_with_config<Request, Response, R>(
+    r: R,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
 where
     R: ToString,
     for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
@@ -22,9 +44,28 @@ where
 {
     let (tx, rx) = mpsc::channel(32);
 
-    let (s, _) = connect_async(r.to_string()).await?;
+    let (mut s, _) = connect_async(r.to_string()).await?;
+
+    if let Some(hello) = &config.handshake {
+        s.send(WsMessage::Binary(Handshake::Hello(hello.clone()).encode()))
+            .await?;
+        let message = s
+            .by_ref()
+            .try_filter(|msg| future::ready(msg.is_binary() || msg.is_text()))
+            .next()
+            .await;
+        let data = match message {
+            Some(message) => message?.into_data(),
+            None => anyhow::bail!("connection closed during the handshake"),
+        };
+        handshake::check_reply(hello, &data)?;
+    }
 
-    tokio::spawn(run_loop(s, rx));
+    let peer = match s.get_ref() {
+        MaybeTlsStream::Plain(s) => s.peer_addr().ok(),
+        _ => None,
+    };
+    tokio::spawn(run_loop(s, peer, rx));
 
     Ok(tx)
 }
@@ -33,21 +74,33 @@ type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
 async fn run_loop<Request, Response>(
     s: WsStream,
+    peer: Option<SocketAddr>,
     mut rpc_rx: mpsc::Receiver<Message<Request, Response>>,
 ) where
     for<'de> Response: Deserialize<'de> + Send + Unpin + 'static,
     Request: Serialize + Send + Unpin + 'static,
 {
     let (mut w, r) = s.split();
+    let transport = Transport::new("websocket");
 
     let mut id_generator: i64 = 0;
     let mut ws_rx = r.try_filter(|msg| future::ready(msg.is_binary() || msg.is_text()));
     while let Some(msg) = rpc_rx.recv().await {
-        let Message::<Request, Response> { req, resp } = msg;
-
-        let data = match serde_json::to_vec(&WsRpcMessage {
+        let Message::<Request, Response> {
+            req,
+            resp,
+            span,
+            metadata,
+            ..
+        } = msg;
+        span.record_request_id(id_generator);
+        span.record_peer(peer);
+
+        let data = match serde_json::to_vec(&RpcRequest {
             id: id_generator,
             value: req,
+            trace: span.trace_context(),
+            metadata,
         }) {
             Ok(data) => data,
             Err(e) => {
@@ -55,115 +108,82 @@ async fn run_loop<Request, Response>(
                 continue;
             }
         };
+        id_generator += 1;
 
+        transport.sent(data.len());
         if let Err(e) = w.send(WsMessage::Binary(data)).await {
             log::warn!("Fail to send from websocket: {:?}", e);
             continue;
         }
 
-        let response = match ws_rx.next().await {
-            Some(v) => v,
-            None => {
-                log::warn!("Failed to recv from webscoket");
-                continue;
-            }
-        };
-
-        let response = match response {
-            Ok(v) => v,
-            Err(e) => {
-                log::warn!("{:?}", e);
-                continue;
+        let value = match ws_rx.next().await {
+            Some(Ok(response)) => {
+                let data = response.into_data();
+                transport.received(data.len());
+                match serde_json::from_slice::<RpcResponse<Response>>(&data) {
+                    Ok(RpcResponse { id: _, value }) => value,
+                    Err(e) => Err(Error::Protocol(e.to_string())),
+                }
             }
+            Some(Err(e)) => Err(Error::Protocol(e.to_string())),
+            None => Err(Error::Protocol("websocket stream exited".into())),
         };
 
-        let WsRpcMessage { id: _, value } =
-            match serde_json::from_slice::<WsRpcMessage<Response>>(&response.into_data()) {
-                Ok(data) => data,
-                Err(e) => {
-                    log::warn!("{:?}", e);
-                    continue;
-                }
-            };
-
-        if let Err(_) = resp.send(value) {
+        if resp.send(value).is_err() {
             log::warn!("Failed to send response");
         }
-
-        id_generator += 1;
     }
 }
 
 async fn on_accept<Request, Response>(
     s: TcpStream,
+    peer: SocketAddr,
     rpctx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
 ) -> anyhow::Result<()>
 where
     for<'de> Request: Deserialize<'de> + Send + 'static,
     Response: Serialize + Send + 'static,
 {
-    let ws = tokio_tungstenite::accept_async(s).await?;
-
-    let (mut w, r) = ws.split();
-
-    let mut ws_rx = r.try_filter(|msg| future::ready(msg.is_binary() || msg.is_text()));
-
-    while let Some(message) = ws_rx.next().await {
-        let message = match message {
-            Ok(v) => v,
-            Err(e) => {
-                log::warn!("Fail to recv from websocket: {:?}", e);
-                continue;
-            }
-        };
-
-        let (tx, rx) = oneshot::channel();
-
-        let WsRpcMessage { id, value } =
-            match serde_json::from_slice::<WsRpcMessage<Request>>(&message.into_data()) {
-                Ok(data) => data,
-                Err(e) => {
-                    log::warn!("{:?}", e);
-                    continue;
-                }
-            };
-
-        if let Err(e) = rpctx
-            .send(Message {
-                req: value,
-                resp: tx,
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(config.limits.max_frame_size),
+        max_frame_size: Some(config.limits.max_frame_size),
+        ..Default::default()
+    };
+    let ws = tokio_tungstenite::accept_async_with_config(s, Some(ws_config)).await?;
+
+    let (w, r) = ws.split();
+
+    let r = r
+        .try_filter(|msg| future::ready(msg.is_binary() || msg.is_text()))
+        .map(|message| match message {
+            Ok(message) => Ok(Frame::Data(message.into_data())),
+            Err(WsError::Capacity(_)) => Ok(Frame::Oversized),
+            Err(e) => Err(e.into()),
+        });
+    // JSON-RPC clients expect text messages.
+    let text = config.jsonrpc.is_some();
+    let w = w
+        .sink_map_err(anyhow::Error::from)
+        .with(move |data: Vec<u8>| {
+            future::ready(if text {
+                String::from_utf8(data)
+                    .map(WsMessage::Text)
+                    .map_err(anyhow::Error::from)
+            } else {
+                Ok(WsMessage::Binary(data))
             })
-            .await
-        {
-            log::warn!("Failed to send request: {}", e);
-            continue;
-        }
-
-        let response = match rx.await {
-            Ok(v) => v,
-            Err(e) => {
-                log::warn!("Failed to wait response: {:?}", e);
-                continue;
-            }
-        };
-
-        let data = match serde_json::to_vec(&WsRpcMessage {
-            id,
-            value: response,
-        }) {
-            Ok(data) => data,
-            Err(e) => {
-                log::warn!("{:?}", e);
-                continue;
-            }
-        };
-
-        if let Err(e) = w.send(WsMessage::Binary(data)).await {
-            anyhow::bail!("Failed to send from websocket: {:?}", e);
-        }
-    }
+        });
 
-    anyhow::bail!("websocket stream exited")
+    conn::serve(
+        r,
+        Box::pin(w),
+        Some(peer),
+        Transport::new("websocket"),
+        rpctx,
+        config,
+    )
+    .await
 }
 
 pub async fn reader<Addr, Request, Response>(
@@ -175,14 +195,40 @@ where
     for<'de> Request: Deserialize<'de> + Send + 'static,
     Response: Serialize + Send + 'static,
 {
-    let listener = TcpListener::bind(addr).await?;
+    reader_with_config(addr, tx, ServerConfig::default()).await
+}
 
+pub async fn reader_with_config<Addr, Request, Response>(
+    addr: Addr,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    Addr: ToSocketAddrs,
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
+    reader_with_listener(TcpListener::bind(addr).await?, tx, config).await
+}
+
+/// Serves like [`reader_with_config`] the connections of `listener`, bound
+/// by the caller, e.g. to port 0 to read back the address.
+pub async fn reader_with_listener<Request, Response>(
+    listener: TcpListener,
+    tx: mpsc::Sender<Message<Request, Response>>,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    for<'de> Request: Deserialize<'de> + Send + 'static,
+    Response: Serialize + Send + 'static,
+{
     loop {
-        let (s, _) = listener.accept().await?;
+        let (s, peer) = listener.accept().await?;
 
         let tx = tx.clone();
+        let config = config.clone();
         tokio::spawn(async move {
-            if let Err(e) = on_accept(s, tx).await {
+            if let Err(e) = on_accept(s, peer, tx, config).await {
                 log::warn!("{:?}", e);
             }
         });
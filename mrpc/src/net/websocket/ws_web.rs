@@ -7,14 +7,22 @@ use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
 use std::{cell::RefCell, rc::Rc};
 
-
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{ErrorEvent, Event, MessageEvent, WebSocket};
 
-use crate::{Message, sync::{mpsc, oneshot, Mutex}, spawn_local};
+use crate::{
+    net::{
+        handshake::{self, Handshake},
+        ClientConfig, RpcRequest, RpcResponse,
+    },
+    spawn_local,
+    sync::{mpsc, oneshot, Mutex},
+    Error, Message,
+};
 
-use super::message::WsRpcMessage;
+/// The calls waiting for their response, by request id.
+type IdMap<Response> = Arc<Mutex<HashMap<i64, oneshot::Sender<Result<Response, Error>>>>>;
 
 #[derive(Debug)]
 pub enum WsEvent {
@@ -116,10 +124,10 @@ impl Stream for WsStream {
 
         if s.evq.is_empty() {
             s.waker = Some(cx.waker().clone());
-            return Poll::Pending;
+            Poll::Pending
         } else {
             let e = s.evq.pop();
-            return Poll::Ready(e);
+            Poll::Ready(e)
         }
     }
 }
@@ -127,6 +135,18 @@ impl Stream for WsStream {
 pub async fn connect<Request, Response, Addr>(
     addr: Addr,
 ) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    Addr: ToString,
+    for<'de> Response: Deserialize<'de> + Send + 'static,
+    Request: Serialize + Send + 'static,
+{
+    connect_with_config(addr, ClientConfig::default()).await
+}
+
+pub async fn connect_with_config<Request, Response, Addr>(
+    addr: Addr,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
 where
     Addr: ToString,
     for<'de> Response: Deserialize<'de> + Send + 'static,
@@ -145,7 +165,7 @@ where
 
     if let Some(ev) = wss.next().await {
         match ev {
-            WsEvent::Open => {  }
+            WsEvent::Open => {}
             WsEvent::Message(_) => {
                 anyhow::bail!(
                     "Failed to connect websocket: message event should not have happened"
@@ -159,6 +179,21 @@ where
         anyhow::bail!("Failed to recv websocket event");
     }
 
+    if let Some(hello) = &config.handshake {
+        if let Err(e) = ws.send_with_u8_array(&Handshake::Hello(hello.clone()).encode()) {
+            anyhow::bail!("{:?}", e);
+        }
+        match wss.next().await {
+            Some(WsEvent::Message(data)) => {
+                handshake::check_reply(hello, &data)?;
+            }
+            Some(WsEvent::Error(e)) => {
+                anyhow::bail!("Failed to connect websocket: {:?}", e);
+            }
+            _ => anyhow::bail!("connection closed during the handshake"),
+        }
+    }
+
     let id_map = Arc::new(Mutex::new(HashMap::new()));
     spawn_local(accept_ws_event_loop(wss, id_map.clone()));
     spawn_local(accept_rpc_request_loop(
@@ -169,9 +204,34 @@ where
     Ok(tx)
 }
 
+/// Connects like [`connect_with_config`], falling back to the
+/// [Server-Sent Events transport](crate::net::sse) at `fallback` when the
+/// WebSocket cannot be opened, as behind some proxies.
+#[cfg(feature = "sse_web")]
+pub async fn connect_with_fallback<Request, Response, Addr, Fallback>(
+    addr: Addr,
+    fallback: Fallback,
+    config: ClientConfig,
+) -> anyhow::Result<mpsc::Sender<Message<Request, Response>>>
+where
+    Addr: ToString,
+    Fallback: ToString,
+    for<'de> Response: Deserialize<'de> + Send + 'static,
+    Request: Serialize + Send + 'static,
+{
+    match connect_with_config(addr, config.clone()).await {
+        Ok(tx) => Ok(tx),
+        Err(e) => {
+            let fallback = fallback.to_string();
+            log::warn!("{:?}, falling back to {}", e, fallback);
+            crate::net::sse::connect_with_config(fallback, config).await
+        }
+    }
+}
+
 async fn accept_ws_event_loop<Response>(
     mut s: WsStream,
-    id_map: Arc<Mutex<HashMap<i64, oneshot::Sender<Response>>>>,
+    id_map: IdMap<Response>,
 ) where
     for<'de> Response: Deserialize<'de> + Send + 'static,
 {
@@ -181,18 +241,24 @@ async fn accept_ws_event_loop<Response>(
                 log::warn!("open event should not have happened");
             }
             WsEvent::Message(data) => {
-                let WsRpcMessage { id, value } =
-                    match serde_json::from_slice::<WsRpcMessage<Response>>(&data) {
-                        Ok(data) => data,
-                        Err(e) => {
-                            log::warn!("{:?}", e);
-                            continue;
-                        }
-                    };
+                let (id, value) = match serde_json::from_slice::<RpcResponse<Response>>(&data) {
+                    Ok(RpcResponse {
+                        id: Some(id),
+                        value,
+                    }) => (id, value),
+                    Ok(RpcResponse { id: None, value }) => {
+                        log::warn!("Response without id: {:?}", value.err());
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("{:?}", e);
+                        continue;
+                    }
+                };
 
                 match id_map.lock().await.remove(&id) {
                     Some(rpc_response_tx) => {
-                        if let Err(_) = rpc_response_tx.send(value) {
+                        if rpc_response_tx.send(value).is_err() {
                             log::warn!("Failed to send rpc response");
                         }
                     }
@@ -212,18 +278,27 @@ async fn accept_ws_event_loop<Response>(
 async fn accept_rpc_request_loop<Request, Response>(
     mut rpc_request_source: mpsc::Receiver<Message<Request, Response>>,
     ws: SendWrapper<WebSocket>,
-    id_map: Arc<Mutex<HashMap<i64, oneshot::Sender<Response>>>>,
+    id_map: IdMap<Response>,
 ) where
     for<'de> Response: Deserialize<'de> + Send + 'static,
     Request: Serialize + Send + 'static,
 {
     let mut id_generator: i64 = 0;
     while let Some(message) = rpc_request_source.recv().await {
-        let Message::<Request, Response> { req, resp } = message;
-
-        let data = match serde_json::to_string(&WsRpcMessage {
+        let Message::<Request, Response> {
+            req,
+            resp,
+            span,
+            metadata,
+            ..
+        } = message;
+        span.record_request_id(id_generator);
+
+        let data = match serde_json::to_string(&RpcRequest {
             id: id_generator,
             value: req,
+            trace: span.trace_context(),
+            metadata,
         }) {
             Ok(data) => data,
             Err(e) => {
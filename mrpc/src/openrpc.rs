@@ -0,0 +1,125 @@
+//! OpenRPC documents of servers, emitted by `message(serde, schema)` on a
+//! `#[mrpc::server]` as `ServerRequest::openrpc()`.
+//!
+//! The JSON Schemas of the arguments and outputs are derived by `schemars`,
+//! so every service of the server needs `message(serde, schema)` as well,
+//! and the types they use must implement
+//! [`JsonSchema`](schemars::JsonSchema). The methods are named
+//! `Service.method` like in [`JsonRpc`](crate::net::JsonRpc).
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    Map,
+};
+use serde::Serialize;
+
+use crate::descriptor::ServerDescriptor;
+
+/// Version of the specification the documents follow.
+pub const OPENRPC_VERSION: &str = "1.2.6";
+
+/// Schemas of a method, in the order of the
+/// [`MethodDescriptor::args`](crate::descriptor::MethodDescriptor).
+pub struct MethodSchema {
+    pub args: Vec<Schema>,
+    pub output: Schema,
+}
+
+/// Implemented by the requests of the services with `message(serde,
+/// schema)`.
+pub trait MethodSchemas {
+    /// The schemas of the methods, in the order of `descriptor()`.
+    fn method_schemas(gen: &mut SchemaGenerator) -> Vec<MethodSchema>;
+}
+
+/// A generator putting the definitions where OpenRPC expects them.
+pub fn generator() -> SchemaGenerator {
+    SchemaSettings::draft07()
+        .with(|settings| settings.definitions_path = "#/components/schemas/".into())
+        .into_generator()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenRpc {
+    pub openrpc: String,
+    pub info: Info,
+    pub methods: Vec<Method>,
+    pub components: Components,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Info {
+    pub title: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Method {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub params: Vec<ContentDescriptor>,
+    pub result: ContentDescriptor,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentDescriptor {
+    pub name: String,
+    pub required: bool,
+    pub schema: Schema,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Components {
+    pub schemas: Map<String, Schema>,
+}
+
+/// The document of a server, from its descriptor and the schemas of its
+/// services in the same order. `gen` holds the definitions the schemas
+/// refer to.
+pub fn document(
+    descriptor: &ServerDescriptor,
+    version: &str,
+    schemas: Vec<Vec<MethodSchema>>,
+    mut gen: SchemaGenerator,
+) -> OpenRpc {
+    let mut methods = Vec::new();
+    for (entry, schemas) in descriptor.services.iter().zip(schemas) {
+        for (method, schema) in entry.service.methods.iter().zip(schemas) {
+            let params = method
+                .args
+                .iter()
+                .zip(schema.args)
+                .map(|(arg, schema)| ContentDescriptor {
+                    name: arg.name.clone(),
+                    required: !arg.default,
+                    schema,
+                })
+                .collect();
+
+            methods.push(Method {
+                name: format!("{}.{}", entry.name, method.name),
+                description: method.doc.clone(),
+                params,
+                result: ContentDescriptor {
+                    name: "result".into(),
+                    required: true,
+                    schema: schema.output,
+                },
+            });
+        }
+    }
+
+    OpenRpc {
+        openrpc: OPENRPC_VERSION.into(),
+        info: Info {
+            title: descriptor.name.clone(),
+            version: version.into(),
+        },
+        methods,
+        components: Components {
+            schemas: gen.take_definitions(),
+        },
+    }
+}
@@ -0,0 +1,68 @@
+//! The service added to a server by `#[mrpc::server(reflection)]`, which
+//! tells what the server offers.
+//!
+//! Its requests are `{"Reflection": ..}` whatever the `rename_all` of the
+//! server, which the macro requires to be externally tagged, so clients can
+//! call it before they know anything else of the server, see
+//! [`describe_route`].
+
+use std::sync::Arc;
+
+use crate::descriptor::{Route, ServerDescriptor, ServiceEntry, Tagging};
+
+#[cfg_attr(feature = "schemars", crate::service(message(serde, debug, schema)))]
+#[cfg_attr(not(feature = "schemars"), crate::service(message(serde, debug)))]
+pub trait Reflection {
+    /// Describes every service of the server.
+    async fn describe() -> ServerDescriptor;
+    /// Describes the service of the server named `name`.
+    async fn describe_service(name: String) -> Option<ServiceEntry>;
+}
+
+/// Answers with the descriptor of the server it was created with.
+pub struct ServerReflection {
+    descriptor: ServerDescriptor,
+}
+
+impl ServerReflection {
+    pub fn new(descriptor: ServerDescriptor) -> Self {
+        Self { descriptor }
+    }
+}
+
+#[crate::async_trait]
+impl Reflection for ServerReflection {
+    async fn describe(self: Arc<Self>) -> ServerDescriptor {
+        self.descriptor.clone()
+    }
+
+    async fn describe_service(self: Arc<Self>, name: String) -> Option<ServiceEntry> {
+        self.descriptor
+            .services
+            .iter()
+            .find(|entry| entry.name == name)
+            .cloned()
+    }
+}
+
+/// The route of [`Reflection::describe`] on any server with `reflection`.
+pub fn describe_route() -> Route {
+    let entry = ServiceEntry {
+        name: "Reflection".to_string(),
+        wire_name: None,
+        type_args: Vec::new(),
+        service: ReflectionRequest::descriptor(),
+    };
+    let server = ServerDescriptor {
+        name: String::new(),
+        tagging: Tagging::External,
+        services: vec![entry.clone()],
+    };
+    let describe = entry
+        .service
+        .methods
+        .iter()
+        .find(|method| method.name == "describe")
+        .expect("`Reflection` has `describe`");
+    Route::new(&server, &entry, describe).expect("`Reflection` is tagged")
+}
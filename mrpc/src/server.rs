@@ -0,0 +1,435 @@
+//! Runtime support for the `serve` generated by `#[mrpc::server]`.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use web_time::Instant;
+
+use crate::{descriptor::ServiceDescriptor, spawn, Error, RemoteErrorKind};
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Implemented by `#[mrpc::service]` for `dyn Service`, to call a service
+/// instance however its methods take `self`.
+#[async_trait]
+pub trait Dispatch {
+    type Request: Send;
+    type Response: Send;
+    /// What `create_<service>` returns in an `Arc`: `dyn Service`, or
+    /// `Mutex<dyn Service>` when some methods take `&mut self`.
+    type Target: ?Sized + Send + Sync;
+
+    async fn dispatch(service: Arc<Self::Target>, req: Self::Request) -> Self::Response;
+
+    fn descriptor() -> ServiceDescriptor;
+}
+
+/// Limits on the calls handled at the same time, set with
+/// `concurrency(limit = .., queue = ..)` on the server or on a service.
+/// `None` means unlimited.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Concurrency {
+    /// Calls running at the same time.
+    pub limit: Option<usize>,
+    /// Calls waiting to run. Calls arriving when it is full are rejected
+    /// with [`Error::Overloaded`].
+    pub queue: Option<usize>,
+}
+
+struct Limiter {
+    semaphore: Option<Arc<Semaphore>>,
+    waiting: AtomicUsize,
+    queue: Option<usize>,
+}
+
+impl Limiter {
+    fn new(concurrency: Concurrency) -> Arc<Self> {
+        Arc::new(Self {
+            semaphore: concurrency
+                .limit
+                .map(|limit| Arc::new(Semaphore::new(limit))),
+            waiting: AtomicUsize::new(0),
+            queue: concurrency.queue,
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        matches!(self.queue, Some(queue) if self.waiting.load(Ordering::Acquire) >= queue)
+    }
+
+    /// Takes a permit if one is free. `Some(None)` means unlimited.
+    fn try_acquire(&self) -> Option<Option<OwnedSemaphorePermit>> {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.clone().try_acquire_owned().ok().map(Some),
+            None => Some(None),
+        }
+    }
+
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+}
+
+type Permits = (Option<OwnedSemaphorePermit>, Option<OwnedSemaphorePermit>);
+
+/// Holds the server-wide limit, shared by the lanes of every service.
+pub struct Scheduler {
+    global: Arc<Limiter>,
+}
+
+impl Scheduler {
+    pub fn new(global: Concurrency) -> Self {
+        Self {
+            global: Limiter::new(global),
+        }
+    }
+
+    /// Creates the lane of one service. Every call of a `sequential` lane
+    /// runs one at a time.
+    pub fn lane(&self, name: &'static str, concurrency: Concurrency, sequential: bool) -> Lane {
+        Lane {
+            name,
+            global: self.global.clone(),
+            local: Limiter::new(concurrency),
+            sequential,
+            worker: StdMutex::new(None),
+        }
+    }
+}
+
+/// Schedules the calls of one service.
+pub struct Lane {
+    name: &'static str,
+    global: Arc<Limiter>,
+    local: Arc<Limiter>,
+    sequential: bool,
+    worker: StdMutex<Option<mpsc::UnboundedSender<Job>>>,
+}
+
+impl Lane {
+    /// Admits a call in arrival order, or rejects it when it would have to
+    /// wait in a full queue. `sequential` calls run one at a time in the
+    /// order they were admitted, and are rejected with
+    /// [`Error::ServiceUnavailable`] if the worker running them is gone.
+    pub fn admit(&self, sequential: bool) -> Result<Ticket<'_>, Error> {
+        let sequential = sequential || self.sequential;
+
+        if !sequential {
+            if let (Some(local), Some(global)) =
+                (self.local.try_acquire(), self.global.try_acquire())
+            {
+                return Ok(Ticket {
+                    lane: self,
+                    worker: None,
+                    permits: Some((local, global)),
+                });
+            }
+        }
+
+        if self.global.is_full() || self.local.is_full() {
+            return Err(Error::Overloaded(self.name.into()));
+        }
+
+        // A call running out of order is worse than a failed one.
+        let worker = match sequential.then(|| self.worker()) {
+            Some(worker) if worker.is_closed() => {
+                return Err(Error::ServiceUnavailable(self.name.into()));
+            }
+            worker => worker,
+        };
+
+        self.global.waiting.fetch_add(1, Ordering::AcqRel);
+        self.local.waiting.fetch_add(1, Ordering::AcqRel);
+
+        Ok(Ticket {
+            lane: self,
+            worker,
+            permits: None,
+        })
+    }
+
+    fn worker(&self) -> mpsc::UnboundedSender<Job> {
+        self.worker
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                let (tx, mut rx) = mpsc::unbounded_channel::<Job>();
+                let name = self.name;
+                spawn(async move {
+                    while let Some(job) = rx.recv().await {
+                        // The calls themselves catch their panics, but not
+                        // creating the service.
+                        if let Err(payload) = AssertUnwindSafe(job).catch_unwind().await {
+                            log::warn!("Call of {} panicked: {}", name, panic_message(&*payload));
+                        }
+                    }
+                });
+                tx
+            })
+            .clone()
+    }
+}
+
+/// An admitted call, either running or waiting for its turn.
+pub struct Ticket<'a> {
+    lane: &'a Lane,
+    /// The worker of the lane, for a sequential call.
+    worker: Option<mpsc::UnboundedSender<Job>>,
+    permits: Option<Permits>,
+}
+
+impl Ticket<'_> {
+    pub fn spawn<F>(self, call: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if let Some(permits) = self.permits {
+            spawn(async move {
+                let _permits = permits;
+                call.await;
+            });
+            return;
+        }
+
+        let (global, local) = (self.lane.global.clone(), self.lane.local.clone());
+        let job = Box::pin(async move {
+            let _local = local.acquire().await;
+            let _global = global.acquire().await;
+            local.waiting.fetch_sub(1, Ordering::AcqRel);
+            global.waiting.fetch_sub(1, Ordering::AcqRel);
+            call.await;
+        });
+
+        match self.worker {
+            Some(worker) => {
+                if worker.send(job).is_err() {
+                    log::warn!("Dropped a call of {}: its worker is gone", self.lane.name);
+                    self.lane.local.waiting.fetch_sub(1, Ordering::AcqRel);
+                    self.lane.global.waiting.fetch_sub(1, Ordering::AcqRel);
+                }
+            }
+            None => {
+                spawn(job);
+            }
+        }
+    }
+}
+
+/// Settings of the generated `serve`, returned by the `serve_options` method
+/// of the server trait.
+#[derive(Clone, Default)]
+pub struct ServeOptions {
+    pub retry: Retry,
+    /// Handle to invalidate service instances while serving.
+    pub control: Control,
+    /// Drop the instance of a service whose method panicked, so that the
+    /// next call creates a new one.
+    pub recreate_on_panic: bool,
+}
+
+/// When to try again to create a service after `create_<service>` failed.
+///
+/// Calls arriving before the backoff elapsed fail with
+/// [`Error::ServiceUnavailable`] without calling `create_<service>`, whatever
+/// the scope of the service. The
+/// backoff doubles after each consecutive failure, up to `max_backoff`.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Retry {
+    fn delay(&self, failures: u32) -> Duration {
+        let factor = 1u32 << failures.saturating_sub(1).min(31);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Invalidates service instances so that the next call creates them again.
+///
+/// Clones control the same instances.
+#[derive(Clone, Default)]
+pub struct Control {
+    generations: Arc<StdMutex<HashMap<String, u64>>>,
+}
+
+impl Control {
+    /// Drops the instances of the service named after its variant in the
+    /// server enum, e.g. `"Worker"`. Calls already running keep theirs.
+    pub fn invalidate(&self, service: &str) {
+        *self
+            .generations
+            .lock()
+            .unwrap()
+            .entry(service.into())
+            .or_default() += 1;
+    }
+
+    fn generation(&self, service: &str) -> u64 {
+        self.generations
+            .lock()
+            .unwrap()
+            .get(service)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// The failed attempts to create a service, shared by all its instances
+/// whatever their scope, so that the backoff of [`Retry`] holds across them.
+#[derive(Default)]
+pub struct Backoff {
+    state: StdMutex<BackoffState>,
+}
+
+#[derive(Default)]
+struct BackoffState {
+    generation: u64,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    /// Whether `create_<service>` may be called, starting afresh when the
+    /// service was invalidated since the last failure.
+    fn ready(&self, generation: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            *state = BackoffState {
+                generation,
+                ..Default::default()
+            };
+        }
+        !matches!(state.retry_at, Some(at) if Instant::now() < at)
+    }
+
+    fn succeeded(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.retry_at = None;
+    }
+
+    fn failed(&self, retry: &Retry) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        state.retry_at = Some(Instant::now() + retry.delay(state.failures));
+    }
+}
+
+/// A service instance, created on the first call that needs it.
+pub struct Instance<T> {
+    value: Option<T>,
+    generation: u64,
+}
+
+impl<T> Default for Instance<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            generation: 0,
+        }
+    }
+}
+
+impl<T: Clone> Instance<T> {
+    /// Returns the instance, creating it with `create` if it does not exist
+    /// or was invalidated, unless the last failure is still in `backoff`.
+    pub async fn get<F, Fut>(
+        &mut self,
+        name: &str,
+        options: &ServeOptions,
+        backoff: &Backoff,
+        create: F,
+    ) -> Result<T, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let generation = options.control.generation(name);
+        if self.generation != generation {
+            *self = Self {
+                value: None,
+                generation,
+            };
+        }
+
+        if let Some(value) = &self.value {
+            return Ok(value.clone());
+        }
+
+        if !backoff.ready(generation) {
+            return Err(Error::ServiceUnavailable(name.into()));
+        }
+
+        match create().await {
+            Ok(value) => {
+                backoff.succeeded();
+                self.value = Some(value.clone());
+                Ok(value)
+            }
+            Err(e) => {
+                log::warn!("Failed to create {}: {:?}", name, e);
+                backoff.failed(&options.retry);
+                Err(Error::ServiceUnavailable(name.into()))
+            }
+        }
+    }
+
+    /// Drops the instance if `is_poisoned` says so.
+    pub fn poison(&mut self, is_poisoned: impl FnOnce(&T) -> bool) {
+        if self.value.as_ref().is_some_and(is_poisoned) {
+            self.value = None;
+        }
+    }
+}
+
+/// Runs a call, turning a panic into [`Error::Remote`].
+pub async fn catch_panic<F>(service: &str, method: &str, call: F) -> Result<F::Output, Error>
+where
+    F: Future,
+{
+    AssertUnwindSafe(call).catch_unwind().await.map_err(|payload| {
+        let message = panic_message(&*payload);
+        log::warn!("{}::{} panicked: {}", service, method, message);
+        Error::Remote {
+            kind: RemoteErrorKind::Panic,
+            message,
+        }
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".into()
+    }
+}
@@ -0,0 +1,264 @@
+//! Spans of the calls, recorded with the `tracing` feature.
+//!
+//! A call of a generated client runs in a `mrpc.call` span, and its handler
+//! on the server in a `mrpc.handle` span. Both have the fields `service`,
+//! `method`, `request_id`, `peer` and `outcome`, plus `error` when the call
+//! failed. The handlers are instrumented with their span, so the events of
+//! the services belong to the call.
+//!
+//! Without the feature, [`Span`] is empty and its methods do nothing, so the
+//! generated code and the transports use it either way.
+//!
+//! With the `opentelemetry` feature, and a `tracing-opentelemetry` layer in
+//! the subscriber, the requests carry the [`TraceContext`] of the calls, so
+//! the handlers continue the traces of their callers across processes.
+
+use std::{fmt::Display, future::Future, net::SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+/// The W3C Trace Context of a call, in the envelope of its request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext {
+    pub traceparent: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tracestate: String,
+}
+
+#[cfg(feature = "opentelemetry")]
+impl TraceContext {
+    fn from_span_context(context: &opentelemetry::trace::SpanContext) -> Option<Self> {
+        if !context.is_valid() {
+            return None;
+        }
+        Some(Self {
+            traceparent: format!(
+                "00-{}-{}-{:02x}",
+                context.trace_id(),
+                context.span_id(),
+                context.trace_flags().to_u8()
+            ),
+            tracestate: context.trace_state().header(),
+        })
+    }
+
+    /// The remote span of `traceparent`, `None` when it is invalid. Later
+    /// versions of the format may append fields, which are ignored.
+    fn span_context(&self) -> Option<opentelemetry::trace::SpanContext> {
+        use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+        let mut parts = self.traceparent.trim().split('-');
+        let (version, trace_id, span_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        let hex = |part: &str, len: usize| {
+            part.len() == len && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        if !hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if !hex(trace_id, 32) || !hex(span_id, 16) || !hex(flags, 2) {
+            return None;
+        }
+
+        let context = SpanContext::new(
+            TraceId::from_hex(trace_id).ok()?,
+            SpanId::from_hex(span_id).ok()?,
+            TraceFlags::new(u8::from_str_radix(flags, 16).ok()? & TraceFlags::SAMPLED.to_u8()),
+            true,
+            self.tracestate
+                .parse()
+                .unwrap_or_else(|_| TraceState::default()),
+        );
+        context.is_valid().then_some(context)
+    }
+}
+
+/// The span of a call, travelling with its [`Message`](crate::Message).
+#[derive(Clone, Debug)]
+pub struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Span {
+    /// A span recording nothing.
+    pub fn none() -> Self {
+        Self {
+            inner: tracing::Span::none(),
+        }
+    }
+
+    /// The span the caller runs in.
+    pub fn current() -> Self {
+        Self {
+            inner: tracing::Span::current(),
+        }
+    }
+
+    /// The span of a call by a client.
+    pub fn client(service: &str, method: &str) -> Self {
+        Self {
+            inner: tracing::info_span!(
+                "mrpc.call",
+                service,
+                method,
+                request_id = tracing::field::Empty,
+                peer = tracing::field::Empty,
+                outcome = tracing::field::Empty,
+                error = tracing::field::Empty,
+            ),
+        }
+    }
+
+    /// The span of a request received from `peer`, before it is known
+    /// which method it calls.
+    pub fn server(peer: Option<SocketAddr>) -> Self {
+        let span = Self {
+            inner: tracing::info_span!(
+                "mrpc.handle",
+                service = tracing::field::Empty,
+                method = tracing::field::Empty,
+                request_id = tracing::field::Empty,
+                peer = tracing::field::Empty,
+                outcome = tracing::field::Empty,
+                error = tracing::field::Empty,
+            ),
+        };
+        span.record_peer(peer);
+        span
+    }
+
+    pub fn record_method(&self, service: &str, method: &str) {
+        self.inner.record("service", &service);
+        self.inner.record("method", &method);
+    }
+
+    pub fn record_request_id(&self, id: impl Display) {
+        self.inner
+            .record("request_id", &tracing::field::display(id));
+    }
+
+    pub fn record_peer(&self, peer: Option<SocketAddr>) {
+        if let Some(peer) = peer {
+            self.inner.record("peer", &tracing::field::display(peer));
+        }
+    }
+
+    /// Records `ok`, or `error` and the error.
+    pub fn record_outcome<T, E>(&self, result: &Result<T, E>)
+    where
+        E: Display,
+    {
+        match result {
+            Ok(_) => {
+                self.inner.record("outcome", &"ok");
+            }
+            Err(e) => {
+                self.inner.record("outcome", &"error");
+                self.inner.record("error", &tracing::field::display(e));
+            }
+        }
+    }
+
+    /// Runs `fut` in the span.
+    pub fn instrument<F>(&self, fut: F) -> impl Future<Output = F::Output>
+    where
+        F: Future,
+    {
+        tracing::Instrument::instrument(fut, self.inner.clone())
+    }
+
+    /// The underlying `tracing` span.
+    pub fn as_tracing(&self) -> &tracing::Span {
+        &self.inner
+    }
+
+    /// The context to send along the request of the call.
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        #[cfg(feature = "opentelemetry")]
+        {
+            use opentelemetry::trace::TraceContextExt;
+            use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+            let context = self.inner.context();
+            TraceContext::from_span_context(context.span().span_context())
+        }
+        #[cfg(not(feature = "opentelemetry"))]
+        None
+    }
+
+    /// Continues the trace of the caller, before the span is entered.
+    pub fn set_remote_parent(&self, context: &TraceContext) {
+        #[cfg(feature = "opentelemetry")]
+        {
+            use opentelemetry::trace::TraceContextExt;
+            use tracing_opentelemetry::{OpenTelemetrySpanExt, SetParentError};
+
+            let span_context = match context.span_context() {
+                Some(span_context) => span_context,
+                None => {
+                    log::warn!("Invalid traceparent: {}", context.traceparent);
+                    return;
+                }
+            };
+            let parent = opentelemetry::Context::new().with_remote_span_context(span_context);
+            match self.inner.set_parent(parent) {
+                Ok(()) | Err(SetParentError::LayerNotFound) => {}
+                Err(e) => log::warn!("Failed to set the parent of {:?}: {}", self.inner, e),
+            }
+        }
+        #[cfg(not(feature = "opentelemetry"))]
+        let _ = context;
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub fn none() -> Self {
+        Self {}
+    }
+
+    pub fn current() -> Self {
+        Self {}
+    }
+
+    pub fn client(_service: &str, _method: &str) -> Self {
+        Self {}
+    }
+
+    pub fn server(_peer: Option<SocketAddr>) -> Self {
+        Self {}
+    }
+
+    pub fn record_method(&self, _service: &str, _method: &str) {}
+
+    pub fn record_request_id(&self, _id: impl Display) {}
+
+    pub fn record_peer(&self, _peer: Option<SocketAddr>) {}
+
+    pub fn record_outcome<T, E>(&self, _result: &Result<T, E>)
+    where
+        E: Display,
+    {
+    }
+
+    pub fn instrument<F>(&self, fut: F) -> impl Future<Output = F::Output>
+    where
+        F: Future,
+    {
+        fut
+    }
+
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        None
+    }
+
+    pub fn set_remote_parent(&self, _context: &TraceContext) {}
+}
@@ -0,0 +1,200 @@
+#![cfg(feature = "http")]
+
+use std::{sync::Arc, time::Duration};
+
+use mrpc::{
+    net::{http, Limits, ServerConfig},
+    sync::mpsc,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+#[mrpc::service(message(serde))]
+trait Calculator {
+    #[rpc(name = "addTo")]
+    async fn add(a: i64, b: i64) -> i64;
+    async fn zero() -> i64;
+}
+
+#[mrpc::service(message(serde, compact))]
+trait Echo {
+    #[rpc(id = 3)]
+    async fn echo(value: String) -> String;
+}
+
+struct CalculatorImpl;
+
+#[mrpc::async_trait]
+impl Calculator for CalculatorImpl {
+    async fn add(self: Arc<Self>, a: i64, b: i64) -> i64 {
+        a + b
+    }
+
+    async fn zero(self: Arc<Self>) -> i64 {
+        0
+    }
+}
+
+struct EchoImpl;
+
+#[mrpc::async_trait]
+impl Echo for EchoImpl {
+    async fn echo(self: Arc<Self>, value: String) -> String {
+        value
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Calculator(Calculator),
+    Echo(Echo),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_calculator(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Calculator>> {
+        Ok(Arc::new(CalculatorImpl))
+    }
+
+    async fn create_echo(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Echo>> {
+        Ok(Arc::new(EchoImpl))
+    }
+}
+
+/// Serves on a free port and returns its address.
+async fn serve(config: ServerConfig) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    tokio::spawn(async move {
+        http::reader_with_listener(listener, &ServerRequest::descriptor(), tx, config).await
+    });
+    addr
+}
+
+/// Sends one request on a new connection and returns the status, the
+/// headers and the JSON body of the response.
+async fn request(addr: &str, method: &str, path: &str, body: &str) -> (u16, String, Value) {
+    let mut s = TcpStream::connect(addr).await.unwrap();
+
+    let req = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    );
+    s.write_all(req.as_bytes()).await.unwrap();
+
+    let mut data = String::new();
+    tokio::time::timeout(Duration::from_secs(5), s.read_to_string(&mut data))
+        .await
+        .expect("no response")
+        .unwrap();
+
+    let (head, body) = data.split_once("\r\n\r\n").expect("no body");
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(body).unwrap()
+    };
+    (status, head.to_lowercase(), body)
+}
+
+#[tokio::test]
+async fn calls() {
+    let addr = serve(ServerConfig::default()).await;
+
+    let (status, head, body) =
+        request(&addr, "POST", "/Calculator/addTo", r#"{"a": 2, "b": 3}"#).await;
+    assert_eq!(status, 200);
+    assert!(head.contains("content-type: application/json"));
+    assert_eq!(body, json!(5));
+
+    let (status, _, body) = request(&addr, "POST", "/Calculator/add", r#"{"a": 1, "b": 1}"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(body, json!(2));
+
+    let (status, _, body) = request(&addr, "POST", "/Calculator/zero", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body, json!(0));
+
+    let (status, _, body) = request(&addr, "POST", "/Echo/echo", r#"{"value": "hi"}"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(body, json!("hi"));
+}
+
+#[tokio::test]
+async fn errors() {
+    let addr = serve(ServerConfig {
+        limits: Limits {
+            max_frame_size: 64,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let (status, _, body) = request(&addr, "POST", "/Calculator/reset", "").await;
+    assert_eq!(status, 404);
+    assert!(body["error"]["Protocol"].is_string());
+
+    let (status, head, _) = request(&addr, "GET", "/Calculator/zero", "").await;
+    assert_eq!(status, 405);
+    assert!(head.contains("allow: post"));
+
+    let (status, _, body) = request(&addr, "POST", "/Calculator/addTo", "{").await;
+    assert_eq!(status, 400);
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("protocol error"));
+
+    let (status, _, _) = request(
+        &addr,
+        "POST",
+        "/Calculator/addTo",
+        r#"{"a": "one", "b": 1}"#,
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    let (status, _, _) = request(&addr, "POST", "/Echo/echo", "{}").await;
+    assert_eq!(status, 400);
+
+    let value = "a".repeat(100);
+    let (status, head, body) = request(
+        &addr,
+        "POST",
+        "/Echo/echo",
+        &json!({ "value": value }).to_string(),
+    )
+    .await;
+    assert_eq!(status, 413);
+    assert!(head.contains("connection: close"));
+    assert_eq!(body["error"], json!({ "LimitExceeded": "FrameSize" }));
+}
+
+#[tokio::test]
+async fn refused_connections() {
+    let addr = serve(ServerConfig {
+        limits: Limits {
+            max_connections: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let (status, _, body) = request(&addr, "POST", "/Calculator/zero", "").await;
+    assert_eq!(status, 429);
+    assert_eq!(body["error"], json!({ "LimitExceeded": "Connections" }));
+}
@@ -0,0 +1,436 @@
+#![cfg(feature = "tcp")]
+
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mrpc::{
+    net::{tcp, JsonRpc, Limits, ServerConfig},
+    sync::mpsc,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+};
+
+#[mrpc::service(message(serde))]
+trait Counter {
+    #[rpc(name = "addTo")]
+    async fn add(amount: i64, times: i64) -> i64;
+    async fn total() -> i64;
+}
+
+#[mrpc::service(message(serde, compact))]
+trait Echo {
+    #[rpc(id = 3)]
+    async fn echo(value: String) -> String;
+}
+
+#[derive(Default)]
+struct CounterImpl {
+    total: AtomicI64,
+}
+
+#[mrpc::async_trait]
+impl Counter for CounterImpl {
+    async fn add(self: Arc<Self>, amount: i64, times: i64) -> i64 {
+        self.total.fetch_add(amount * times, Ordering::SeqCst) + amount * times
+    }
+
+    async fn total(self: Arc<Self>) -> i64 {
+        self.total.load(Ordering::SeqCst)
+    }
+}
+
+struct EchoImpl;
+
+#[mrpc::async_trait]
+impl Echo for EchoImpl {
+    async fn echo(self: Arc<Self>, value: String) -> String {
+        value
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Counter(Counter),
+    Echo(Echo),
+}
+
+#[derive(Default)]
+struct ServerImpl {
+    counter: Arc<CounterImpl>,
+}
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_counter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Counter>> {
+        Ok(self.counter.clone())
+    }
+
+    async fn create_echo(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Echo>> {
+        Ok(Arc::new(EchoImpl))
+    }
+}
+
+/// A server whose enums serde renames and tags adjacently.
+mod tagged {
+    use std::sync::Arc;
+
+    #[mrpc::service(message(serde(
+        rename_all = "snake_case",
+        tag = "method",
+        content = "params"
+    )))]
+    pub trait Store {
+        async fn get_value(key: String) -> Option<String>;
+    }
+
+    pub struct StoreImpl;
+
+    #[mrpc::async_trait]
+    impl Store for StoreImpl {
+        async fn get_value(self: Arc<Self>, key: String) -> Option<String> {
+            Some(key.to_uppercase())
+        }
+    }
+
+    #[mrpc::server(message(serde(
+        rename_all = "lowercase",
+        tag = "service",
+        content = "request"
+    )))]
+    pub enum Server {
+        Store(Store),
+    }
+
+    pub struct ServerImpl;
+
+    #[mrpc::async_trait]
+    impl Server for ServerImpl {
+        async fn create_store(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Store>> {
+            Ok(Arc::new(StoreImpl))
+        }
+    }
+
+    #[mrpc::server(message(serde(untagged)))]
+    pub enum UntaggedServer {
+        Store(Store),
+    }
+}
+
+struct Peer {
+    w: OwnedWriteHalf,
+    lines: Lines<BufReader<OwnedReadHalf>>,
+}
+
+impl Peer {
+    async fn connect(addr: &str) -> Self {
+        let (r, w) = TcpStream::connect(addr).await.unwrap().into_split();
+        Self {
+            w,
+            lines: BufReader::new(r).lines(),
+        }
+    }
+
+    async fn send(&mut self, line: &str) {
+        self.w.write_all(line.as_bytes()).await.unwrap();
+        self.w.write_all(b"\n").await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Value {
+        let line = tokio::time::timeout(Duration::from_secs(5), self.lines.next_line())
+            .await
+            .expect("no response")
+            .unwrap()
+            .expect("connection closed");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    async fn call(&mut self, request: Value) -> Value {
+        self.send(&request.to_string()).await;
+        self.recv().await
+    }
+}
+
+/// A listener on a free port, and its address.
+async fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+async fn serve() -> String {
+    serve_with_limits(Limits::default()).await
+}
+
+async fn serve_with_limits(limits: Limits) -> String {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    let config = ServerConfig {
+        jsonrpc: Some(Arc::new(
+            JsonRpc::new(&ServerRequest::descriptor()).unwrap(),
+        )),
+        limits,
+        ..Default::default()
+    };
+    tokio::spawn(Server::serve(Arc::new(ServerImpl::default()), rx));
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+    addr
+}
+
+fn error_code(response: &Value) -> i64 {
+    response["error"]["code"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn calls() {
+    let addr = serve().await;
+    let mut peer = Peer::connect(&addr).await;
+
+    let response = peer
+        .call(json!({
+            "jsonrpc": "2.0",
+            "method": "Counter.addTo",
+            "params": { "amount": 2, "times": 3 },
+            "id": 1,
+        }))
+        .await;
+    assert_eq!(response, json!({ "jsonrpc": "2.0", "result": 6, "id": 1 }));
+
+    let response = peer
+        .call(json!({
+            "jsonrpc": "2.0",
+            "method": "Counter.add",
+            "params": [1, 1],
+            "id": "two",
+        }))
+        .await;
+    assert_eq!(
+        response,
+        json!({ "jsonrpc": "2.0", "result": 7, "id": "two" })
+    );
+
+    let response = peer
+        .call(json!({ "jsonrpc": "2.0", "method": "Counter.total", "id": 3 }))
+        .await;
+    assert_eq!(response["result"], 7);
+
+    let response = peer
+        .call(json!({
+            "jsonrpc": "2.0",
+            "method": "Echo.echo",
+            "params": { "value": "hi" },
+            "id": 4,
+        }))
+        .await;
+    assert_eq!(response["result"], "hi");
+}
+
+#[tokio::test]
+async fn errors() {
+    let addr = serve().await;
+    let mut peer = Peer::connect(&addr).await;
+
+    peer.send("{").await;
+    let response = peer.recv().await;
+    assert_eq!(error_code(&response), -32700);
+    assert_eq!(response["id"], Value::Null);
+
+    let response = peer
+        .call(json!({ "method": "Counter.total", "id": 1 }))
+        .await;
+    assert_eq!(error_code(&response), -32600);
+    assert_eq!(response["id"], 1);
+
+    let response = peer
+        .call(json!({ "jsonrpc": "2.0", "method": "Counter.reset", "id": 2 }))
+        .await;
+    assert_eq!(error_code(&response), -32601);
+
+    let response = peer
+        .call(json!({
+            "jsonrpc": "2.0",
+            "method": "Counter.add",
+            "params": { "amount": "one", "times": 1 },
+            "id": 3,
+        }))
+        .await;
+    assert_eq!(error_code(&response), -32602);
+
+    let response = peer
+        .call(json!({
+            "jsonrpc": "2.0",
+            "method": "Echo.echo",
+            "params": [],
+            "id": 4,
+        }))
+        .await;
+    assert_eq!(error_code(&response), -32602);
+
+    let response = peer.call(json!([])).await;
+    assert_eq!(error_code(&response), -32600);
+}
+
+#[tokio::test]
+async fn batches_and_notifications() {
+    let addr = serve().await;
+    let mut peer = Peer::connect(&addr).await;
+
+    // Notifications are handled but never answered.
+    peer.send(
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "Counter.add",
+            "params": { "amount": 5, "times": 1 },
+        })
+        .to_string(),
+    )
+    .await;
+    peer.send(&json!({ "jsonrpc": "2.0", "method": "Counter.reset" }).to_string())
+        .await;
+    peer.send(&json!([{ "jsonrpc": "2.0", "method": "Counter.total" }]).to_string())
+        .await;
+
+    let response = peer
+        .call(json!([
+            { "jsonrpc": "2.0", "method": "Counter.total", "id": 1 },
+            { "jsonrpc": "2.0", "method": "Counter.add", "params": [1, 1] },
+            { "jsonrpc": "2.0", "method": "Counter.reset", "id": 2 },
+            1,
+            { "jsonrpc": "2.0", "method": "Echo.echo", "params": ["a"], "id": 3 },
+        ]))
+        .await;
+    let responses = response.as_array().unwrap();
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[0]["id"], 1);
+    assert!(responses[0]["result"].is_i64());
+    assert_eq!(error_code(&responses[1]), -32601);
+    assert_eq!(responses[1]["id"], 2);
+    assert_eq!(error_code(&responses[2]), -32600);
+    assert_eq!(responses[2]["id"], Value::Null);
+    assert_eq!(
+        responses[3],
+        json!({ "jsonrpc": "2.0", "result": "a", "id": 3 })
+    );
+
+    // The first notification may still be running.
+    for _ in 0..50 {
+        let response = peer
+            .call(json!({ "jsonrpc": "2.0", "method": "Counter.total", "id": 4 }))
+            .await;
+        if response["result"] == 6 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the notifications were not handled");
+}
+
+#[tokio::test]
+async fn batch_over_the_limit() {
+    let addr = serve_with_limits(Limits {
+        max_concurrent_requests: Some(2),
+        ..Default::default()
+    })
+    .await;
+    let mut peer = Peer::connect(&addr).await;
+
+    // Each call of the batch counts towards the limit.
+    let response = peer
+        .call(json!([
+            { "jsonrpc": "2.0", "method": "Echo.echo", "params": ["a"], "id": 1 },
+            { "jsonrpc": "2.0", "method": "Echo.echo", "params": ["b"], "id": 2 },
+            { "jsonrpc": "2.0", "method": "Echo.echo", "params": ["c"], "id": 3 },
+        ]))
+        .await;
+    let responses = response.as_array().unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["result"], "a");
+    assert_eq!(responses[1]["result"], "b");
+    assert_eq!(error_code(&responses[2]), -32000);
+    assert_eq!(responses[2]["id"], 3);
+}
+
+#[cfg(feature = "websocket")]
+#[tokio::test]
+async fn websocket() {
+    use futures::{SinkExt, StreamExt};
+    use mrpc::net::websocket;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    let config = ServerConfig {
+        jsonrpc: Some(Arc::new(
+            JsonRpc::new(&ServerRequest::descriptor()).unwrap(),
+        )),
+        ..Default::default()
+    };
+    tokio::spawn(Server::serve(Arc::new(ServerImpl::default()), rx));
+    tokio::spawn(websocket::reader_with_listener(listener, tx, config));
+
+    let (mut s, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+        .await
+        .unwrap();
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "method": "Echo.echo",
+        "params": { "value": "ws" },
+        "id": 1,
+    });
+    s.send(WsMessage::Text(request.to_string())).await.unwrap();
+    match s.next().await {
+        Some(Ok(WsMessage::Text(text))) => assert_eq!(
+            serde_json::from_str::<Value>(&text).unwrap(),
+            json!({ "jsonrpc": "2.0", "result": "ws", "id": 1 })
+        ),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn renamed_and_tagged() {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    let config = ServerConfig {
+        jsonrpc: Some(Arc::new(
+            JsonRpc::new(&tagged::ServerRequest::descriptor()).unwrap(),
+        )),
+        ..Default::default()
+    };
+    tokio::spawn(tagged::Server::serve(Arc::new(tagged::ServerImpl), rx));
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+    let mut peer = Peer::connect(&addr).await;
+
+    let response = peer
+        .call(json!({
+            "jsonrpc": "2.0",
+            "method": "Store.get_value",
+            "params": { "key": "a" },
+            "id": 1,
+        }))
+        .await;
+    assert_eq!(
+        response,
+        json!({ "jsonrpc": "2.0", "result": "A", "id": 1 })
+    );
+}
+
+#[test]
+fn refuses_untagged() {
+    let e = JsonRpc::new(&tagged::UntaggedServerRequest::descriptor()).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "the services of `UntaggedServer` can't be called by name, they are untagged"
+    );
+}
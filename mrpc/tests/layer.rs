@@ -0,0 +1,136 @@
+#![cfg(all(feature = "tower", feature = "tcp"))]
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mrpc::{
+    layer::{self, Request},
+    net::{tcp, ServerConfig},
+    sync::mpsc,
+    Error, Payload, RemoteErrorKind,
+};
+use tokio::net::TcpListener;
+use tower::{
+    filter::FilterLayer,
+    util::{MapRequestLayer, MapResponseLayer},
+    ServiceBuilder,
+};
+
+#[mrpc::service(message(serde))]
+trait Greeter {
+    async fn greet(name: String) -> String;
+    async fn sleep(millis: u64);
+}
+
+struct GreeterImpl;
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    async fn greet(self: Arc<Self>, name: String) -> String {
+        format!("Hello {}", name)
+    }
+
+    async fn sleep(self: Arc<Self>, millis: u64) {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Greeter(Greeter),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+}
+
+fn authorize(
+    request: Request<Payload<ServerRequest>>,
+) -> Result<Request<Payload<ServerRequest>>, Error> {
+    match request.metadata.get("token").map(String::as_str) {
+        Some("secret") => Ok(request),
+        _ => Err(Error::Protocol("unauthorized".into())),
+    }
+}
+
+async fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+fn error(e: mrpc::anyhow::Error) -> Error {
+    e.downcast::<Error>().expect("not an mrpc error")
+}
+
+#[tokio::test]
+async fn metadata() {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    let tx = layer::intercept(FilterLayer::new(authorize), tx);
+    tokio::spawn(tcp::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+
+    let unauthorized = ServerClient::new(tcp::writer(&addr).await.unwrap()).greeter();
+    assert_eq!(
+        error(unauthorized.greet("mrpc".into()).await.unwrap_err()),
+        Error::Protocol("unauthorized".into())
+    );
+
+    let token = MapRequestLayer::new(|mut request: Request<Payload<ServerRequest>>| {
+        request.metadata.insert("token".into(), "secret".into());
+        request
+    });
+    let authorized =
+        ServerClient::new(layer::intercept(token, tcp::writer(&addr).await.unwrap())).greeter();
+    assert_eq!(authorized.greet("mrpc".into()).await.unwrap(), "Hello mrpc");
+}
+
+#[tokio::test]
+async fn tower_middleware() {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    let layer = ServiceBuilder::new().timeout(Duration::from_millis(50));
+    tokio::spawn(tcp::reader_with_listener(
+        listener,
+        layer::intercept(layer, tx),
+        ServerConfig::default(),
+    ));
+
+    let greeter = ServerClient::new(tcp::writer(&addr).await.unwrap()).greeter();
+    greeter.sleep(0).await.unwrap();
+    assert_eq!(
+        error(greeter.sleep(1000).await.unwrap_err()),
+        Error::Remote {
+            kind: RemoteErrorKind::Layer,
+            message: "request timed out".into(),
+        }
+    );
+
+    // The layers of the client see the responses as well.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let count = MapResponseLayer::new(move |resp: ServerResponse| {
+        counted.fetch_add(1, Ordering::Relaxed);
+        resp
+    });
+    let greeter =
+        ServerClient::new(layer::intercept(count, tcp::writer(&addr).await.unwrap())).greeter();
+    assert_eq!(greeter.greet("a".into()).await.unwrap(), "Hello a");
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,166 @@
+#![cfg(all(feature = "prometheus", feature = "tcp"))]
+
+use std::{sync::Arc, time::Duration};
+
+use mrpc::{
+    metrics::prometheus,
+    net::{tcp, ServerConfig},
+    sync::mpsc,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+#[mrpc::service(message(serde))]
+trait Greeter {
+    async fn greet(name: String) -> String;
+    fn fail();
+}
+
+struct GreeterImpl;
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    async fn greet(self: Arc<Self>, name: String) -> String {
+        format!("Hello {}", name)
+    }
+
+    fn fail(self: Arc<Self>) {
+        panic!("failed");
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Greeter(Greeter),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+}
+
+async fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+async fn scrape(addr: &str) -> String {
+    let mut s = TcpStream::connect(addr).await.unwrap();
+
+    let req = format!(
+        "GET /metrics HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        addr
+    );
+    s.write_all(req.as_bytes()).await.unwrap();
+    let mut data = String::new();
+    tokio::time::timeout(Duration::from_secs(5), s.read_to_string(&mut data))
+        .await
+        .expect("no response")
+        .unwrap();
+
+    let (head, body) = data.split_once("\r\n\r\n").expect("no body");
+    assert!(head.starts_with("HTTP/1.1 200 "), "{}", head);
+    assert!(head.to_lowercase().contains("content-type: text/plain"));
+    body.to_string()
+}
+
+/// The value of the sample of `name` with all the `labels`.
+fn sample(metrics: &str, name: &str, labels: &[&str]) -> Option<f64> {
+    metrics.lines().find_map(|line| {
+        let (series, value) = line.rsplit_once(' ')?;
+        let (metric, rest) = series.split_once('{')?;
+        if metric != name || !labels.iter().all(|label| rest.contains(label)) {
+            return None;
+        }
+        value.parse().ok()
+    })
+}
+
+#[tokio::test]
+async fn prometheus() {
+    let handle = prometheus::install().unwrap();
+    let (metrics_listener, metrics_addr) = listen().await;
+    tokio::spawn(prometheus::serve_with_listener(metrics_listener, handle));
+
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    tokio::spawn(tcp::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+
+    let greeter = ServerClient::new(tcp::writer(&addr).await.unwrap()).greeter();
+    assert_eq!(greeter.greet("a".into()).await.unwrap(), "Hello a");
+    assert_eq!(greeter.greet("b".into()).await.unwrap(), "Hello b");
+    assert!(greeter.fail().await.is_err());
+
+    // The handlers end right after their response is sent.
+    let mut metrics = String::new();
+    for _ in 0..50 {
+        metrics = scrape(&metrics_addr).await;
+        let in_flight = sample(
+            &metrics,
+            "mrpc_requests_in_flight",
+            &[r#"side="server""#, r#"method="greet""#],
+        );
+        if in_flight == Some(0.0) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    for side in [r#"side="client""#, r#"side="server""#] {
+        let greet = [side, r#"service="Greeter""#, r#"method="greet""#];
+        let fail = [side, r#"service="Greeter""#, r#"method="fail""#];
+        assert_eq!(
+            sample(&metrics, "mrpc_requests_total", &greet),
+            Some(2.0),
+            "{}",
+            metrics
+        );
+        assert_eq!(sample(&metrics, "mrpc_requests_total", &fail), Some(1.0));
+        assert_eq!(
+            sample(&metrics, "mrpc_requests_in_flight", &greet),
+            Some(0.0)
+        );
+        assert_eq!(
+            sample(
+                &metrics,
+                "mrpc_request_duration_seconds_bucket",
+                &[side, r#"method="greet""#, r#"le="+Inf""#]
+            ),
+            Some(2.0)
+        );
+        assert_eq!(
+            sample(&metrics, "mrpc_request_duration_seconds_count", &greet),
+            Some(2.0)
+        );
+        assert_eq!(sample(&metrics, "mrpc_errors_total", &greet), None);
+        assert_eq!(
+            sample(
+                &metrics,
+                "mrpc_errors_total",
+                &[side, r#"method="fail""#, r#"kind="remote""#]
+            ),
+            Some(1.0)
+        );
+    }
+
+    for direction in [r#"direction="in""#, r#"direction="out""#] {
+        let bytes = sample(
+            &metrics,
+            "mrpc_transport_bytes_total",
+            &[r#"transport="tcp""#, direction],
+        );
+        assert!(bytes > Some(0.0), "{}", metrics);
+    }
+}
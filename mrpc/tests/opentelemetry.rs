@@ -0,0 +1,143 @@
+#![cfg(all(feature = "opentelemetry", feature = "tcp", feature = "http"))]
+
+use std::{sync::Arc, time::Duration};
+
+use mrpc::{
+    net::{http, tcp, ServerConfig},
+    sync::mpsc,
+};
+use opentelemetry::trace::{SpanId, TraceId, TracerProvider};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::prelude::*;
+
+#[mrpc::service(message(serde))]
+trait Greeter {
+    async fn greet(name: String) -> String;
+}
+
+struct GreeterImpl;
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    async fn greet(self: Arc<Self>, name: String) -> String {
+        format!("Hello {}", name)
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Greeter(Greeter),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+}
+
+/// Exports the spans of the thread to memory.
+fn export() -> (InMemorySpanExporter, DefaultGuard) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("mrpc"));
+    let guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+    (exporter, guard)
+}
+
+/// The exported span named `name`, waiting for the handlers to end.
+async fn span(exporter: &InMemorySpanExporter, name: &str) -> SpanData {
+    for _ in 0..50 {
+        let spans = exporter.get_finished_spans().unwrap();
+        if let Some(span) = spans.into_iter().find(|span| span.name == name) {
+            return span;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no span {}", name);
+}
+
+#[tokio::test]
+async fn propagation() {
+    let (exporter, _guard) = export();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    tokio::spawn(tcp::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+
+    let client = ServerClient::new(tcp::writer(&addr).await.unwrap());
+    assert_eq!(
+        client.greeter().greet("mrpc".into()).await.unwrap(),
+        "Hello mrpc"
+    );
+
+    let call = span(&exporter, "mrpc.call").await;
+    let handle = span(&exporter, "mrpc.handle").await;
+    assert_eq!(call.parent_span_id, SpanId::INVALID);
+    assert_eq!(handle.span_context.trace_id(), call.span_context.trace_id());
+    assert_eq!(handle.parent_span_id, call.span_context.span_id());
+    assert!(handle.parent_span_is_remote);
+}
+
+#[tokio::test]
+async fn http_headers() {
+    let (exporter, _guard) = export();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    let descriptor = ServerRequest::descriptor();
+    let config = ServerConfig::default();
+    tokio::spawn(
+        async move { http::reader_with_listener(listener, &descriptor, tx, config).await },
+    );
+
+    let mut s = TcpStream::connect(&addr).await.unwrap();
+
+    let body = r#"{"name":"http"}"#;
+    let req = format!(
+        "POST /Greeter/greet HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\
+         traceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\r\n\
+         tracestate: vendor=value\r\n\r\n{}",
+        addr,
+        body.len(),
+        body
+    );
+    s.write_all(req.as_bytes()).await.unwrap();
+    let mut data = String::new();
+    tokio::time::timeout(Duration::from_secs(5), s.read_to_string(&mut data))
+        .await
+        .expect("no response")
+        .unwrap();
+    assert!(data.starts_with("HTTP/1.1 200 "), "{}", data);
+
+    let handle = span(&exporter, "mrpc.handle").await;
+    assert_eq!(
+        handle.span_context.trace_id(),
+        TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+    );
+    assert_eq!(
+        handle.parent_span_id,
+        SpanId::from_hex("00f067aa0ba902b7").unwrap()
+    );
+    assert_eq!(
+        handle.span_context.trace_state().get("vendor"),
+        Some("value")
+    );
+}
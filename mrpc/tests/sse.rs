@@ -0,0 +1,251 @@
+#![cfg(feature = "sse")]
+
+use std::{sync::Arc, time::Duration};
+
+use mrpc::{
+    net::{sse, Hello, JsonRpc, Limits, ServerConfig, PROTOCOL_VERSION},
+    sync::mpsc,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{TcpListener, TcpStream},
+};
+
+#[mrpc::service(message(serde))]
+trait Echo {
+    async fn echo(value: String) -> String;
+}
+
+struct EchoImpl;
+
+#[mrpc::async_trait]
+impl Echo for EchoImpl {
+    async fn echo(self: Arc<Self>, value: String) -> String {
+        value
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Echo(Echo),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_echo(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Echo>> {
+        Ok(Arc::new(EchoImpl))
+    }
+}
+
+/// Serves on a free port and returns its address.
+async fn serve(config: ServerConfig) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    tokio::spawn(sse::reader_with_listener(listener, tx, config));
+    addr
+}
+
+/// An open event stream, read as HTTP/1.0 to avoid the chunked encoding.
+struct Events {
+    lines: Lines<BufReader<TcpStream>>,
+}
+
+impl Events {
+    async fn open(addr: &str) -> (Self, String) {
+        let mut s = TcpStream::connect(addr).await.unwrap();
+        s.write_all(b"GET /rpc HTTP/1.0\r\n\r\n").await.unwrap();
+        let mut events = Self {
+            lines: BufReader::new(s).lines(),
+        };
+
+        let status = events.line().await;
+        assert!(status.contains(" 200 "), "{}", status);
+        let mut headers = Vec::new();
+        loop {
+            let line = events.line().await;
+            if line.is_empty() {
+                break;
+            }
+            headers.push(line.to_lowercase());
+        }
+        assert!(headers.contains(&"content-type: text/event-stream".to_string()));
+
+        let (name, session) = events.next().await;
+        assert_eq!(name.as_deref(), Some("session"));
+        (events, session)
+    }
+
+    async fn line(&mut self) -> String {
+        tokio::time::timeout(Duration::from_secs(5), self.lines.next_line())
+            .await
+            .expect("no event")
+            .unwrap()
+            .expect("event stream closed")
+    }
+
+    /// The name and the data of the next event.
+    async fn next(&mut self) -> (Option<String>, String) {
+        let (mut name, mut data) = (None, Vec::new());
+        loop {
+            let line = self.line().await;
+            if line.is_empty() {
+                return (name, data.join("\n"));
+            }
+            if let Some(value) = line.strip_prefix("event: ") {
+                name = Some(value.to_string());
+            } else if let Some(value) = line.strip_prefix("data: ") {
+                data.push(value.to_string());
+            }
+        }
+    }
+
+    async fn message(&mut self) -> Value {
+        let (name, data) = self.next().await;
+        assert_eq!(name, None);
+        serde_json::from_str(&data).unwrap()
+    }
+}
+
+/// Posts `body` and returns the status of the response.
+async fn post(addr: &str, query: &str, body: &str) -> u16 {
+    let mut s = TcpStream::connect(addr).await.unwrap();
+    let req = format!(
+        "POST /rpc?{} HTTP/1.0\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+        query,
+        body.len(),
+        body
+    );
+    s.write_all(req.as_bytes()).await.unwrap();
+
+    let mut data = String::new();
+    tokio::time::timeout(Duration::from_secs(5), s.read_to_string(&mut data))
+        .await
+        .expect("no response")
+        .unwrap();
+    data.split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+fn echo(id: i64, value: &str) -> String {
+    json!({ "id": id, "value": { "Echo": { "Echo": { "value": value } } } }).to_string()
+}
+
+#[tokio::test]
+async fn sessions() {
+    let addr = serve(ServerConfig::default()).await;
+
+    let (mut events, session) = Events::open(&addr).await;
+    let query = format!("session={}", session);
+
+    assert_eq!(post(&addr, &query, &echo(1, "a")).await, 202);
+    assert_eq!(
+        events.message().await,
+        json!({ "id": 1, "value": { "Ok": { "Echo": { "Echo": "a" } } } })
+    );
+
+    assert_eq!(post(&addr, &query, "{").await, 202);
+    let response = events.message().await;
+    assert_eq!(response["id"], Value::Null);
+    assert!(response["value"]["Err"]["Protocol"].is_string());
+
+    // Another session doesn't see the responses of the first.
+    let (mut other, other_session) = Events::open(&addr).await;
+    assert_ne!(session, other_session);
+    let other_query = format!("session={}", other_session);
+    assert_eq!(post(&addr, &other_query, &echo(1, "b")).await, 202);
+    assert_eq!(post(&addr, &query, &echo(2, "c")).await, 202);
+    assert_eq!(other.message().await["value"]["Ok"]["Echo"]["Echo"], "b");
+    assert_eq!(events.message().await["id"], 2);
+
+    // The session ends with its event stream.
+    drop(events);
+    for _ in 0..50 {
+        if post(&addr, &query, &echo(3, "d")).await == 404 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the session outlived its event stream");
+}
+
+#[tokio::test]
+async fn errors() {
+    let addr = serve(ServerConfig {
+        limits: Limits {
+            max_frame_size: 64,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    assert_eq!(post(&addr, "session=1", &echo(1, "a")).await, 404);
+    assert_eq!(post(&addr, "", &echo(1, "a")).await, 404);
+
+    let (mut events, session) = Events::open(&addr).await;
+    let query = format!("session={}", session);
+    assert_eq!(post(&addr, &query, &echo(1, &"a".repeat(100))).await, 413);
+    assert_eq!(
+        events.message().await,
+        json!({ "id": null, "value": { "Err": { "LimitExceeded": "FrameSize" } } })
+    );
+}
+
+#[tokio::test]
+async fn jsonrpc() {
+    let addr = serve(ServerConfig {
+        jsonrpc: Some(Arc::new(
+            JsonRpc::new(&ServerRequest::descriptor()).unwrap(),
+        )),
+        ..Default::default()
+    })
+    .await;
+
+    let (mut events, session) = Events::open(&addr).await;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "method": "Echo.echo",
+        "params": ["sse"],
+        "id": 1,
+    });
+    assert_eq!(
+        post(&addr, &format!("session={}", session), &request.to_string()).await,
+        202
+    );
+    assert_eq!(
+        events.message().await,
+        json!({ "jsonrpc": "2.0", "result": "sse", "id": 1 })
+    );
+}
+
+/// The handshake of `sse::connect_with_config` in the browser.
+#[tokio::test]
+async fn handshake() {
+    let addr = serve(ServerConfig {
+        handshake: Some(Hello::default()),
+        ..Default::default()
+    })
+    .await;
+
+    let (mut events, session) = Events::open(&addr).await;
+    let query = format!("session={}", session);
+    let hello = json!({ "hello": Hello::default() });
+    assert_eq!(post(&addr, &query, &hello.to_string()).await, 202);
+    assert_eq!(
+        events.message().await,
+        json!({ "accept": { "protocol": PROTOCOL_VERSION } })
+    );
+
+    assert_eq!(post(&addr, &query, &echo(1, "a")).await, 202);
+    assert_eq!(events.message().await["value"]["Ok"]["Echo"]["Echo"], "a");
+
+    // A session without the hello is refused.
+    let (mut events, session) = Events::open(&addr).await;
+    let query = format!("session={}", session);
+    assert_eq!(post(&addr, &query, &echo(1, "a")).await, 202);
+    assert!(events.message().await["value"]["Err"]["Incompatible"].is_string());
+}
@@ -0,0 +1,350 @@
+#![cfg(feature = "tcp")]
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use mrpc::{
+    net::{tcp, ClientConfig, Hello, Limits, RpcResponse, ServerConfig, PROTOCOL_VERSION},
+    sync::{mpsc, oneshot},
+    trace::Span,
+    Connection, Error, Limit, Message,
+};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+/// A listener on a free port, and its address.
+async fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+async fn connect(addr: &str) -> Framed<TcpStream, LengthDelimitedCodec> {
+    let s = TcpStream::connect(addr).await.unwrap();
+    Framed::new(s, LengthDelimitedCodec::new())
+}
+
+#[tokio::test]
+async fn malformed_frames() {
+    let (listener, addr) = listen().await;
+    let (tx, mut rx) = mpsc::channel::<Message<i32, i32>>(32);
+    let config = ServerConfig {
+        max_protocol_errors: Some(2),
+        ..Default::default()
+    };
+    let stats = config.stats.clone();
+
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let _ = msg.resp.send(Ok(msg.req * 2));
+        }
+    });
+
+    let mut s = connect(&addr).await;
+
+    s.send(Bytes::from(r#"{"id":1,"value":21}"#)).await.unwrap();
+    let resp: RpcResponse<i32> = serde_json::from_slice(&s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.id, Some(1));
+    assert_eq!(resp.value, Ok(42));
+
+    s.send(Bytes::from(r#"{"id":2,"value":"x"}"#))
+        .await
+        .unwrap();
+    let resp: RpcResponse<i32> = serde_json::from_slice(&s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.id, Some(2));
+    assert!(matches!(resp.value, Err(Error::Protocol(_))));
+
+    s.send(Bytes::from("garbage")).await.unwrap();
+    let resp: RpcResponse<i32> = serde_json::from_slice(&s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.id, None);
+    assert!(matches!(resp.value, Err(Error::Protocol(_))));
+
+    assert!(s.next().await.is_none());
+    assert_eq!(stats.malformed_frames(), 2);
+    assert_eq!(stats.closed_connections(), 1);
+}
+
+#[tokio::test]
+async fn limits() {
+    let (listener, addr) = listen().await;
+    let (tx, mut rx) = mpsc::channel::<Message<i32, i32>>(32);
+    let config = ServerConfig {
+        limits: Limits {
+            max_frame_size: 64,
+            max_concurrent_requests: Some(1),
+            max_connections: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let stats = config.stats.clone();
+
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+    let (hold_tx, hold_rx) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let mut hold_rx = Some(hold_rx);
+        while let Some(msg) = rx.recv().await {
+            if let Some(hold_rx) = hold_rx.take() {
+                let _ = hold_rx.await;
+            }
+            let _ = msg.resp.send(Ok(msg.req));
+        }
+    });
+
+    let mut s = connect(&addr).await;
+
+    s.send(Bytes::from(r#"{"id":1,"value":1}"#)).await.unwrap();
+    s.send(Bytes::from(r#"{"id":2,"value":2}"#)).await.unwrap();
+    let resp: RpcResponse<i32> = serde_json::from_slice(&s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.id, Some(2));
+    assert_eq!(
+        resp.value,
+        Err(Error::LimitExceeded(Limit::ConcurrentRequests))
+    );
+
+    let mut other = connect(&addr).await;
+    let resp: RpcResponse<i32> =
+        serde_json::from_slice(&other.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.value, Err(Error::LimitExceeded(Limit::Connections)));
+    assert!(other.next().await.is_none());
+    assert_eq!(stats.rejected_connections(), 1);
+
+    hold_tx.send(()).unwrap();
+    let resp: RpcResponse<i32> = serde_json::from_slice(&s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.id, Some(1));
+    assert_eq!(resp.value, Ok(1));
+
+    s.send(Bytes::from(vec![b' '; 100])).await.unwrap();
+    let resp: RpcResponse<i32> = serde_json::from_slice(&s.next().await.unwrap().unwrap()).unwrap();
+    assert_eq!(resp.value, Err(Error::LimitExceeded(Limit::FrameSize)));
+    assert!(s.next().await.is_none());
+    assert_eq!(stats.oversized_frames(), 1);
+}
+
+#[tokio::test]
+async fn pending_responses() {
+    let (listener, addr) = listen().await;
+    let (tx, mut rx) = mpsc::channel::<Message<i32, String>>(32);
+    let config = ServerConfig {
+        limits: Limits {
+            max_pending_responses: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let stats = config.stats.clone();
+
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let _ = msg.resp.send(Ok("x".repeat(1024 * 1024)));
+        }
+    });
+
+    let mut s = connect(&addr).await;
+    s.send(Bytes::from(r#"{"id":0,"value":0}"#)).await.unwrap();
+    assert!(s.next().await.unwrap().is_ok());
+    assert_eq!(stats.active_connections(), 1);
+
+    // The peer stops reading, so the responses fill the socket, then the
+    // queue, and the server gives up on it.
+    for id in 1..64 {
+        let req = format!(r#"{{"id":{},"value":{}}}"#, id, id);
+        if s.send(Bytes::from(req)).await.is_err() {
+            break;
+        }
+    }
+
+    for _ in 0..250 {
+        if stats.active_connections() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(stats.active_connections(), 0);
+}
+
+async fn echo_server(handshake: Option<Hello>) -> String {
+    let (listener, addr) = listen().await;
+    let (tx, mut rx) = mpsc::channel::<Message<i32, i32>>(32);
+    let config = ServerConfig {
+        handshake,
+        ..Default::default()
+    };
+
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let _ = msg.resp.send(Ok(msg.req));
+        }
+    });
+    addr
+}
+
+async fn client(
+    addr: &str,
+    handshake: Option<Hello>,
+) -> anyhow::Result<mpsc::Sender<Message<i32, i32>>> {
+    tcp::writer_with_config(addr, ClientConfig { handshake }).await
+}
+
+async fn call(client: &mpsc::Sender<Message<i32, i32>>, req: i32) -> Result<i32, Error> {
+    let (tx, rx) = oneshot::channel();
+    let msg = Message {
+        req,
+        resp: tx,
+        conn: Connection::new(None),
+        span: Span::none(),
+        metadata: Default::default(),
+    };
+    assert!(client.send(msg).await.is_ok(), "client closed");
+    rx.await.unwrap()
+}
+
+#[tokio::test]
+async fn handshake() {
+    let addr = echo_server(Some(Hello {
+        descriptor: Some("a".into()),
+        ..Default::default()
+    }))
+    .await;
+
+    let same = client(
+        &addr,
+        Some(Hello {
+            descriptor: Some("a".into()),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(call(&same, 7).await, Ok(7));
+
+    let unchecked = client(&addr, Some(Hello::default())).await.unwrap();
+    assert_eq!(call(&unchecked, 8).await, Ok(8));
+
+    let e = client(
+        &addr,
+        Some(Hello {
+            descriptor: Some("b".into()),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        e.downcast::<Error>().unwrap(),
+        Error::Incompatible("service descriptors differ: b instead of a".into())
+    );
+
+    let e = client(
+        &addr,
+        Some(Hello {
+            protocol: PROTOCOL_VERSION + 1,
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert!(e.to_string().contains("protocol version"));
+
+    let legacy = client(&addr, None).await.unwrap();
+    assert_eq!(
+        call(&legacy, 1).await,
+        Err(Error::Incompatible("the server expects a handshake".into()))
+    );
+}
+
+#[tokio::test]
+async fn handshake_timeout() {
+    let (listener, addr) = listen().await;
+    let (tx, _rx) = mpsc::channel::<Message<i32, i32>>(32);
+    let config = ServerConfig {
+        handshake: Some(Hello::default()),
+        limits: Limits {
+            handshake_timeout: Duration::from_millis(100),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    tokio::spawn(tcp::reader_with_listener(listener, tx, config));
+
+    // A client which never says hello is disconnected.
+    let mut s = connect(&addr).await;
+    let next = tokio::time::timeout(Duration::from_secs(5), s.next()).await;
+    assert!(next.expect("still connected").is_none());
+}
+
+#[tokio::test]
+async fn handshake_without_server_support() {
+    let addr = echo_server(None).await;
+
+    let e = client(&addr, Some(Hello::default())).await.unwrap_err();
+    assert_eq!(
+        e.downcast::<Error>().unwrap(),
+        Error::Incompatible("the server does not expect a handshake".into())
+    );
+}
+
+static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Blob(Vec<u8>);
+
+impl Clone for Blob {
+    fn clone(&self) -> Self {
+        CLONES.fetch_add(1, Ordering::Relaxed);
+        Blob(self.0.clone())
+    }
+}
+
+#[mrpc::service(message(serde))]
+trait Store {
+    #[rpc(borrow)]
+    async fn put(&self, blob: Blob) -> usize;
+}
+
+struct StoreImpl;
+
+#[mrpc::async_trait]
+impl Store for StoreImpl {
+    async fn put(&self, blob: Blob) -> usize {
+        blob.0.len()
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Store(Store),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_store(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Store>> {
+        Ok(Arc::new(StoreImpl))
+    }
+}
+
+#[tokio::test]
+async fn borrowed_requests_are_not_cloned() {
+    let (listener, addr) = listen().await;
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    tokio::spawn(tcp::reader_with_listener(listener, tx, Default::default()));
+
+    let store = ServerClient::new(tcp::writer(&addr).await.unwrap()).store();
+    let blob = Blob(vec![1, 2, 3]);
+    assert_eq!(store.put(&blob).await.unwrap(), 3);
+    assert_eq!(CLONES.load(Ordering::Relaxed), 0);
+}
@@ -0,0 +1,186 @@
+#![cfg(all(feature = "tracing", feature = "tcp"))]
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use mrpc::{
+    net::{tcp, ServerConfig},
+    sync::mpsc,
+};
+use tokio::net::TcpListener;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+#[mrpc::service(message(serde))]
+trait Greeter {
+    async fn greet(name: String) -> String;
+    fn fail();
+}
+
+struct GreeterImpl;
+
+#[mrpc::async_trait]
+impl Greeter for GreeterImpl {
+    async fn greet(self: Arc<Self>, name: String) -> String {
+        tracing::info!(%name, "greeting");
+        format!("Hello {}", name)
+    }
+
+    fn fail(self: Arc<Self>) {
+        panic!("failed");
+    }
+}
+
+#[mrpc::server(message(serde))]
+enum Server {
+    Greeter(Greeter),
+}
+
+struct ServerImpl;
+
+#[mrpc::async_trait]
+impl Server for ServerImpl {
+    async fn create_greeter(self: Arc<Self>) -> mrpc::anyhow::Result<Arc<dyn Greeter>> {
+        Ok(Arc::new(GreeterImpl))
+    }
+}
+
+type Fields = HashMap<String, String>;
+
+struct Visitor<'a>(&'a mut Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().into(), format!("{:?}", value));
+    }
+}
+
+#[derive(Default)]
+struct Recorded {
+    spans: Vec<(&'static str, Fields)>,
+    /// The index in `spans` of the live spans, whose ids are reused.
+    live: HashMap<u64, usize>,
+    /// The events, with the index of the span they belong to.
+    events: Vec<(Fields, Option<usize>)>,
+}
+
+/// Records the fields of the spans and the events, and where the events
+/// were emitted.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Recorded>>);
+
+impl<S> Layer<S> for Recorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+        let mut fields = Fields::new();
+        attrs.record(&mut Visitor(&mut fields));
+        let mut recorded = self.0.lock().unwrap();
+        recorded.spans.push((attrs.metadata().name(), fields));
+        let index = recorded.spans.len() - 1;
+        recorded.live.insert(id.into_u64(), index);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+        let mut recorded = self.0.lock().unwrap();
+        let index = recorded.live[&id.into_u64()];
+        values.record(&mut Visitor(&mut recorded.spans[index].1));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::new();
+        event.record(&mut Visitor(&mut fields));
+        let mut recorded = self.0.lock().unwrap();
+        let span = ctx
+            .event_span(event)
+            .map(|span| recorded.live[&span.id().into_u64()]);
+        recorded.events.push((fields, span));
+    }
+}
+
+impl Recorder {
+    fn spans(&self, name: &str) -> Vec<Fields> {
+        let recorded = self.0.lock().unwrap();
+        recorded
+            .spans
+            .iter()
+            .filter(|(span, _)| *span == name)
+            .map(|(_, fields)| fields.clone())
+            .collect()
+    }
+}
+
+fn field<'a>(fields: &'a Fields, name: &str) -> Option<&'a str> {
+    fields.get(name).map(String::as_str)
+}
+
+#[tokio::test]
+async fn spans() {
+    let recorder = Recorder::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(Server::serve(Arc::new(ServerImpl), rx));
+    tokio::spawn(tcp::reader_with_listener(
+        listener,
+        tx,
+        ServerConfig::default(),
+    ));
+
+    let client = ServerClient::new(tcp::writer(&addr).await.unwrap());
+
+    let greeter = client.greeter();
+    assert_eq!(greeter.greet("mrpc".into()).await.unwrap(), "Hello mrpc");
+    assert!(greeter.fail().await.is_err());
+
+    let calls = recorder.spans("mrpc.call");
+    assert_eq!(calls.len(), 2);
+    assert_eq!(field(&calls[0], "service"), Some("Greeter"));
+    assert_eq!(field(&calls[0], "method"), Some("greet"));
+    assert_eq!(field(&calls[0], "request_id"), Some("0"));
+    assert_eq!(field(&calls[0], "peer"), Some(addr.as_str()));
+    assert_eq!(field(&calls[0], "outcome"), Some("ok"));
+    assert_eq!(field(&calls[1], "request_id"), Some("1"));
+    assert_eq!(field(&calls[1], "outcome"), Some("error"));
+
+    let handles = recorder.spans("mrpc.handle");
+    assert_eq!(handles.len(), 2);
+    assert_eq!(field(&handles[0], "service"), Some("Greeter"));
+    assert_eq!(field(&handles[0], "method"), Some("greet"));
+    assert_eq!(field(&handles[0], "request_id"), Some("0"));
+    assert!(handles[0].contains_key("peer"));
+    assert_eq!(field(&handles[0], "outcome"), Some("ok"));
+    assert_eq!(field(&handles[1], "method"), Some("fail"));
+    assert_eq!(field(&handles[1], "outcome"), Some("error"));
+    assert_eq!(
+        field(&handles[1], "error"),
+        Some("service panicked: failed")
+    );
+
+    // The events of the services belong to the handler span.
+    let recorded = recorder.0.lock().unwrap();
+    let (fields, span) = recorded
+        .events
+        .iter()
+        .find(|(fields, _)| field(fields, "message") == Some("greeting"))
+        .expect("no event");
+    assert_eq!(field(fields, "name"), Some("mrpc"));
+    let (name, fields) = &recorded.spans[span.expect("event outside of a span")];
+    assert_eq!(*name, "mrpc.handle");
+    assert_eq!(field(fields, "method"), Some("greet"));
+}